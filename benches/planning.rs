@@ -0,0 +1,40 @@
+//! Planning time for very large books (book values in cents).
+//!
+//! `cargo bench --bench planning`; panics if planning a 1e12-MU population takes a second.
+
+use audit_sampling::{mus_planning, PlanningModel, PlanningOptions};
+use std::time::{Duration, Instant};
+
+const RUNS: usize = 5;
+
+fn median_time(data: &[f64], opts: &PlanningOptions) -> (usize, Duration) {
+    let mut times = Vec::with_capacity(RUNS);
+    let mut n = 0;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        n = mus_planning(data, opts.clone()).expect("planning").n;
+        times.push(start.elapsed());
+    }
+    times.sort();
+    (n, times[RUNS / 2])
+}
+
+fn main() {
+    for book_value in [1e9, 1e11, 1e12] {
+        let data = vec![book_value / 100_000.0; 100_000];
+        for (te_rate, ee_ratio) in [(0.05, 0.0), (0.05, 0.25), (0.01, 0.5), (0.002, 0.7)] {
+            for model in [PlanningModel::Hypergeometric, PlanningModel::Binomial, PlanningModel::Poisson] {
+                let opts = PlanningOptions {
+                    confidence_level: 0.95,
+                    tolerable_error: book_value * te_rate,
+                    expected_error: book_value * te_rate * ee_ratio,
+                    model,
+                    ..PlanningOptions::default()
+                };
+                let (n, time) = median_time(&data, &opts);
+                println!("BV={book_value:e} TE={te_rate} EE/TE={ee_ratio} {model:?}: n={n} in {time:?}");
+                assert!(time < Duration::from_secs(1), "planning took {time:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,172 @@
+//! Plans and extractions as versioned JSON documents, so a plan can be reviewed and approved
+//! before extraction and the extraction archived with the working papers.
+//!
+//! A document is the value's own JSON object with two header fields in front:
+//! `{"schema_version": 1, "kind": "plan", "data": [...], "n": 52, ...}`. Readers accept any
+//! `schema_version` up to `SCHEMA_VERSION` and reject newer ones instead of guessing.
+
+use crate::{Extraction, MusError, Plan};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// Version written to new documents; bump it whenever a field changes meaning or is removed.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct Document<'a, T> {
+    schema_version: u32,
+    kind: &'static str,
+    #[serde(flatten)]
+    data: &'a T,
+}
+
+#[derive(Deserialize)]
+struct Header {
+    schema_version: u32,
+    kind: String,
+}
+
+#[derive(Deserialize)]
+struct Body<T> {
+    #[serde(flatten)]
+    data: T,
+}
+
+fn to_json<T: Serialize>(kind: &'static str, value: &T) -> Result<String, MusError> {
+    serde_json::to_string_pretty(&Document { schema_version: SCHEMA_VERSION, kind, data: value })
+        .map_err(|e| MusError::Calculation(format!("{kind} JSON: {e}")))
+}
+
+fn from_json<T: DeserializeOwned>(kind: &'static str, text: &str) -> Result<T, MusError> {
+    let header: Header = serde_json::from_str(text).map_err(|e| MusError::InvalidInput(format!("{kind} JSON: {e}")))?;
+    if header.kind != kind {
+        return Err(MusError::InvalidInput(format!("expected a {kind} document, found {}", header.kind)));
+    }
+    if header.schema_version > SCHEMA_VERSION {
+        return Err(MusError::InvalidInput(format!(
+            "{kind} schema version {} is newer than the supported version {SCHEMA_VERSION}",
+            header.schema_version
+        )));
+    }
+    let body: Body<T> = serde_json::from_str(text).map_err(|e| MusError::InvalidInput(format!("{kind} JSON: {e}")))?;
+    Ok(body.data)
+}
+
+impl Plan {
+    pub fn to_json(&self) -> Result<String, MusError> {
+        to_json("plan", self)
+    }
+
+    pub fn from_json(text: &str) -> Result<Plan, MusError> {
+        let plan: Plan = from_json("plan", text)?;
+        if plan.ids.len() != plan.data.len() {
+            return Err(MusError::InvalidInput("plan.ids must match plan.data".into()));
+        }
+        Ok(plan)
+    }
+}
+
+impl Extraction {
+    pub fn to_json(&self) -> Result<String, MusError> {
+        to_json("extraction", self)
+    }
+
+    pub fn from_json(text: &str) -> Result<Extraction, MusError> {
+        let extract: Extraction = from_json("extraction", text)?;
+        let n = extract.plan.data.len();
+        if extract.plan.ids.len() != n || extract.sample.iter().any(|s| s.index >= n) || extract.high_values.iter().any(|h| h.index >= n) {
+            return Err(MusError::InvalidInput("extraction items must refer to plan.data".into()));
+        }
+        Ok(extract)
+    }
+}
+
+/// Serde for `f64` fields that may be infinite or NaN (an empty plan's interval, unset planning
+/// errors): JSON numbers when finite, otherwise the strings `"inf"`, `"-inf"` and `"nan"`.
+pub(crate) mod float {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Number(f64),
+        Text(String),
+    }
+
+    impl From<f64> for Repr {
+        fn from(v: f64) -> Self {
+            match v {
+                _ if v.is_finite() => Repr::Number(v),
+                _ if v.is_nan() => Repr::Text("nan".into()),
+                _ if v > 0.0 => Repr::Text("inf".into()),
+                _ => Repr::Text("-inf".into()),
+            }
+        }
+    }
+
+    impl TryFrom<Repr> for f64 {
+        type Error = String;
+        fn try_from(r: Repr) -> Result<f64, String> {
+            match r {
+                Repr::Number(v) => Ok(v),
+                Repr::Text(t) => match t.as_str() {
+                    "nan" => Ok(f64::NAN),
+                    "inf" => Ok(f64::INFINITY),
+                    "-inf" => Ok(f64::NEG_INFINITY),
+                    _ => Err(format!("invalid number {t:?}")),
+                },
+            }
+        }
+    }
+
+    pub(crate) fn serialize<S: Serializer>(v: &f64, s: S) -> Result<S::Ok, S::Error> {
+        Repr::from(*v).serialize(s)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<f64, D::Error> {
+        f64::try_from(Repr::deserialize(d)?).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn plan_and_extraction_round_trip() {
+        let data: Vec<f64> = (0..400u64).map(|i| (i * 37 % 500) as f64 + 0.25).collect();
+        let opts = PlanningOptions { tolerable_error: 5_000.0, expected_error: 500.0, currency: Currency::CNY, ..PlanningOptions::default() };
+        let plan = mus_planning(&data, opts).expect("plan");
+        let text = plan.to_json().expect("json");
+        assert!(text.contains("\"schema_version\": 1") && text.contains("\"kind\": \"plan\""));
+        let loaded = Plan::from_json(&text).expect("load");
+        assert_eq!((loaded.n, loaded.currency, &loaded.data, &loaded.ids), (plan.n, plan.currency, &plan.data, &plan.ids));
+        // Extracting from the saved plan gives the same sample as from the original
+        let opts = ExtractionOptions { seed: Some(11), ..ExtractionOptions::default() };
+        let ext = mus_extraction(&plan, opts.clone()).expect("extract");
+        let again = mus_extraction(&loaded, opts).expect("extract");
+        let hits = |e: &Extraction| e.sample.iter().map(|s| (s.index, s.mus_hit)).collect::<Vec<_>>();
+        assert_eq!(hits(&ext), hits(&again));
+        let archived = Extraction::from_json(&ext.to_json().expect("json")).expect("load");
+        assert_eq!(hits(&archived), hits(&ext));
+        assert_eq!(archived.sampling_interval, ext.sampling_interval);
+        // Every field is required: a document without the interval or the replanning options is refused
+        let without = |text: &str, field: &str| {
+            let mut value: serde_json::Value = serde_json::from_str(text).expect("json");
+            value.as_object_mut().expect("object").remove(field);
+            value.to_string()
+        };
+        assert!(Extraction::from_json(&without(&ext.to_json().expect("json"), "interval")).is_err());
+        assert!(Plan::from_json(&without(&text, "n_min")).is_err());
+        assert!(Plan::from_json(&without(&text, "conservative")).is_err());
+        // Wrong kind and newer schema versions are refused
+        assert!(Extraction::from_json(&text).is_err());
+        assert!(Plan::from_json(&text.replacen("\"schema_version\": 1", "\"schema_version\": 2", 1)).is_err());
+        // Missing values and the infinite interval of an empty plan survive JSON
+        let empty = mus_planning(&[1.0, f64::NAN, 3.0], PlanningOptions { tolerable_error: 10.0, expected_error: 0.0, ..PlanningOptions::default() }).expect("plan");
+        let loaded = Plan::from_json(&empty.to_json().expect("json")).expect("load");
+        assert_eq!(loaded.data[1], Money(0));
+        assert_eq!(loaded.high_value_threshold, f64::INFINITY);
+        assert_eq!(loaded.warnings, empty.warnings);
+    }
+}
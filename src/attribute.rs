@@ -0,0 +1,286 @@
+//! Attribute sampling for tests of controls: sample size planning and evaluation
+//!
+//! The sample size is the smallest `n` for which observing the expected number of
+//! deviations `k = ceil(expected_deviation_rate × n)` still rejects a true deviation
+//! rate at the tolerable rate with the given confidence. Evaluation returns the exact
+//! one-sided upper deviation limit for the deviations found.
+
+use crate::sample_size::min_draws_for_cdf_at_most_q;
+use crate::{Conclusion, MusError};
+use statrs::distribution::{Beta, Binomial, ContinuousCDF, DiscreteCDF, Gamma, Hypergeometric, Poisson};
+
+/// Upper limit for the sample size search of the binomial and Poisson models.
+const MAX_ATTRIBUTE_N: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AttributeModel {
+    /// Infinite population, deviations ~ Binomial(n, p).
+    #[default]
+    Binomial,
+    /// Poisson approximation, deviations ~ Poisson(n p).
+    Poisson,
+    /// Finite population of `population_size` items, deviations ~ Hypergeometric.
+    Hypergeometric,
+}
+
+#[derive(Debug, Clone)]
+pub struct AttributePlanningOptions {
+    pub model: AttributeModel,
+    pub confidence_level: f64,
+    pub tolerable_deviation_rate: f64,
+    pub expected_deviation_rate: f64,
+    /// Required by the hypergeometric model; caps `n` for the others.
+    pub population_size: Option<usize>,
+}
+
+impl Default for AttributePlanningOptions {
+    fn default() -> Self {
+        Self {
+            model: AttributeModel::default(),
+            confidence_level: 0.90,
+            tolerable_deviation_rate: f64::NAN,
+            expected_deviation_rate: 0.0,
+            population_size: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AttributePlan {
+    pub model: AttributeModel,
+    pub confidence_level: f64,
+    pub tolerable_deviation_rate: f64,
+    pub expected_deviation_rate: f64,
+    pub population_size: Option<usize>,
+    pub n: usize,
+    /// Number of deviations that can be found while still supporting reliance.
+    pub allowable_deviations: u64,
+}
+
+fn tail_at_most(model: AttributeModel, k: u64, n: usize, rate: f64) -> Result<f64, MusError> {
+    match model {
+        AttributeModel::Binomial => {
+            let d = Binomial::new(rate, n as u64).map_err(|e| MusError::Calculation(format!("binomial: {e}")))?;
+            Ok(d.cdf(k))
+        }
+        AttributeModel::Poisson => {
+            let d = Poisson::new(rate * n as f64).map_err(|e| MusError::Calculation(format!("poisson: {e}")))?;
+            Ok(d.cdf(k))
+        }
+        AttributeModel::Hypergeometric => unreachable!("hypergeometric uses min_draws_for_cdf_at_most_q"),
+    }
+}
+
+pub fn attribute_planning(opts: AttributePlanningOptions) -> Result<AttributePlan, MusError> {
+    if !(opts.confidence_level > 0.0 && opts.confidence_level < 1.0) {
+        return Err(MusError::InvalidInput("confidence_level must be in (0,1)".into()));
+    }
+    let tdr = opts.tolerable_deviation_rate;
+    let edr = opts.expected_deviation_rate;
+    if !(tdr > 0.0 && tdr < 1.0) {
+        return Err(MusError::InvalidInput("tolerable_deviation_rate must be in (0,1)".into()));
+    }
+    if !(0.0..tdr).contains(&edr) {
+        return Err(MusError::InvalidInput("expected_deviation_rate must be in [0, tolerable_deviation_rate)".into()));
+    }
+    if opts.population_size == Some(0) {
+        return Err(MusError::InvalidInput("population_size must be > 0".into()));
+    }
+    let alpha = 1.0 - opts.confidence_level;
+    let allowable = |n: usize| (edr * n as f64).ceil() as u64;
+
+    let (n, k) = match opts.model {
+        AttributeModel::Hypergeometric => {
+            let big_n = opts
+                .population_size
+                .ok_or_else(|| MusError::InvalidInput("population_size is required for the hypergeometric model".into()))?;
+            let m = (tdr * big_n as f64).ceil() as u64;
+            let n_black = big_n as u64 - m;
+            // Iterate k = ceil(EDR·n) until the sample size is stable (n grows with k)
+            let mut k = 0u64;
+            loop {
+                if k >= m {
+                    return Err(MusError::Calculation("expected deviations reach the tolerable deviations in the population".into()));
+                }
+                let n = min_draws_for_cdf_at_most_q(k, alpha, m, n_black, big_n as u64)? as usize;
+                let k_new = allowable(n);
+                if k_new <= k {
+                    break (n, k);
+                }
+                k = k_new;
+            }
+        }
+        model => {
+            let cap = opts.population_size.unwrap_or(MAX_ATTRIBUTE_N).min(MAX_ATTRIBUTE_N);
+            let mut found = None;
+            for n in 1..=cap {
+                let k = allowable(n);
+                if tail_at_most(model, k, n, tdr)? <= alpha {
+                    found = Some((n, k));
+                    break;
+                }
+            }
+            match (found, opts.population_size) {
+                (Some(f), _) => f,
+                (None, Some(big_n)) if big_n <= MAX_ATTRIBUTE_N => (big_n, allowable(big_n)),
+                (None, _) => return Err(MusError::Calculation(format!("no sample size up to {MAX_ATTRIBUTE_N} reaches the confidence level"))),
+            }
+        }
+    };
+    Ok(AttributePlan {
+        model: opts.model,
+        confidence_level: opts.confidence_level,
+        tolerable_deviation_rate: tdr,
+        expected_deviation_rate: edr,
+        population_size: opts.population_size,
+        n,
+        allowable_deviations: k,
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct AttributeEvaluationOptions {
+    pub model: AttributeModel,
+    pub confidence_level: f64,
+    pub tolerable_deviation_rate: f64,
+    /// Required by the hypergeometric model.
+    pub population_size: Option<usize>,
+}
+
+impl Default for AttributeEvaluationOptions {
+    fn default() -> Self {
+        Self { model: AttributeModel::default(), confidence_level: 0.90, tolerable_deviation_rate: f64::NAN, population_size: None }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AttributeEvaluation {
+    pub model: AttributeModel,
+    pub confidence_level: f64,
+    pub tolerable_deviation_rate: f64,
+    pub n: usize,
+    pub deviations: u64,
+    pub sample_deviation_rate: f64,
+    pub upper_deviation_limit: f64,
+    /// Upper deviation limit − sample deviation rate.
+    pub allowance_for_sampling_risk: f64,
+    /// `Accept` (reliance on the control is supported) when the upper deviation limit
+    /// does not exceed the tolerable deviation rate.
+    pub conclusion: Conclusion,
+}
+
+/// Largest number of deviating items `M` in a population of `big_n` that is not rejected
+/// by observing `k` deviations in `n` draws, i.e. `P[X ≤ k | M] > alpha`.
+fn hypergeometric_upper_count(big_n: u64, n: u64, k: u64, alpha: f64) -> Result<u64, MusError> {
+    let cdf = |m: u64| -> Result<f64, MusError> {
+        let d = Hypergeometric::new(big_n, m, n).map_err(|e| MusError::Calculation(format!("hypergeometric new: {e}")))?;
+        Ok(d.cdf(k))
+    };
+    // Every sampled non-deviation is a non-deviating item of the population
+    let mut lo = k;
+    let mut hi = big_n - (n - k);
+    while lo < hi {
+        let mid = lo + (hi - lo).div_ceil(2);
+        if cdf(mid)? > alpha { lo = mid; } else { hi = mid - 1; }
+    }
+    Ok(lo)
+}
+
+pub fn attribute_evaluation(n: usize, deviations: u64, opts: AttributeEvaluationOptions) -> Result<AttributeEvaluation, MusError> {
+    if !(opts.confidence_level > 0.0 && opts.confidence_level < 1.0) {
+        return Err(MusError::InvalidInput("confidence_level must be in (0,1)".into()));
+    }
+    if !(opts.tolerable_deviation_rate > 0.0 && opts.tolerable_deviation_rate < 1.0) {
+        return Err(MusError::InvalidInput("tolerable_deviation_rate must be in (0,1)".into()));
+    }
+    if n == 0 {
+        return Err(MusError::InvalidInput("sample is empty; nothing to evaluate".into()));
+    }
+    if deviations > n as u64 {
+        return Err(MusError::InvalidInput("deviations must not exceed the sample size".into()));
+    }
+    let conf = opts.confidence_level;
+    let k = deviations;
+    let upper = match opts.model {
+        AttributeModel::Binomial if k == n as u64 => 1.0,
+        AttributeModel::Binomial => {
+            // Clopper–Pearson one-sided upper limit
+            let b = Beta::new(k as f64 + 1.0, (n as u64 - k) as f64).map_err(|e| MusError::Calculation(format!("beta: {e}")))?;
+            b.inverse_cdf(conf)
+        }
+        AttributeModel::Poisson => {
+            let g = Gamma::new(k as f64 + 1.0, 1.0).map_err(|e| MusError::Calculation(format!("gamma: {e}")))?;
+            (g.inverse_cdf(conf) / n as f64).min(1.0)
+        }
+        AttributeModel::Hypergeometric => {
+            let big_n = opts
+                .population_size
+                .ok_or_else(|| MusError::InvalidInput("population_size is required for the hypergeometric model".into()))?;
+            if big_n < n {
+                return Err(MusError::InvalidInput("population_size must be >= sample size".into()));
+            }
+            hypergeometric_upper_count(big_n as u64, n as u64, k, 1.0 - conf)? as f64 / big_n as f64
+        }
+    };
+    let rate = k as f64 / n as f64;
+    Ok(AttributeEvaluation {
+        model: opts.model,
+        confidence_level: conf,
+        tolerable_deviation_rate: opts.tolerable_deviation_rate,
+        n,
+        deviations: k,
+        sample_deviation_rate: rate,
+        upper_deviation_limit: upper,
+        allowance_for_sampling_risk: upper - rate,
+        conclusion: if upper <= opts.tolerable_deviation_rate { Conclusion::Accept } else { Conclusion::Reject },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn planning_matches_aicpa_tables() {
+        let plan = |model, conf, tdr, edr, pop| {
+            attribute_planning(AttributePlanningOptions {
+                model,
+                confidence_level: conf,
+                tolerable_deviation_rate: tdr,
+                expected_deviation_rate: edr,
+                population_size: pop,
+            })
+            .expect("plan")
+        };
+        // AICPA table, 95% confidence: TDR 5%/EDR 0% → 59; TDR 5%/EDR 1% → 93 (1 deviation)
+        assert_eq!(plan(AttributeModel::Binomial, 0.95, 0.05, 0.0, None).n, 59);
+        let p = plan(AttributeModel::Binomial, 0.95, 0.05, 0.01, None);
+        assert_eq!((p.n, p.allowable_deviations), (93, 1));
+        // Poisson with zero expected deviations: n = ceil(-ln(0.05)/0.05) = 60
+        assert_eq!(plan(AttributeModel::Poisson, 0.95, 0.05, 0.0, None).n, 60);
+        // A finite population needs fewer items than the binomial model
+        let h = plan(AttributeModel::Hypergeometric, 0.95, 0.05, 0.0, Some(500));
+        assert!(h.n < 59 && h.n > 50);
+    }
+
+    #[test]
+    fn evaluation_upper_limits() {
+        let eval = |model, n, k, pop| {
+            attribute_evaluation(n, k, AttributeEvaluationOptions { model, confidence_level: 0.95, tolerable_deviation_rate: 0.05, population_size: pop })
+                .expect("evaluate")
+        };
+        // Clopper–Pearson, 0 of 59: 1 − 0.05^(1/59) ≈ 0.0495 → reliance supported
+        let b = eval(AttributeModel::Binomial, 59, 0, None);
+        assert_abs_diff_eq!(b.upper_deviation_limit, 1.0 - 0.05f64.powf(1.0 / 59.0), epsilon = 1e-9);
+        assert_eq!(b.conclusion, Conclusion::Accept);
+        // One deviation in 59 items exceeds the 5% tolerable rate
+        assert_eq!(eval(AttributeModel::Binomial, 59, 1, None).conclusion, Conclusion::Reject);
+        // Poisson, 0 deviations: −ln(0.05)/n
+        assert_abs_diff_eq!(eval(AttributeModel::Poisson, 60, 0, None).upper_deviation_limit, -0.05f64.ln() / 60.0, epsilon = 1e-9);
+        // Census: the population rate is known exactly
+        assert_abs_diff_eq!(eval(AttributeModel::Hypergeometric, 100, 2, Some(100)).upper_deviation_limit, 0.02, epsilon = 1e-12);
+        let h = eval(AttributeModel::Hypergeometric, 59, 1, Some(500));
+        assert!(h.upper_deviation_limit < eval(AttributeModel::Binomial, 59, 1, None).upper_deviation_limit);
+    }
+}
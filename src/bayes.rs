@@ -0,0 +1,425 @@
+//! Bayesian MUS planning and evaluation with conjugate beta (binomial) or gamma (Poisson) priors
+//!
+//! The misstatement rate `θ` per monetary unit gets a prior; after `n` sampled units with
+//! a sum of taintings `k` the posterior is `Beta(α + k, β + n − k)` or `Gamma(α + k, β + n)`.
+//! See Design.md for the planning criterion and the Bayes factor.
+
+use crate::{monetary_units, plan_items, sum_nonneg, Conclusion, Currency, Extraction, MusError, Plan, PlanningOptions};
+use statrs::distribution::{Beta, ContinuousCDF, Gamma};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BayesLikelihood {
+    /// Beta prior, binomial likelihood.
+    Binomial,
+    /// Gamma prior, Poisson likelihood.
+    #[default]
+    Poisson,
+}
+
+/// Prior (or posterior) distribution of the misstatement rate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BayesPrior {
+    Beta { alpha: f64, beta: f64 },
+    /// `rate` is the inverse scale.
+    Gamma { shape: f64, rate: f64 },
+}
+
+impl BayesPrior {
+    /// Beta(1, 1) or Gamma(1, 1): no prior information.
+    pub fn uniform(likelihood: BayesLikelihood) -> Self {
+        Self::from_prior_sample(likelihood, 0.0, 0.0)
+    }
+
+    /// Prior equivalent to an earlier sample of `n` monetary units with a sum of taintings `k`
+    /// (e.g. prior-year results), on top of the uniform prior.
+    pub fn from_prior_sample(likelihood: BayesLikelihood, n: f64, k: f64) -> Self {
+        match likelihood {
+            BayesLikelihood::Binomial => Self::Beta { alpha: 1.0 + k, beta: 1.0 + n - k },
+            BayesLikelihood::Poisson => Self::Gamma { shape: 1.0 + k, rate: 1.0 + n },
+        }
+    }
+
+    /// Prior from the audit risk model: the error-free sample that `inherent_risk × control_risk`
+    /// stands for at the materiality rate `tolerable_error / book_value`.
+    pub fn from_risk_assessment(likelihood: BayesLikelihood, inherent_risk: f64, control_risk: f64, materiality_rate: f64) -> Result<Self, MusError> {
+        let rmm = inherent_risk * control_risk;
+        if !(rmm > 0.0 && rmm <= 1.0) {
+            return Err(MusError::InvalidInput("inherent_risk and control_risk must be in (0,1]".into()));
+        }
+        if !(materiality_rate > 0.0 && materiality_rate < 1.0) {
+            return Err(MusError::InvalidInput("materiality_rate must be in (0,1)".into()));
+        }
+        let n = match likelihood {
+            BayesLikelihood::Binomial => rmm.ln() / (1.0 - materiality_rate).ln(),
+            BayesLikelihood::Poisson => -rmm.ln() / materiality_rate,
+        };
+        Ok(Self::from_prior_sample(likelihood, n, 0.0))
+    }
+
+    fn validate(&self) -> Result<(), MusError> {
+        let (a, b) = match *self {
+            Self::Beta { alpha, beta } => (alpha, beta),
+            Self::Gamma { shape, rate } => (shape, rate),
+        };
+        if !(a.is_finite() && b.is_finite() && a > 0.0 && b > 0.0) {
+            return Err(MusError::InvalidInput("prior parameters must be finite and > 0".into()));
+        }
+        Ok(())
+    }
+
+    /// Posterior after `n` monetary units with a sum of taintings `k`.
+    pub fn update(&self, n: f64, k: f64) -> Self {
+        match *self {
+            Self::Beta { alpha, beta } => Self::Beta { alpha: alpha + k, beta: beta + n - k },
+            Self::Gamma { shape, rate } => Self::Gamma { shape: shape + k, rate: rate + n },
+        }
+    }
+
+    pub fn mode(&self) -> f64 {
+        match *self {
+            Self::Beta { alpha, beta } if alpha > 1.0 => (alpha - 1.0) / (alpha + beta - 2.0),
+            Self::Gamma { shape, rate } if shape > 1.0 => (shape - 1.0) / rate,
+            _ => 0.0,
+        }
+    }
+
+    pub fn cdf(&self, x: f64) -> Result<f64, MusError> {
+        Ok(match *self {
+            Self::Beta { alpha, beta } => Beta::new(alpha, beta).map_err(|e| MusError::Calculation(format!("beta: {e}")))?.cdf(x),
+            Self::Gamma { shape, rate } => Gamma::new(shape, rate).map_err(|e| MusError::Calculation(format!("gamma: {e}")))?.cdf(x),
+        })
+    }
+
+    pub fn quantile(&self, p: f64) -> Result<f64, MusError> {
+        Ok(match *self {
+            Self::Beta { alpha, beta } => Beta::new(alpha, beta).map_err(|e| MusError::Calculation(format!("beta: {e}")))?.inverse_cdf(p),
+            Self::Gamma { shape, rate } => Gamma::new(shape, rate).map_err(|e| MusError::Calculation(format!("gamma: {e}")))?.inverse_cdf(p),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BayesPlanningOptions {
+    pub prior: BayesPrior,
+    pub confidence_level: f64,
+    pub tolerable_error: f64,
+    /// Expected misstatement; the plan assumes `n × expected_error / book_value` tainting.
+    pub expected_error: f64,
+    /// Largest sample size tried.
+    pub max_n: usize,
+}
+
+impl Default for BayesPlanningOptions {
+    fn default() -> Self {
+        Self {
+            prior: BayesPrior::uniform(BayesLikelihood::Poisson),
+            confidence_level: 0.95,
+            tolerable_error: f64::NAN,
+            expected_error: 0.0,
+            max_n: 10_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BayesPlan {
+    pub prior: BayesPrior,
+    pub confidence_level: f64,
+    pub book_value: f64,
+    pub tolerable_error: f64,
+    pub expected_error: f64,
+    /// `tolerable_error` over the sampling population value at `n`, as in `bayes_evaluation`.
+    pub materiality_rate: f64,
+    /// Smallest `n` whose expected posterior upper bound is within the materiality rate.
+    /// `to_plan` turns it into a `Plan` for `mus_extraction`.
+    pub n: usize,
+    /// Items at or above the interval `book_value / n`, taken as high values by `mus_extraction`.
+    pub high_values: usize,
+    /// Sampling units that fall in the sampling population (at least), the `n` of the posterior.
+    pub sampling_units: usize,
+    pub expected_errors: f64,
+    /// Posterior assuming the expected errors are found.
+    pub expected_posterior: BayesPrior,
+    /// Upper bound on the misstatement rate under `expected_posterior`.
+    pub expected_upper_bound: f64,
+    /// Prior probability that the misstatement rate is below the materiality rate.
+    pub prior_probability: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct BayesEvaluationOptions {
+    pub prior: BayesPrior,
+    /// Audited values of `Extraction.high_values` in the same order.
+    /// Empty means the high values were audited without misstatement.
+    pub high_value_audit_values: Vec<f64>,
+}
+
+impl Default for BayesEvaluationOptions {
+    fn default() -> Self {
+        Self { prior: BayesPrior::uniform(BayesLikelihood::Poisson), high_value_audit_values: Vec::new() }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BayesEvaluation {
+    pub prior: BayesPrior,
+    pub posterior: BayesPrior,
+    pub confidence_level: f64,
+    pub n: usize,
+    /// Sum of overstatement taintings in the sample.
+    pub sum_taintings: f64,
+    /// Posterior mode of the misstatement rate.
+    pub mode: f64,
+    /// Posterior `confidence_level` quantile of the misstatement rate.
+    pub upper_bound: f64,
+    /// Mode and upper bound scaled to the sampling population, plus known misstatement in high values.
+    pub most_likely_misstatement: f64,
+    pub upper_error_limit: f64,
+    /// Posterior over prior odds of `θ < materiality rate` against `θ ≥ materiality rate`, with
+    /// the materiality rate taken as tolerable error over the sampling population value.
+    /// Infinite (or zero) once the posterior probability rounds to 1 (or 0).
+    pub bayes_factor: f64,
+    pub conclusion: Conclusion,
+}
+
+fn odds(p: f64) -> f64 {
+    p / (1.0 - p)
+}
+
+impl BayesPlan {
+    /// A `Plan` with sample size `n` for `mus_extraction`: its interval `book_value / n` takes
+    /// the high values planning assumed. `book_values` are those passed to `bayes_planning`,
+    /// counted in minor units of `currency`.
+    ///
+    /// The result is a classical plan with a fixed `n`: it carries no prior, its `model` and
+    /// `tolerable_taintings` are the classical defaults, and `ExtensionSize::ExpectedError`
+    /// re-plans it with `mus_planning`. Extend such samples with `ExtensionSize::AdditionalN`.
+    pub fn to_plan(&self, book_values: &[f64], currency: Currency) -> Result<Plan, MusError> {
+        let ids = (1..=book_values.len()).map(|i| i.to_string()).collect();
+        let opts = PlanningOptions {
+            confidence_level: self.confidence_level,
+            tolerable_error: self.tolerable_error,
+            expected_error: self.expected_error,
+            currency,
+            ..PlanningOptions::default()
+        };
+        plan_items(book_values, ids, opts, Some(self.n))
+    }
+}
+
+pub fn bayes_planning(book_values: &[f64], opts: BayesPlanningOptions) -> Result<BayesPlan, MusError> {
+    opts.prior.validate()?;
+    if !(opts.confidence_level > 0.0 && opts.confidence_level < 1.0) {
+        return Err(MusError::InvalidInput("confidence_level must be in (0,1)".into()));
+    }
+    let book_value = sum_nonneg(book_values);
+    if book_value <= 0.0 {
+        return Err(MusError::InvalidInput("book value must be > 0".into()));
+    }
+    if !(opts.tolerable_error.is_finite() && opts.tolerable_error > 0.0 && opts.tolerable_error < book_value) {
+        return Err(MusError::InvalidInput("tolerable_error must be in (0, book value)".into()));
+    }
+    if !(opts.expected_error.is_finite() && opts.expected_error >= 0.0 && opts.expected_error < opts.tolerable_error) {
+        return Err(MusError::InvalidInput("expected_error must be in [0, tolerable_error)".into()));
+    }
+    let expected_rate = opts.expected_error / book_value;
+    for n in 1..=opts.max_n {
+        // `mus_extraction` takes items at or above `BV / n` as high values and places at least
+        // ⌊pop / interval⌋ units in the rest; `bayes_evaluation` measures θ on that population
+        let interval = book_value / n as f64;
+        let (high_values, pop_sum) = book_values
+            .iter()
+            .filter(|&&v| v > 0.0)
+            .fold((0, 0.0), |(h, s), &v| if v >= interval { (h + 1, s) } else { (h, s + v) });
+        let units = (pop_sum / interval + 1e-9).floor() as usize;
+        let materiality_rate = opts.tolerable_error / pop_sum;
+        let k = units as f64 * expected_rate;
+        let posterior = opts.prior.update(units as f64, k);
+        let upper = posterior.quantile(opts.confidence_level)?;
+        if upper <= materiality_rate {
+            return Ok(BayesPlan {
+                prior: opts.prior,
+                confidence_level: opts.confidence_level,
+                book_value,
+                tolerable_error: opts.tolerable_error,
+                expected_error: opts.expected_error,
+                materiality_rate,
+                n,
+                high_values,
+                sampling_units: units,
+                expected_errors: k,
+                expected_posterior: posterior,
+                expected_upper_bound: upper,
+                prior_probability: opts.prior.cdf(materiality_rate)?,
+            });
+        }
+    }
+    Err(MusError::Calculation(format!("no sample size up to {} meets the tolerable error", opts.max_n)))
+}
+
+/// Evaluate the overstatement taintings of `extract` against the prior; confidence level and
+/// tolerable error come from the extraction's plan.
+pub fn bayes_evaluation(extract: &Extraction, audited_values: &[f64], opts: BayesEvaluationOptions) -> Result<BayesEvaluation, MusError> {
+    let prior = opts.prior;
+    prior.validate()?;
+    if audited_values.len() != extract.sample.len() {
+        return Err(MusError::InvalidInput(format!(
+            "audited_values must match the sample size ({} != {})",
+            audited_values.len(),
+            extract.sample.len()
+        )));
+    }
+    if !opts.high_value_audit_values.is_empty() && opts.high_value_audit_values.len() != extract.high_values.len() {
+        return Err(MusError::InvalidInput("high_value_audit_values must match the number of high values".into()));
+    }
+    if audited_values.iter().chain(&opts.high_value_audit_values).any(|v| !v.is_finite()) {
+        return Err(MusError::InvalidInput("audited values must be finite".into()));
+    }
+    let plan = &extract.plan;
+    let currency = extract.plan.currency;
+    let pop_sum = monetary_units(extract.sample_population.iter().map(|&(_, v, _)| v)) as f64 / currency.scale();
+    // θ is the misstatement rate of the sampling population (the UEL scales it by `pop_sum`),
+    // so materiality is measured against the same value, not the plan's book value
+    let materiality_rate = plan.tolerable_error / pop_sum;
+    if !(materiality_rate > 0.0 && materiality_rate < 1.0) {
+        return Err(MusError::InvalidInput("tolerable_error must be in (0, sampling population value)".into()));
+    }
+    let n = extract.sample.len();
+    let sum_taintings: f64 = extract
+        .sample
+        .iter()
+        .zip(audited_values)
+        .map(|(item, &audit)| {
+            let book = currency.amount(item.book_value);
+            ((book - audit) / book).max(0.0)
+        })
+        .sum();
+    // The Beta update adds n − k to β, so k is capped at n to keep β ≥ β0 > 0
+    let k = match prior {
+        BayesPrior::Beta { .. } => sum_taintings.min(n as f64),
+        BayesPrior::Gamma { .. } => sum_taintings,
+    };
+    let posterior = prior.update(n as f64, k);
+    posterior.validate()?;
+    let mode = posterior.mode();
+    let upper_bound = posterior.quantile(plan.confidence_level)?;
+    let known: f64 = extract
+        .high_values
+        .iter()
+        .zip(&opts.high_value_audit_values)
+        .fold(0.0, |acc, (h, &audit)| acc + (currency.amount(h.book_value) - audit).max(0.0));
+    let upper_error_limit = upper_bound * pop_sum + known;
+    let prior_p = prior.cdf(materiality_rate)?;
+    // A prior without mass on one side of the materiality rate has no odds to update
+    if !(prior_p > 0.0 && prior_p < 1.0) {
+        return Err(MusError::Calculation(format!("prior probability of θ < materiality rate is {prior_p}; the Bayes factor is undefined")));
+    }
+    let posterior_p = posterior.cdf(materiality_rate)?;
+    let conclusion = if upper_error_limit <= plan.tolerable_error { Conclusion::Accept } else { Conclusion::Reject };
+    Ok(BayesEvaluation {
+        prior,
+        posterior,
+        confidence_level: plan.confidence_level,
+        n,
+        sum_taintings,
+        mode,
+        upper_bound,
+        most_likely_misstatement: mode * pop_sum + known,
+        upper_error_limit,
+        bayes_factor: odds(posterior_p) / odds(prior_p),
+        conclusion,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mus_extraction, mus_planning, ExtractionOptions};
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn uniform_priors_match_closed_forms() {
+        // θ_max = 5%, 95%: Gamma(1, 1 + n) bound −ln(0.05) / (1 + n); Beta(1, 1 + n) bound 1 − 0.05^(1/(n+1))
+        let data = vec![100.0; 1000];
+        let te = 5_000.0;
+        let gamma = bayes_planning(&data, BayesPlanningOptions { tolerable_error: te, ..Default::default() }).expect("gamma");
+        assert_eq!(gamma.n, 59);
+        assert_abs_diff_eq!(gamma.expected_upper_bound, -0.05f64.ln() / 60.0, epsilon = 1e-9);
+        let prior = BayesPrior::uniform(BayesLikelihood::Binomial);
+        let beta = bayes_planning(&data, BayesPlanningOptions { prior, tolerable_error: te, ..Default::default() }).expect("beta");
+        assert_eq!(beta.n, 58);
+        // A favourable risk assessment (IR × CR = 0.5) lowers n by ln(2) / θ_max ≈ 14
+        let prior = BayesPrior::from_risk_assessment(BayesLikelihood::Poisson, 1.0, 0.5, 0.05).expect("prior");
+        let informed = bayes_planning(&data, BayesPlanningOptions { prior, tolerable_error: te, ..Default::default() }).expect("informed");
+        assert_eq!(informed.n, 46);
+    }
+
+    #[test]
+    fn evaluation_posterior_and_bayes_factor() {
+        let mut data: Vec<f64> = (0..500u64).map(|i| (i % 1000 + 1) as f64).collect();
+        data.push(20_000.0);
+        let plan = mus_planning(&data, PlanningOptions { tolerable_error: 10_000.0, expected_error: 0.0, ..PlanningOptions::default() }).expect("plan");
+        let ext = mus_extraction(&plan, ExtractionOptions { seed: Some(1), ..ExtractionOptions::default() }).expect("extract");
+        assert_eq!(ext.high_values.len(), 1);
+        let n = ext.sample.len() as f64;
+        // Materiality rate of the sampling population, which excludes the high value
+        let theta = plan.tolerable_error / (plan.book_value - 20_000.0);
+        let mut audited: Vec<f64> = ext.sample.iter().map(|s| ext.plan.currency.amount(s.book_value)).collect();
+        let ev = bayes_evaluation(&ext, &audited, BayesEvaluationOptions::default()).expect("evaluate");
+        assert_eq!(ev.posterior, BayesPrior::Gamma { shape: 1.0, rate: 1.0 + n });
+        assert_eq!(ev.mode, 0.0);
+        assert_abs_diff_eq!(ev.upper_bound, -(1.0 - plan.confidence_level).ln() / (1.0 + n), epsilon = 1e-9);
+        let odds = |p: f64| p / (1.0 - p);
+        let bf = odds(1.0 - (-(1.0 + n) * theta).exp()) / odds(1.0 - (-theta).exp());
+        assert_abs_diff_eq!(ev.bayes_factor / bf, 1.0, epsilon = 1e-9);
+        // A 50% tainting moves the posterior mode to 0.5 / (1 + n)
+        audited[0] *= 0.5;
+        let ev = bayes_evaluation(&ext, &audited, BayesEvaluationOptions::default()).expect("evaluate");
+        assert_abs_diff_eq!(ev.mode, 0.5 / (1.0 + n), epsilon = 1e-12);
+    }
+
+    #[test]
+    fn degenerate_priors_have_no_bayes_factor() {
+        let data: Vec<f64> = (0..500u64).map(|i| (i % 1000 + 1) as f64).collect();
+        let plan = mus_planning(&data, PlanningOptions { tolerable_error: 10_000.0, expected_error: 0.0, ..PlanningOptions::default() }).expect("plan");
+        let ext = mus_extraction(&plan, ExtractionOptions { seed: Some(1), ..ExtractionOptions::default() }).expect("extract");
+        let audited: Vec<f64> = ext.sample.iter().map(|s| ext.plan.currency.amount(s.book_value)).collect();
+        // θ_max ≈ 8%: Beta(5000, 1) has no mass below it, Gamma(1, 10 000) none above it
+        for prior in [BayesPrior::Beta { alpha: 5_000.0, beta: 1.0 }, BayesPrior::Gamma { shape: 1.0, rate: 10_000.0 }] {
+            let result = bayes_evaluation(&ext, &audited, BayesEvaluationOptions { prior, ..Default::default() });
+            assert!(matches!(result, Err(MusError::Calculation(_))), "{prior:?}");
+        }
+        // A posterior probability that rounds to 1 gives an infinite factor, not NaN
+        let prior = BayesPrior::Gamma { shape: 1.0, rate: 450.0 };
+        let ev = bayes_evaluation(&ext, &audited, BayesEvaluationOptions { prior, ..Default::default() }).expect("evaluate");
+        assert!(prior.cdf(10_000.0 / 125_250.0).expect("cdf") < 1.0);
+        assert_eq!(ev.bayes_factor, f64::INFINITY);
+    }
+
+    #[test]
+    fn planning_measures_materiality_on_the_sampling_population() {
+        // 5 items of 20 000 are high values at any n > 10; the other 100 000 carry the sample
+        let mut data = vec![100.0; 1000];
+        data.extend([20_000.0; 5]);
+        let te = 5_000.0;
+        let bp = bayes_planning(&data, BayesPlanningOptions { tolerable_error: te, ..Default::default() }).expect("plan");
+        // Units in the sampling population are n / 2; Gamma(1, 1 + u) needs u ≥ −ln(0.05) / 0.05 − 1
+        assert_eq!((bp.n, bp.high_values, bp.sampling_units), (118, 5, 59));
+        assert_abs_diff_eq!(bp.materiality_rate, te / 100_000.0);
+        assert!(bp.expected_upper_bound <= bp.materiality_rate);
+
+        // Extracting the plan's n items and finding no errors passes the Bayesian evaluation
+        let plan = bp.to_plan(&data, Currency::UNITS).expect("mus plan");
+        assert_eq!((plan.n, plan.book_value, plan.tolerable_error), (bp.n, bp.book_value, te));
+        assert_abs_diff_eq!(plan.high_value_threshold, bp.book_value / bp.n as f64);
+        for seed in 0..20 {
+            let ext = mus_extraction(&plan, ExtractionOptions { seed: Some(seed), ..ExtractionOptions::default() }).expect("extract");
+            assert_eq!(ext.high_values.len(), bp.high_values);
+            assert!(ext.sample.len() >= bp.sampling_units);
+            let audited: Vec<f64> = ext.sample.iter().map(|s| ext.plan.currency.amount(s.book_value)).collect();
+            let ev = bayes_evaluation(&ext, &audited, BayesEvaluationOptions::default()).expect("evaluate");
+            assert!(ev.upper_bound <= bp.materiality_rate);
+            assert_eq!(ev.conclusion, Conclusion::Accept);
+        }
+    }
+}
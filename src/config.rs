@@ -1,4 +1,4 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use serde::Deserialize;
 use std::{collections::HashMap, fs, path::Path};
 
@@ -23,8 +23,119 @@ pub struct Rule {
 
 pub type ConfigMap = HashMap<String, Vec<Rule>>;
 
-pub fn load_config(path: &Path) -> Result<ConfigMap> {
+/// 一个列角色的候选表头：`"借方金额"` 或 `["借方金额", "借方本币"]`，按顺序取第一个存在的（精确匹配）
+#[derive(Debug, Clone, Deserialize)]
+#[serde(from = "ColumnSpecRepr")]
+pub struct ColumnSpec(pub Vec<String>);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ColumnSpecRepr {
+    Name(String),
+    Aliases(Vec<String>),
+}
+
+impl From<ColumnSpecRepr> for ColumnSpec {
+    fn from(r: ColumnSpecRepr) -> Self {
+        match r {
+            ColumnSpecRepr::Name(n) => ColumnSpec(vec![n]),
+            ColumnSpecRepr::Aliases(v) => ColumnSpec(v),
+        }
+    }
+}
+
+/// 配置中的 `columns` 段：显式指定各角色对应的表头，未指定的角色仍按表头关键字自动识别
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ColumnMapping {
+    pub date: Option<ColumnSpec>,
+    pub account_code: Option<ColumnSpec>,
+    pub debit: Option<ColumnSpec>,
+    pub credit: Option<ColumnSpec>,
+    pub direction: Option<ColumnSpec>,
+    pub signed_amount: Option<ColumnSpec>,
+    pub report_subject: Option<ColumnSpec>,
+    pub voucher_line: Option<ColumnSpec>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub accounts: ConfigMap,
+    pub columns: ColumnMapping,
+}
+
+pub fn load_config(path: &Path) -> Result<Config> {
     let text = fs::read_to_string(path).with_context(|| format!("读取配置失败: {}", path.display()))?;
-    let cfg: ConfigMap = serde_json::from_str(&text).context("配置 JSON 解析失败")?;
-    Ok(cfg)
+    parse_config(&text)
+}
+
+fn parse_config(text: &str) -> Result<Config> {
+    let mut map: serde_json::Map<String, serde_json::Value> = serde_json::from_str(text).context("配置 JSON 解析失败")?;
+    // 顶层 `columns` 为对象时是列映射；为数组时仍是名为 columns 的科目
+    let columns = match map.get("columns") {
+        Some(serde_json::Value::Object(_)) => map.remove("columns"),
+        _ => None,
+    };
+    let columns: ColumnMapping = match columns {
+        Some(v) => serde_json::from_value(v).context("配置 columns 段解析失败")?,
+        None => ColumnMapping::default(),
+    };
+    for (role, spec) in columns.roles() {
+        if spec.is_some_and(|s| s.0.iter().all(|n| n.trim().is_empty())) {
+            bail!("配置 columns.{role} 未给出列名");
+        }
+    }
+    let accounts: ConfigMap = serde_json::from_value(serde_json::Value::Object(map)).context("配置 JSON 解析失败")?;
+    Ok(Config { accounts, columns })
+}
+
+impl ColumnMapping {
+    /// （配置键, 映射）列表，顺序与结构体字段一致
+    pub fn roles(&self) -> [(&'static str, Option<&ColumnSpec>); 8] {
+        [
+            ("date", self.date.as_ref()),
+            ("account_code", self.account_code.as_ref()),
+            ("debit", self.debit.as_ref()),
+            ("credit", self.credit.as_ref()),
+            ("direction", self.direction.as_ref()),
+            ("signed_amount", self.signed_amount.as_ref()),
+            ("report_subject", self.report_subject.as_ref()),
+            ("voucher_line", self.voucher_line.as_ref()),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn columns_object_is_a_mapping_and_other_keys_are_accounts() {
+        let config = parse_config(
+            r#"{
+                "columns": { "date": "记账日期", "debit": ["借方本币", "借方金额"] },
+                "应付账款": [{ "account_codes": ["2202"], "transaction_type": "credit" }]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(config.columns.date.as_ref().map(|s| s.0.clone()), Some(vec!["记账日期".to_string()]));
+        assert_eq!(config.columns.debit.as_ref().map(|s| s.0.clone()), Some(vec!["借方本币".to_string(), "借方金额".to_string()]));
+        assert!(config.columns.credit.is_none());
+        assert_eq!(config.accounts.keys().collect::<Vec<_>>(), ["应付账款"]);
+        assert!(matches!(config.accounts["应付账款"][0].transaction_type, Some(TransactionType::Credit)));
+    }
+
+    #[test]
+    fn columns_array_is_still_an_account() {
+        let config = parse_config(r#"{ "columns": [{ "account_codes": ["1601"] }] }"#).unwrap();
+        assert!(config.columns.roles().iter().all(|(_, spec)| spec.is_none()));
+        assert_eq!(config.accounts["columns"][0].account_codes, Some(vec!["1601".to_string()]));
+    }
+
+    #[test]
+    fn invalid_column_mappings_are_rejected() {
+        let err = parse_config(r#"{ "columns": { "debit": ["", " "] } }"#).unwrap_err();
+        assert!(err.to_string().contains("columns.debit"), "{err}");
+        assert!(parse_config(r#"{ "columns": { "amount": "金额" } }"#).is_err());
+    }
 }
@@ -0,0 +1,619 @@
+//! MUS evaluation: upper error limit from an audited sample
+//!
+//! Port of `MUS.evaluation` (with the moment, binomial and multinomial bounds) from ref/R.
+//! See Design.md for the formulas of each bound.
+
+use crate::{monetary_units, Extraction, ExtractionMode, MusError};
+use statrs::distribution::{Beta, ContinuousCDF, Gamma, Normal};
+use statrs::function::gamma::ln_gamma;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EvaluationBound {
+    /// Stringer bound with Poisson (gamma) confidence factors.
+    #[default]
+    Stringer,
+    /// Cell bound (Leslie, Teitlebaum & Anderson).
+    Cell,
+    /// Moment bound (Dworin & Grimlund), three-parameter gamma fit.
+    Moment,
+    /// Binomial (Clopper-Pearson) bound on the sum of taintings.
+    Binomial,
+    /// Multinomial bound (Fienberg, Neter & Leitch) on taintings rounded up to 10% cells.
+    Multinomial,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EvaluationOptions {
+    pub bound: EvaluationBound,
+    /// Audited values of `Extraction.high_values` in the same order.
+    /// Empty means the high values were audited without misstatement.
+    pub high_value_audit_values: Vec<f64>,
+}
+
+impl EvaluationOptions {
+    /// Default options with the bound that matches how `extract` was drawn:
+    /// the cell bound for cell sampling, Stringer otherwise.
+    pub fn for_extraction(extract: &Extraction) -> Self {
+        let bound = match extract.mode {
+            ExtractionMode::Cell => EvaluationBound::Cell,
+            ExtractionMode::FixedInterval => EvaluationBound::Stringer,
+        };
+        Self { bound, ..Self::default() }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conclusion {
+    /// Upper error limit(s) do not exceed the tolerable error.
+    Accept,
+    /// An upper error limit exceeds the tolerable error.
+    Reject,
+    /// Component of a combined plan (`Plan.combined`); conclude on `mus_combine` instead.
+    Deferred,
+}
+
+/// One side (overstatement or understatement) of a MUS evaluation; amounts are positive
+/// in the direction of that side.
+#[derive(Debug, Clone)]
+pub struct MisstatementBound {
+    pub num_errors: usize,
+    pub sum_taintings: f64,
+    /// Sample errors projected to the sampling population (interval × sum of taintings).
+    pub projected_misstatement: f64,
+    /// Misstatement found in high values (no projection).
+    pub known_misstatement: f64,
+    pub most_likely_misstatement: f64,
+    pub basic_precision: f64,
+    /// UEL − projected misstatement − basic precision: the ranked-factor increments for
+    /// Stringer, the remainder of the bound for the other methods.
+    pub precision_gap_widening: f64,
+    pub upper_error_limit: f64,
+}
+
+/// Several MUS evaluations (populations or strata of one account) bounded together.
+#[derive(Debug, Clone)]
+pub struct CombinedEvaluation {
+    pub components: usize,
+    /// Always `Stringer`: the only bound `mus_combine` pools.
+    pub bound: EvaluationBound,
+    pub confidence_level: f64,
+    pub tolerable_error: f64,
+    pub book_value: f64,
+    pub overstatement: MisstatementBound,
+    pub understatement: MisstatementBound,
+    pub net_most_likely_misstatement: f64,
+    pub net_overstatement_limit: f64,
+    pub net_understatement_limit: f64,
+    pub conclusion: Conclusion,
+}
+
+#[derive(Debug, Clone)]
+pub struct Evaluation {
+    pub extraction: Extraction,
+    pub bound: EvaluationBound,
+    pub confidence_level: f64,
+    pub audited_values: Vec<f64>,
+    /// `(book − audited) / book`; negative values are understatements.
+    pub taintings: Vec<f64>,
+    pub overstatement: MisstatementBound,
+    pub understatement: MisstatementBound,
+    /// Overstatement MLE − understatement MLE.
+    pub net_most_likely_misstatement: f64,
+    /// Overstatement UEL reduced by the understatement MLE.
+    pub net_overstatement_limit: f64,
+    /// Understatement UEL reduced by the overstatement MLE.
+    pub net_understatement_limit: f64,
+    /// Accept when both net-adjusted limits are within the tolerable error.
+    pub conclusion: Conclusion,
+}
+
+fn poisson_factor(confidence_level: f64, k: usize) -> Result<f64, MusError> {
+    let g = Gamma::new(k as f64 + 1.0, 1.0).map_err(|e| MusError::Calculation(format!("gamma: {e}")))?;
+    Ok(g.inverse_cdf(confidence_level))
+}
+
+fn stringer_pgw(sorted_taintings: &[f64], confidence_level: f64) -> Result<f64, MusError> {
+    let mut pgw = 0.0;
+    let mut prev = poisson_factor(confidence_level, 0)?;
+    for (k, &t) in sorted_taintings.iter().enumerate() {
+        let f = poisson_factor(confidence_level, k + 1)?;
+        pgw += (f - prev - 1.0) * t;
+        prev = f;
+    }
+    Ok(pgw)
+}
+
+fn cell_bound(sorted_taintings: &[f64], interval: f64, confidence_level: f64) -> Result<f64, MusError> {
+    let mut uel = interval * poisson_factor(confidence_level, 0)?;
+    let mut cum = 0.0;
+    for (k, &t) in sorted_taintings.iter().enumerate() {
+        cum += t;
+        let avg = cum / (k + 1) as f64;
+        let by_factor = interval * avg * poisson_factor(confidence_level, k + 1)?;
+        uel = (uel + interval * t).max(by_factor);
+    }
+    Ok(uel)
+}
+
+// Upper bound on the mean tainting per monetary unit.
+fn moment_bound(taintings: &[f64], n: usize, confidence_level: f64) -> Result<f64, MusError> {
+    let errors: Vec<f64> = taintings.iter().copied().filter(|&t| t > 0.0).collect();
+    let m = errors.len() as f64;
+    let mean_err = if errors.is_empty() { 0.0 } else { errors.iter().sum::<f64>() / m };
+    // Hypothetical tainting standing in for the errors not (yet) observed
+    let t_star = 0.81 * (1.0 - 0.667 * (10.0 * mean_err).tanh()) * (1.0 + 0.667 * (m / 10.0).tanh());
+    let nn = n as f64 + 1.0;
+    let (mut s1, mut s2, mut s3) = (t_star, t_star.powi(2), t_star.powi(3));
+    for &t in &errors {
+        s1 += t;
+        s2 += t * t;
+        s3 += t * t * t;
+    }
+    let (u1, u2, u3) = (s1 / nn, s2 / nn, s3 / nn);
+    let c2 = (u2 - u1 * u1) / n as f64;
+    let c3 = (u3 - 3.0 * u1 * u2 + 2.0 * u1.powi(3)) / (n as f64).powi(2);
+    if c3 <= 0.0 || c2 <= 0.0 {
+        // Degenerate skewness: fall back to the normal approximation
+        let z = Normal::new(0.0, 1.0).map_err(|e| MusError::Calculation(format!("normal: {e}")))?.inverse_cdf(confidence_level);
+        return Ok(u1 + z * c2.max(0.0).sqrt());
+    }
+    let a = 4.0 * c2.powi(3) / c3.powi(2);
+    let b = c3 / (2.0 * c2);
+    let g = u1 - 2.0 * c2.powi(2) / c3;
+    let q = Gamma::new(a, 1.0).map_err(|e| MusError::Calculation(format!("gamma: {e}")))?.inverse_cdf(confidence_level);
+    Ok(g + b * q)
+}
+
+fn binomial_bound(sum_taintings: f64, n: usize, confidence_level: f64) -> Result<f64, MusError> {
+    let fails = n as f64 - sum_taintings;
+    if fails <= 0.0 { return Ok(1.0); }
+    let beta = Beta::new(sum_taintings + 1.0, fails).map_err(|e| MusError::Calculation(format!("beta: {e}")))?;
+    Ok(beta.inverse_cdf(confidence_level))
+}
+
+// ln P[Z is no worse than the observed outcome] under the multinomial model, where
+// `cats` holds (tainting, cumulative observed count from the top) in descending order
+// and `p[0]` is the probability of a correct monetary unit.
+fn fnl_ln_prob(p: &[f64], cats: &[(f64, usize)], n: usize) -> f64 {
+    let m = cats.last().map(|c| c.1).unwrap_or(0);
+    let nf = n as f64;
+    let mut dp = vec![0.0; m + 1];
+    dp[0] = 1.0;
+    for (k, &(_, limit)) in cats.iter().enumerate() {
+        let lam = nf * p[k + 1];
+        let mut next = vec![0.0; m + 1];
+        for s in 0..=limit {
+            let mut term = 1.0; // lam^z / z!
+            let mut acc = 0.0;
+            for z in 0..=s {
+                if z > 0 { term *= lam / z as f64; }
+                acc += dp[s - z] * term;
+            }
+            next[s] = acc;
+        }
+        dp = next;
+    }
+    let mut total = 0.0;
+    for (s, &v) in dp.iter().enumerate() {
+        if v == 0.0 || s > n { continue; }
+        let rest = (n - s) as f64;
+        let ln_p0 = if rest == 0.0 { 0.0 } else { rest * p[0].ln() };
+        let ln_f = ln_gamma(nf + 1.0) - ln_gamma(rest + 1.0) - s as f64 * nf.ln() + ln_p0;
+        total += v * ln_f.exp();
+    }
+    total.ln()
+}
+
+// Maximise the mean tainting over the multinomial confidence region via a Lagrangian:
+// for fixed λ maximise μ(p) + λ·ln P(p) by exponentiated gradient, then bisect λ so P = α.
+fn multinomial_bound(taintings: &[f64], n: usize, confidence_level: f64) -> Result<f64, MusError> {
+    const CELLS: usize = 10;
+    let mut counts = [0usize; CELLS + 1];
+    for &t in taintings.iter().filter(|&&t| t > 0.0) {
+        let c = ((t * CELLS as f64 - 1e-9).ceil() as usize).clamp(1, CELLS);
+        counts[c] += 1;
+    }
+    // Support: observed cells plus the 100% cell, top-down with cumulative counts
+    let mut cats: Vec<(f64, usize)> = Vec::new();
+    let mut cum = 0usize;
+    for c in (1..=CELLS).rev() {
+        cum += counts[c];
+        if counts[c] > 0 || c == CELLS { cats.push((c as f64 / CELLS as f64, cum)); }
+    }
+    let alpha = (1.0 - confidence_level).ln();
+    let d = cats.len() + 1;
+    let mu = |p: &[f64]| -> f64 { cats.iter().enumerate().map(|(k, c)| c.0 * p[k + 1]).sum() };
+
+    let solve = |lambda: f64, start: &[f64]| -> Vec<f64> {
+        let objective = |p: &[f64]| mu(p) + lambda * fnl_ln_prob(p, &cats, n);
+        let mut p = start.to_vec();
+        let mut f = objective(&p);
+        let mut eta = 1.0;
+        let mut stalled = 0;
+        for _ in 0..500 {
+            let h = 1e-7;
+            let base = fnl_ln_prob(&p, &cats, n);
+            let grad: Vec<f64> = (0..d)
+                .map(|j| {
+                    let mut q = p.clone();
+                    q[j] += h;
+                    let c = if j == 0 { 0.0 } else { cats[j - 1].0 };
+                    c + lambda * (fnl_ln_prob(&q, &cats, n) - base) / h
+                })
+                .collect();
+            let gmax = grad.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+            loop {
+                let mut q: Vec<f64> = p.iter().zip(&grad).map(|(&pj, &g)| pj * (eta * (g - gmax)).exp()).collect();
+                let z: f64 = q.iter().sum();
+                q.iter_mut().for_each(|v| *v = (*v / z).max(1e-300));
+                let fq = objective(&q);
+                if fq >= f {
+                    stalled = if fq - f < 1e-13 { stalled + 1 } else { 0 };
+                    p = q;
+                    f = fq;
+                    eta = (eta * 1.5).min(1e6);
+                    break;
+                }
+                eta *= 0.5;
+                if eta < 1e-12 { return p; }
+            }
+            if stalled >= 5 { break; }
+        }
+        p
+    };
+
+    let mut start = vec![0.5 / (d - 1) as f64; d];
+    start[0] = 0.5;
+    let (mut lo, mut hi) = (-12.0f64, 12.0f64); // bisect on ln λ
+    let mut best = 0.0;
+    for _ in 0..50 {
+        let mid = 0.5 * (lo + hi);
+        let p = solve(mid.exp(), &start);
+        if fnl_ln_prob(&p, &cats, n) >= alpha {
+            best = mu(&p);
+            hi = mid;
+        } else {
+            lo = mid;
+        }
+        // Warm start, kept away from the simplex boundary so no cell gets stuck at zero
+        start = p.iter().map(|&v| 0.9 * v + 0.1 / d as f64).collect();
+        if hi - lo < 1e-6 { break; }
+    }
+    Ok(best)
+}
+
+pub fn mus_evaluation(extract: &Extraction, audited_values: &[f64], opts: EvaluationOptions) -> Result<Evaluation, MusError> {
+    if audited_values.len() != extract.sample.len() {
+        return Err(MusError::InvalidInput(format!(
+            "audited_values must match the sample size ({} != {})",
+            audited_values.len(),
+            extract.sample.len()
+        )));
+    }
+    if !opts.high_value_audit_values.is_empty() && opts.high_value_audit_values.len() != extract.high_values.len() {
+        return Err(MusError::InvalidInput("high_value_audit_values must match the number of high values".into()));
+    }
+    if audited_values.iter().chain(&opts.high_value_audit_values).any(|v| !v.is_finite()) {
+        return Err(MusError::InvalidInput("audited values must be finite".into()));
+    }
+    let confidence_level = extract.plan.confidence_level;
+    let n = extract.sample.len();
+    let currency = extract.plan.currency;
+    let pop_sum = monetary_units(extract.sample_population.iter().map(|&(_, v, _)| v)) as f64 / currency.scale();
+    if n == 0 && pop_sum > 0.0 {
+        return Err(MusError::InvalidInput("sample is empty; nothing to evaluate".into()));
+    }
+    let interval = if n == 0 { 0.0 } else { extract.sampling_interval };
+
+    let taintings: Vec<f64> = extract
+        .sample
+        .iter()
+        .zip(audited_values)
+        .map(|(item, &audit)| {
+            let book = currency.amount(item.book_value);
+            (book - audit) / book
+        })
+        .collect();
+    let known_over: f64 = extract
+        .high_values
+        .iter()
+        .zip(&opts.high_value_audit_values)
+        .fold(0.0, |acc, (h, &audit)| acc + (currency.amount(h.book_value) - audit).max(0.0));
+    let known_under: f64 = extract
+        .high_values
+        .iter()
+        .zip(&opts.high_value_audit_values)
+        .fold(0.0, |acc, (h, &audit)| acc + (audit - currency.amount(h.book_value)).max(0.0));
+
+    let side = |taintings: &[f64], known: f64| -> Result<MisstatementBound, MusError> {
+        let mut errors: Vec<f64> = taintings.iter().copied().filter(|&t| t > 0.0).collect();
+        errors.sort_by(|a, b| b.partial_cmp(a).unwrap());
+        let sum_taintings: f64 = errors.iter().sum();
+        let projected = interval * sum_taintings;
+        let basic_precision = if n == 0 { 0.0 } else { interval * poisson_factor(confidence_level, 0)? };
+        let sampling_uel = if n == 0 {
+            0.0
+        } else {
+            match opts.bound {
+                EvaluationBound::Stringer => basic_precision + projected + interval * stringer_pgw(&errors, confidence_level)?,
+                EvaluationBound::Cell => cell_bound(&errors, interval, confidence_level)?,
+                EvaluationBound::Moment => moment_bound(taintings, n, confidence_level)? * pop_sum,
+                EvaluationBound::Binomial => binomial_bound(sum_taintings, n, confidence_level)? * pop_sum,
+                EvaluationBound::Multinomial => multinomial_bound(taintings, n, confidence_level)? * pop_sum,
+            }
+        };
+        Ok(MisstatementBound {
+            num_errors: errors.len(),
+            sum_taintings,
+            projected_misstatement: projected,
+            known_misstatement: known,
+            most_likely_misstatement: projected + known,
+            basic_precision,
+            precision_gap_widening: sampling_uel - projected - basic_precision,
+            upper_error_limit: sampling_uel + known,
+        })
+    };
+    let overstatement = side(&taintings, known_over)?;
+    // Understatements are evaluated as overstatements of the negated taintings
+    let negated: Vec<f64> = taintings.iter().map(|t| -t).collect();
+    let understatement = side(&negated, known_under)?;
+
+    let net_overstatement_limit = overstatement.upper_error_limit - understatement.most_likely_misstatement;
+    let net_understatement_limit = understatement.upper_error_limit - overstatement.most_likely_misstatement;
+    let tolerable = extract.plan.tolerable_error;
+    let conclusion = if extract.combined {
+        Conclusion::Deferred
+    } else if net_overstatement_limit <= tolerable && net_understatement_limit <= tolerable {
+        Conclusion::Accept
+    } else {
+        Conclusion::Reject
+    };
+
+    Ok(Evaluation {
+        extraction: extract.clone(),
+        bound: opts.bound,
+        confidence_level,
+        audited_values: audited_values.to_vec(),
+        taintings,
+        net_most_likely_misstatement: overstatement.most_likely_misstatement - understatement.most_likely_misstatement,
+        overstatement,
+        understatement,
+        net_overstatement_limit,
+        net_understatement_limit,
+        conclusion,
+    })
+}
+
+// Pooled Stringer bound for one side: errors of every component are projected with their own
+// interval, ranked together, and the basic precision uses the largest interval.
+fn combined_side(parts: &[(f64, Vec<f64>, f64)], confidence_level: f64) -> Result<MisstatementBound, MusError> {
+    let mut amounts: Vec<f64> = parts.iter().flat_map(|(si, t, _)| t.iter().filter(|&&t| t > 0.0).map(move |t| si * t)).collect();
+    amounts.sort_by(|a, b| b.partial_cmp(a).unwrap());
+    let max_interval = parts.iter().map(|p| p.0).fold(0.0, f64::max);
+    let projected: f64 = amounts.iter().sum();
+    let known: f64 = parts.iter().map(|p| p.2).sum();
+    let basic_precision = max_interval * poisson_factor(confidence_level, 0)?;
+    let pgw = stringer_pgw(&amounts, confidence_level)?;
+    Ok(MisstatementBound {
+        num_errors: amounts.len(),
+        sum_taintings: parts.iter().flat_map(|p| p.1.iter().filter(|&&t| t > 0.0)).sum(),
+        projected_misstatement: projected,
+        known_misstatement: known,
+        most_likely_misstatement: projected + known,
+        basic_precision,
+        precision_gap_widening: pgw,
+        upper_error_limit: basic_precision + projected + pgw + known,
+    })
+}
+
+/// Combine evaluations of several populations (e.g. the debit and credit sides of one account)
+/// into one upper misstatement limit per side. All components must share the confidence level
+/// and tolerable error of their plans, and must have been evaluated with the Stringer bound:
+/// their errors are projected with each component's own interval and ranked together into one
+/// Stringer bound, with the basic precision of the largest interval.
+pub fn mus_combine(evaluations: &[Evaluation]) -> Result<CombinedEvaluation, MusError> {
+    let first = evaluations.first().ok_or_else(|| MusError::InvalidInput("nothing to combine".into()))?;
+    let confidence_level = first.confidence_level;
+    let tolerable_error = first.extraction.plan.tolerable_error;
+    for e in evaluations {
+        if e.bound != EvaluationBound::Stringer {
+            return Err(MusError::InvalidInput("combined evaluations must use the Stringer bound".into()));
+        }
+        if (e.confidence_level - confidence_level).abs() > 1e-12 {
+            return Err(MusError::InvalidInput("combined evaluations must share the confidence level".into()));
+        }
+        if (e.extraction.plan.tolerable_error - tolerable_error).abs() > 1e-9 * tolerable_error.abs().max(1.0) {
+            return Err(MusError::InvalidInput("combined evaluations must share the tolerable error".into()));
+        }
+    }
+    let interval = |e: &Evaluation| if e.taintings.is_empty() { 0.0 } else { e.extraction.sampling_interval };
+    let over: Vec<(f64, Vec<f64>, f64)> = evaluations.iter().map(|e| (interval(e), e.taintings.clone(), e.overstatement.known_misstatement)).collect();
+    let under: Vec<(f64, Vec<f64>, f64)> = evaluations
+        .iter()
+        .map(|e| (interval(e), e.taintings.iter().map(|t| -t).collect(), e.understatement.known_misstatement))
+        .collect();
+    let overstatement = combined_side(&over, confidence_level)?;
+    let understatement = combined_side(&under, confidence_level)?;
+    let net_overstatement_limit = overstatement.upper_error_limit - understatement.most_likely_misstatement;
+    let net_understatement_limit = understatement.upper_error_limit - overstatement.most_likely_misstatement;
+    let conclusion = if net_overstatement_limit <= tolerable_error && net_understatement_limit <= tolerable_error {
+        Conclusion::Accept
+    } else {
+        Conclusion::Reject
+    };
+    Ok(CombinedEvaluation {
+        components: evaluations.len(),
+        bound: EvaluationBound::Stringer,
+        confidence_level,
+        tolerable_error,
+        book_value: evaluations.iter().map(|e| e.extraction.plan.book_value).sum(),
+        net_most_likely_misstatement: overstatement.most_likely_misstatement - understatement.most_likely_misstatement,
+        overstatement,
+        understatement,
+        net_overstatement_limit,
+        net_understatement_limit,
+        conclusion,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mus_extraction, mus_planning, ExtractionOptions, PlanningOptions};
+    use approx::assert_abs_diff_eq;
+
+    fn extract() -> Extraction {
+        let data: Vec<f64> = (0..500u64).map(|i| (i % 1000 + 1) as f64).collect();
+        let plan = mus_planning(&data, PlanningOptions { tolerable_error: 10_000.0, expected_error: 2_000.0, ..PlanningOptions::default() }).expect("plan");
+        mus_extraction(&plan, ExtractionOptions { start_point: Some(5.0), obey_n_as_min: true, ..ExtractionOptions::default() }).expect("extract")
+    }
+
+    #[test]
+    fn stringer_components_add_up() {
+        let ext = extract();
+        let mut audited: Vec<f64> = ext.sample.iter().map(|s| ext.plan.currency.amount(s.book_value)).collect();
+        audited[0] *= 0.5;
+        audited[1] = 0.0;
+        let ev = mus_evaluation(&ext, &audited, EvaluationOptions::default()).expect("evaluate");
+        let o = &ev.overstatement;
+        assert_eq!(o.num_errors, 2);
+        assert_abs_diff_eq!(o.sum_taintings, 1.5, epsilon = 1e-12);
+        assert_abs_diff_eq!(o.basic_precision, ext.sampling_interval * std::f64::consts::LN_10, epsilon = 1e-3);
+        assert_abs_diff_eq!(o.upper_error_limit, o.basic_precision + o.most_likely_misstatement + o.precision_gap_widening, epsilon = 1e-9);
+        // Cell and multinomial are no more conservative than Stringer
+        for bound in [EvaluationBound::Cell, EvaluationBound::Multinomial] {
+            let other = mus_evaluation(&ext, &audited, EvaluationOptions { bound, ..Default::default() }).expect("evaluate");
+            assert!(other.overstatement.upper_error_limit <= o.upper_error_limit + 1e-6);
+        }
+    }
+
+    #[test]
+    fn understatements_are_bounded_separately() {
+        let ext = extract();
+        let si = ext.sampling_interval;
+        let mut audited: Vec<f64> = ext.sample.iter().map(|s| ext.plan.currency.amount(s.book_value)).collect();
+        audited[0] *= 0.5;
+        audited[1] *= 2.0;
+        let ev = mus_evaluation(&ext, &audited, EvaluationOptions::default()).expect("evaluate");
+        assert_eq!((ev.overstatement.num_errors, ev.understatement.num_errors), (1, 1));
+        assert_abs_diff_eq!(ev.understatement.most_likely_misstatement, si, epsilon = 1e-9);
+        assert_abs_diff_eq!(ev.net_most_likely_misstatement, -0.5 * si, epsilon = 1e-9);
+        // Both sides use the same bound: one error of tainting t adds t × (UF(1) − UF(0)) × SI
+        let step = poisson_factor(ext.plan.confidence_level, 1).unwrap() - poisson_factor(ext.plan.confidence_level, 0).unwrap();
+        assert_abs_diff_eq!(ev.understatement.upper_error_limit - ev.understatement.basic_precision, step * si, epsilon = 1e-6);
+        assert_abs_diff_eq!(ev.net_overstatement_limit, ev.overstatement.upper_error_limit - si, epsilon = 1e-9);
+        assert_abs_diff_eq!(ev.net_understatement_limit, ev.understatement.upper_error_limit - 0.5 * si, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn combined_matches_single_population_stringer() {
+        let ext = extract();
+        let mut audited: Vec<f64> = ext.sample.iter().map(|s| ext.plan.currency.amount(s.book_value)).collect();
+        audited[3] *= 0.7;
+        let single = mus_evaluation(&ext, &audited, EvaluationOptions::default()).expect("evaluate");
+        // An error-free second component with the same interval adds nothing to the pooled bound
+        let mut component = ext.clone();
+        component.combined = true;
+        let a = mus_evaluation(&component, &audited, EvaluationOptions::default()).expect("evaluate");
+        let clean: Vec<f64> = ext.sample.iter().map(|s| ext.plan.currency.amount(s.book_value)).collect();
+        let b = mus_evaluation(&component, &clean, EvaluationOptions::default()).expect("evaluate");
+        assert_eq!(a.conclusion, Conclusion::Deferred);
+        let combined = mus_combine(&[a, b]).expect("combine");
+        assert_eq!(combined.components, 2);
+        assert_abs_diff_eq!(combined.overstatement.upper_error_limit, single.overstatement.upper_error_limit, epsilon = 1e-6);
+        assert_abs_diff_eq!(combined.understatement.upper_error_limit, single.understatement.upper_error_limit, epsilon = 1e-6);
+        assert_abs_diff_eq!(combined.book_value, 2.0 * ext.plan.book_value, epsilon = 1e-6);
+        assert_eq!(combined.conclusion, single.conclusion);
+        assert_eq!(combined.bound, EvaluationBound::Stringer);
+    }
+
+    #[test]
+    fn combine_rejects_other_bounds() {
+        let mut component = extract();
+        component.combined = true;
+        let clean: Vec<f64> = component.sample.iter().map(|s| component.plan.currency.amount(s.book_value)).collect();
+        let stringer = mus_evaluation(&component, &clean, EvaluationOptions::default()).expect("evaluate");
+        for bound in [EvaluationBound::Cell, EvaluationBound::Moment, EvaluationBound::Binomial, EvaluationBound::Multinomial] {
+            let other = mus_evaluation(&component, &clean, EvaluationOptions { bound, ..Default::default() }).expect("evaluate");
+            assert!(matches!(mus_combine(&[stringer.clone(), other.clone()]), Err(MusError::InvalidInput(_))));
+            assert!(matches!(mus_combine(&[other]), Err(MusError::InvalidInput(_))));
+        }
+    }
+
+    #[test]
+    fn moment_bound_matches_reference() {
+        // Reference: the Dworin–Grimlund formulas of MUS.evaluation evaluated independently at 30 digits
+        assert_abs_diff_eq!(moment_bound(&[0.0; 50], 50, 0.95).expect("bound"), 0.045_634_228_286_891, epsilon = 1e-9);
+        let mut t = vec![0.5, 0.25, 1.0, -0.3];
+        t.resize(60, 0.0);
+        assert_abs_diff_eq!(moment_bound(&t, 60, 0.90).expect("bound"), 0.059_505_393_095_072, epsilon = 1e-9);
+        // Four full taintings out of four skew the moments left (c3 < 0): normal approximation u1 + z·√c2
+        assert_abs_diff_eq!(moment_bound(&[1.0; 4], 4, 0.95).expect("bound"), 1.085_367_453_098_137, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn binomial_bound_matches_reference() {
+        // Reference: qbeta(confidence, Σt + 1, n − Σt) evaluated independently at 30 digits
+        assert_abs_diff_eq!(binomial_bound(0.0, 50, 0.95).expect("bound"), 1.0 - 0.05f64.powf(1.0 / 50.0), epsilon = 1e-12);
+        assert_abs_diff_eq!(binomial_bound(0.0, 50, 0.95).expect("bound"), 0.058_155_079_116_972, epsilon = 1e-9);
+        assert_abs_diff_eq!(binomial_bound(1.75, 60, 0.90).expect("bound"), 0.080_681_038_205_803, epsilon = 1e-9);
+        // Every unit misstated
+        assert_eq!(binomial_bound(4.0, 4, 0.95).expect("bound"), 1.0);
+    }
+
+    #[test]
+    fn moment_and_binomial_limits_scale_the_sampling_population() {
+        let ext = extract();
+        let n = ext.sample.len();
+        let pop_sum = ext.sample_population.iter().map(|&(_, v, _)| v.monetary_units()).sum::<u64>() as f64;
+        let conf = ext.plan.confidence_level;
+        let clean: Vec<f64> = ext.sample.iter().map(|s| ext.plan.currency.amount(s.book_value)).collect();
+        let mut audited = clean.clone();
+        audited[0] *= 0.5;
+        audited[1] = 0.0;
+        let mut taintings = vec![0.5, 1.0];
+        taintings.resize(n, 0.0);
+        for (values, taintings) in [(&clean, vec![0.0; n]), (&audited, taintings)] {
+            let uel = |bound| mus_evaluation(&ext, values, EvaluationOptions { bound, ..Default::default() }).expect("evaluate").overstatement.upper_error_limit;
+            let sum: f64 = taintings.iter().sum();
+            assert_abs_diff_eq!(uel(EvaluationBound::Moment), moment_bound(&taintings, n, conf).expect("bound") * pop_sum, epsilon = 1e-6);
+            assert_abs_diff_eq!(uel(EvaluationBound::Binomial), binomial_bound(sum, n, conf).expect("bound") * pop_sum, epsilon = 1e-6);
+        }
+    }
+
+    #[test]
+    fn negative_book_values_do_not_net_the_sampling_population() {
+        let data: Vec<f64> = (0..500u64).map(|i| if i % 10 == 3 { -400.0 } else { (i % 1000 + 1) as f64 }).collect();
+        let plan = mus_planning(&data, PlanningOptions { tolerable_error: 10_000.0, expected_error: 2_000.0, ..PlanningOptions::default() }).expect("plan");
+        let ext = mus_extraction(&plan, ExtractionOptions { start_point: Some(5.0), obey_n_as_min: true, ..ExtractionOptions::default() }).expect("extract");
+        let values = || ext.sample_population.iter().map(|&(_, v, _)| ext.plan.currency.amount(v));
+        assert!(values().any(|v| v < 0.0));
+        let positive: f64 = values().filter(|&v| v > 0.0).sum();
+        let netted: f64 = values().sum();
+        let n = ext.sample.len();
+        let audited: Vec<f64> = ext.sample.iter().map(|s| ext.plan.currency.amount(s.book_value)).collect();
+        let ev = mus_evaluation(&ext, &audited, EvaluationOptions { bound: EvaluationBound::Binomial, ..Default::default() }).expect("evaluate");
+        let bound = binomial_bound(0.0, n, ext.plan.confidence_level).expect("bound");
+        // The same positive total the selection grid runs over, not the netted book value
+        assert_abs_diff_eq!(ev.overstatement.upper_error_limit, bound * positive, epsilon = 1e-6);
+        assert!(ev.overstatement.upper_error_limit > bound * netted + 1.0);
+    }
+
+    #[test]
+    fn multinomial_without_errors_matches_binomial_zero_bound() {
+        let n = 100;
+        let p = multinomial_bound(&vec![0.0; n], n, 0.95).expect("bound");
+        assert_abs_diff_eq!(p, 1.0 - 0.05f64.powf(1.0 / n as f64), epsilon = 1e-4);
+    }
+
+    #[test]
+    fn multinomial_with_several_taintings_matches_reference() {
+        // Reference: exact outcome enumeration maximised over the simplex by random search
+        let mut t = vec![0.3, 0.3, 0.7];
+        t.resize(30, 0.0);
+        assert_abs_diff_eq!(multinomial_bound(&t, 30, 0.95).expect("bound"), 0.119281, epsilon = 1e-4);
+        let mut t = vec![0.15, 0.5, 1.0];
+        t.resize(40, 0.0);
+        assert_abs_diff_eq!(multinomial_bound(&t, 40, 0.95).expect("bound"), 0.118477, epsilon = 1e-4);
+    }
+}
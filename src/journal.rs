@@ -1,97 +1,633 @@
 use anyhow::{Context, Result};
-use calamine::{open_workbook_auto, Reader};
+use calamine::{open_workbook_auto, Reader, SheetType, SheetVisible};
 use chrono::NaiveDate;
 use csv::ReaderBuilder;
-use std::{collections::HashMap, fs::File, path::Path};
+use encoding_rs::{Encoding, GB18030, UTF_16BE, UTF_16LE, UTF_8};
+use audit_sampling::{Currency, Money};
+use crate::config::{ColumnMapping, ColumnSpec};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::{fmt, fs, path::Path};
 
-#[derive(Debug, Clone)]
-pub struct Record {
-    pub values: HashMap<String, String>,
+/// 单元格：Excel 的数字、日期按原类型保存，CSV 与 Excel 文本保存为共享的字符串
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Empty,
+    Number(f64),
+    Date(NaiveDate),
+    Text(Arc<str>),
+}
+
+impl Cell {
+    fn is_blank(&self) -> bool {
+        match self {
+            Cell::Empty => true,
+            Cell::Text(t) => t.trim().is_empty(),
+            _ => false,
+        }
+    }
+
+    /// 金额：数字单元格直接取值，文本按 `parse_amount` 解析（千分位、括号负数等）
+    pub fn amount(&self) -> f64 {
+        match self {
+            Cell::Number(v) => *v,
+            Cell::Text(t) => parse_amount(t),
+            _ => 0.0,
+        }
+    }
+
+    /// 按币种精度取整到最小货币单位；数字单元格按其最短十进制表示解析，与 Excel 显示的值一致。
+    /// 空白为 0；无法识别的文本为 `None`。
+    pub fn money(&self, currency: Currency) -> Option<Money> {
+        match self {
+            Cell::Number(v) => currency.parse(&v.to_string()),
+            Cell::Text(t) => parse_money(t, currency),
+            _ => Some(Money::default()),
+        }
+    }
+
+    pub fn date(&self) -> Option<NaiveDate> {
+        match self {
+            Cell::Date(d) => Some(*d),
+            Cell::Text(t) => parse_date_flex(t),
+            _ => None,
+        }
+    }
 }
 
+impl fmt::Display for Cell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cell::Empty => Ok(()),
+            Cell::Number(v) => write!(f, "{v}"),
+            Cell::Date(d) => write!(f, "{}", d.format("%Y-%m-%d")),
+            Cell::Text(t) => f.write_str(t),
+        }
+    }
+}
+
+/// 序时账：按列保存单元格，表头只存一份，相同文本（科目名称、方向等）在各行间共享
 #[derive(Debug, Clone)]
 pub struct JournalData {
     pub headers: Vec<String>,
-    pub rows: Vec<Record>,
+    columns: Vec<Vec<Cell>>,
+    /// 表头 → 列号（表头重复时取最后一列）
+    index: HashMap<String, usize>,
+    len: usize,
+}
+
+impl JournalData {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = Record<'_>> {
+        (0..self.len).map(|row| Record { data: self, row, extra: Vec::new() })
+    }
+
+    /// 依次拼接多个工作表并在末尾加 `SOURCE_SHEET_COL` 列记录每行的来源工作表。各表按列名对齐：
+    /// 列为各表表头的并集（按首次出现的顺序），某表没有的列留空。
+    fn concat(parts: Vec<(String, JournalData)>) -> Result<JournalData> {
+        let Some((first, head)) = parts.first() else { anyhow::bail!("所选工作表均为空") };
+        let mut headers = head.headers.clone();
+        for (name, part) in &parts {
+            if part.headers.iter().any(|h| h == SOURCE_SHEET_COL) {
+                anyhow::bail!("工作表“{name}”已有“{SOURCE_SHEET_COL}”列，无法合并");
+            }
+            for h in &part.headers {
+                if !headers.contains(h) {
+                    headers.push(h.clone());
+                }
+            }
+        }
+        headers.push(SOURCE_SHEET_COL.to_string());
+        let mut builder = JournalBuilder::new(headers, Interner::default());
+        for (name, part) in &parts {
+            // 表头与第一张表相同时按位置拼接；否则按列名对齐，列名重复时无法对齐
+            let targets: Vec<usize> = if part.headers == head.headers {
+                (0..part.headers.len()).collect()
+            } else {
+                let mut seen = HashSet::new();
+                if let Some(dup) = part.headers.iter().find(|h| !seen.insert(h.as_str())) {
+                    anyhow::bail!("工作表“{name}”的表头与“{first}”不一致且有重复的列名“{dup}”，无法按列名合并");
+                }
+                part.headers.iter().map(|h| builder.data.index[h]).collect()
+            };
+            for (&t, cells) in targets.iter().zip(&part.columns) {
+                builder.data.columns[t].extend_from_slice(cells);
+            }
+            let source = builder.texts.cell(name);
+            builder.data.len += part.len;
+            let (source_column, columns) = builder.data.columns.split_last_mut().expect("source column");
+            for column in columns {
+                column.resize(builder.data.len, Cell::Empty);
+            }
+            source_column.resize(builder.data.len, source);
+        }
+        Ok(builder.finish())
+    }
+}
+
+/// 共享重复文本：同一字符串只分配一次
+#[derive(Default)]
+struct Interner(HashSet<Arc<str>>);
+
+impl Interner {
+    fn cell(&mut self, s: &str) -> Cell {
+        if s.is_empty() {
+            return Cell::Empty;
+        }
+        if let Some(t) = self.0.get(s) {
+            return Cell::Text(t.clone());
+        }
+        let t: Arc<str> = Arc::from(s);
+        self.0.insert(t.clone());
+        Cell::Text(t)
+    }
+}
+
+/// 逐行装入 `JournalData`，跳过全空行
+struct JournalBuilder {
+    data: JournalData,
+    texts: Interner,
+}
+
+impl JournalBuilder {
+    fn new(headers: Vec<String>, texts: Interner) -> Self {
+        let index = headers.iter().enumerate().map(|(i, h)| (h.clone(), i)).collect();
+        let columns = vec![Vec::new(); headers.len()];
+        JournalBuilder { data: JournalData { headers, columns, index, len: 0 }, texts }
+    }
+
+    /// 装入一行；比表头短的行以空单元格补齐，多出的单元格忽略
+    fn push(&mut self, mut cells: Vec<Cell>) {
+        if cells.iter().all(Cell::is_blank) {
+            return;
+        }
+        cells.resize(self.data.columns.len(), Cell::Empty);
+        for (column, cell) in self.data.columns.iter_mut().zip(cells) {
+            column.push(cell);
+        }
+        self.data.len += 1;
+    }
+
+    fn finish(self) -> JournalData {
+        self.data
+    }
+}
+
+/// 序时账中的一行，另可附加输出列（批次、分层、入选概率）
+#[derive(Debug, Clone)]
+pub struct Record<'a> {
+    data: &'a JournalData,
+    row: usize,
+    extra: Vec<(&'static str, String)>,
+}
+
+impl<'a> Record<'a> {
+    /// 在序时账中的位置（从 0 起，不计表头与空行）
+    pub fn row(&self) -> usize {
+        self.row
+    }
+
+    pub fn cell(&self, column: &str) -> Option<&'a Cell> {
+        self.data.index.get(column).map(|&c| &self.data.columns[c][self.row])
+    }
+
+    /// 单元格的文本（附加列优先）；表头中没有该列时为 None
+    pub fn text(&self, column: &str) -> Option<Cow<'_, str>> {
+        if let Some((_, v)) = self.extra.iter().find(|(c, _)| *c == column) {
+            return Some(Cow::Borrowed(v));
+        }
+        self.cell(column).map(|cell| match cell {
+            Cell::Text(t) => Cow::Borrowed(&**t),
+            other => Cow::Owned(other.to_string()),
+        })
+    }
+
+    pub fn set(&mut self, column: &'static str, value: String) {
+        match self.extra.iter_mut().find(|(c, _)| *c == column) {
+            Some(slot) => slot.1 = value,
+            None => self.extra.push((column, value)),
+        }
+    }
 }
 
 fn normalize_header(h: &str) -> String { h.trim().to_string() }
 
-fn xlsx_to_string<T: calamine::DataType>(cell: &T) -> String {
+fn xlsx_cell<T: calamine::DataType>(cell: &T, texts: &mut Interner) -> Cell {
     // Prefer semantic date rendering only if the cell is marked as datetime
     // or contains ISO8601 datetime text. Avoid misinterpreting numeric amounts as dates.
-    if cell.is_datetime() || cell.is_datetime_iso() {
-        if let Some(dt) = cell.as_date() { return dt.format("%Y-%m-%d").to_string(); }
+    if (cell.is_datetime() || cell.is_datetime_iso())
+        && let Some(dt) = cell.as_date()
+    {
+        return Cell::Date(dt);
     }
-    if let Some(s) = cell.as_string() { return s; }
-    if let Some(i) = cell.as_i64() { return i.to_string(); }
-    if let Some(f) = cell.as_f64() {
-        if (f.fract()).abs() < f64::EPSILON { return format!("{}", f as i64); }
-        return f.to_string();
+    if let Some(s) = cell.get_string() { return texts.cell(s); }
+    if cell.is_int() || cell.is_float() || cell.is_bool() {
+        return cell.as_f64().map_or(Cell::Empty, Cell::Number);
     }
-    if let Some(b) = cell.get_bool() { return b.to_string(); }
-    String::new()
+    Cell::Empty
 }
 
-fn load_excel(path: &Path) -> Result<JournalData> {
-    let mut wb = open_workbook_auto(path).with_context(|| format!("打开 Excel 失败: {}", path.display()))?;
-    // Pick first visible sheet
-    let sheet_names = wb.sheet_names().to_owned();
-    let name = sheet_names
-        .into_iter()
-        .next()
-        .ok_or_else(|| anyhow::anyhow!("Excel 无可读工作表"))?;
-    let range = wb.worksheet_range(&name)?;
+fn range_to_journal(range: &calamine::Range<calamine::Data>, header_rows: Option<(usize, usize)>) -> Result<JournalData> {
+    // 区域从第一个非空单元格所在行开始；按工作表中的行号编号，使 `header_rows` 与 Excel 显示的行号一致
+    let top = range.start().map_or(0, |(row, _)| row as usize);
+    read_table(range.rows().enumerate().map(|(i, r)| Ok((top + i, r))), |r, texts| r.iter().map(|c| xlsx_cell(c, texts)).collect(), header_rows)
+}
+
+/// 自动识别表头时检查的前若干行
+const HEADER_SCAN_ROWS: usize = 30;
+
+/// 表头常用词：包含其一的单元格计 1 分，得分最高的行视为表头
+const HEADER_WORDS: &[&str] = &[
+    "日期", "date", "凭证", "voucher", "摘要", "科目", "account", "借方", "debit", "贷方", "credit", "方向", "direction",
+    "金额", "amount", "发生额", "余额", "balance", "数量", "币种", "本币", "原币", "借正贷负", "净额", "编码", "代码", "编号",
+    "序号", "行号", "制单", "审核", "记账", "对方", "部门", "项目", "客户", "供应商",
+];
+
+/// 多行表头中作为“度量”的上层名称：与下层拼接时放在后面（“金额”+“借方”→“借方金额”）
+const HEADER_MEASURES: &[&str] = &["金额", "发生额", "本期发生额", "本币", "原币", "本币金额", "原币金额", "数量", "amount"];
+
+fn is_header_word(cell: &Cell) -> bool {
+    let Cell::Text(t) = cell else { return false };
+    let l = t.trim().to_lowercase();
+    l.chars().count() <= 20 && !looks_like_data(cell) && HEADER_WORDS.iter().any(|w| l.contains(w))
+}
+
+/// 数字、日期或可解析为数字/日期的文本：只出现在数据行
+fn looks_like_data(cell: &Cell) -> bool {
+    match cell {
+        Cell::Number(_) | Cell::Date(_) => true,
+        Cell::Text(t) => {
+            let (num, _) = clean_amount(t);
+            num.parse::<f64>().is_ok() || parse_date_flex(t).is_some()
+        }
+        Cell::Empty => false,
+    }
+}
+
+fn header_score(row: &[Cell]) -> usize {
+    row.iter().filter(|c| is_header_word(c)).count()
+}
+
+/// 自动识别表头（从 0 起的首尾行）：取得分最高（至少 2 分）的最前一行，否则为第一行；
+/// 其下紧接的、没有数据值且补全了上一行空白（合并单元格）或含表头词的行视为表头的下层，最多 3 行。
+fn detect_header(head: &[Vec<Cell>]) -> (usize, usize) {
+    let Some(first) = (0..head.len()).rev().max_by_key(|&i| header_score(&head[i])).filter(|&i| header_score(&head[i]) >= 2) else {
+        return (0, 0);
+    };
+    let fills_gap = |upper: &[Cell], lower: &[Cell]| lower.iter().enumerate().any(|(c, cell)| !cell.is_blank() && upper.get(c).is_none_or(Cell::is_blank));
+    let mut last = first;
+    while last < first + 2 && last + 1 < head.len() {
+        let (upper, lower) = (&head[last], &head[last + 1]);
+        let continues = !lower.iter().any(looks_like_data) && lower.iter().any(|c| !c.is_blank()) && (fills_gap(upper, lower) || header_score(lower) > 0);
+        if !continues {
+            break;
+        }
+        last += 1;
+    }
+    // 下层表头词更多时（“借方/贷方”之下各有“金额”“数量”），上层至少有两个表头词才并入，以免并入标题行
+    let mut first = first;
+    while first > 0 && last < first + 2 {
+        let (upper, lower) = (&head[first - 1], &head[first]);
+        if upper.iter().any(looks_like_data) || header_score(upper) < 2 || !fills_gap(upper, lower) {
+            break;
+        }
+        first -= 1;
+    }
+    (first, last)
+}
+
+/// 把多行表头压平为一行列名：合并单元格在上层只有最左一格有值，向右沿用到下层有值的空白格；
+/// 上下层拼接时度量词（金额、发生额等）放在后面，如 “金额/借方” 与 “借方/金额” 都得到 “借方金额”。
+fn flatten_header(rows: &[Vec<Cell>]) -> Vec<String> {
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let mut names = vec![String::new(); width];
+    for (level, row) in rows.iter().enumerate() {
+        let below = rows.get(level + 1);
+        let mut carry = String::new();
+        for (c, name) in names.iter_mut().enumerate() {
+            let text = row.get(c).map(|cell| normalize_header(&cell.to_string())).unwrap_or_default();
+            let part = if !text.is_empty() {
+                carry = text.clone();
+                text
+            } else if below.is_some_and(|b| b.get(c).is_some_and(|cell| !cell.is_blank())) {
+                carry.clone()
+            } else {
+                carry.clear();
+                String::new()
+            };
+            *name = match (name.is_empty(), part.is_empty()) {
+                (_, true) => std::mem::take(name),
+                (true, false) => part,
+                (false, false) if HEADER_MEASURES.contains(&name.as_str()) => format!("{part}{name}"),
+                (false, false) => format!("{name}{part}"),
+            };
+        }
+    }
+    names
+}
+
+/// 按行号补齐读取时跳过的空行（CSV 读取器跳过空行，Excel 区域从第一个非空行开始）
+struct NumberedRows<I, R> {
+    rows: I,
+    next: usize,
+    pending: Option<(usize, R)>,
+}
+
+impl<I: Iterator<Item = Result<(usize, R)>>, R> Iterator for NumberedRows<I, R> {
+    type Item = Result<Option<R>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pending.is_none() {
+            match self.rows.next()? {
+                Ok(row) => self.pending = Some(row),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        let at = self.pending.as_ref().map_or(0, |(i, _)| *i);
+        self.next += 1;
+        if self.next <= at {
+            return Some(Ok(None));
+        }
+        Some(Ok(self.pending.take().map(|(_, r)| r)))
+    }
+}
+
+/// 读入表格：`rows` 为（从 0 起的行号, 行），`header_rows` 为表头所在行（从 1 起，含首尾，空行也计数），
+/// None 时在前 `HEADER_SCAN_ROWS` 行中自动识别；表头以上的标题行（公司名称、期间等）丢弃。
+fn read_table<R>(
+    rows: impl Iterator<Item = Result<(usize, R)>>,
+    to_cells: impl Fn(R, &mut Interner) -> Vec<Cell>,
+    header_rows: Option<(usize, usize)>,
+) -> Result<JournalData> {
+    let mut rows = NumberedRows { rows, next: 0, pending: None };
+    let to_cells = |r: Option<R>, texts: &mut Interner| r.map_or_else(Vec::new, |r| to_cells(r, texts));
+    let mut texts = Interner::default();
+    let mut head: Vec<Vec<Cell>> = Vec::new();
+    let scan = header_rows.map_or(HEADER_SCAN_ROWS, |(_, last)| last);
+    while head.len() < scan {
+        match rows.next() {
+            Some(r) => head.push(to_cells(r?, &mut texts)),
+            None => break,
+        }
+    }
+    if head.is_empty() {
+        anyhow::bail!("缺少表头行");
+    }
+    let (first, last) = match header_rows {
+        Some((first, last)) if first >= 1 && first <= last && last <= head.len() => (first - 1, last - 1),
+        Some((first, last)) => anyhow::bail!("表头行 {first}-{last} 超出表格范围（共 {} 行）", head.len()),
+        None => detect_header(&head),
+    };
+    let headers = flatten_header(&head[first..=last]);
+    let mut builder = JournalBuilder::new(headers, texts);
+    for cells in head.drain(last + 1..) {
+        builder.push(cells);
+    }
+    for r in rows {
+        let cells = to_cells(r?, &mut builder.texts);
+        builder.push(cells);
+    }
+    Ok(builder.finish())
+}
 
-    let mut rows_iter = range.rows();
-    let headers_row = rows_iter.next().ok_or_else(|| anyhow::anyhow!("缺少表头行"))?;
-    let headers: Vec<String> = headers_row
+/// 合并多个工作表时记录每行来源的列
+pub const SOURCE_SHEET_COL: &str = "来源工作表";
+
+/// Excel 序时账读取哪些工作表
+#[derive(Debug, Clone, Default)]
+pub enum SheetSelection {
+    /// 第一个可见工作表
+    #[default]
+    FirstVisible,
+    /// 按名称或序号（可见工作表从 1 起）指定；多于一个时合并
+    Listed(Vec<String>),
+    /// 合并全部可见工作表
+    AllVisible,
+}
+
+fn load_excel(path: &Path, opts: &LoadOptions) -> Result<JournalData> {
+    let mut wb = open_workbook_auto(path).with_context(|| format!("打开 Excel 失败: {}", path.display()))?;
+    let all: Vec<String> = wb.sheet_names();
+    let visible: Vec<String> = wb
+        .sheets_metadata()
         .iter()
-        .map(xlsx_to_string)
-        .map(|s| normalize_header(&s))
+        .filter(|s| s.typ == SheetType::WorkSheet && s.visible == SheetVisible::Visible)
+        .map(|s| s.name.clone())
         .collect();
+    let names: Vec<String> = match &opts.sheets {
+        SheetSelection::FirstVisible => vec![visible.first().cloned().ok_or_else(|| anyhow::anyhow!("Excel 无可见工作表"))?],
+        SheetSelection::AllVisible => visible.clone(),
+        SheetSelection::Listed(list) => list
+            .iter()
+            .map(|s| {
+                // 同名工作表优先，其次按可见工作表序号
+                if all.contains(s) {
+                    return Ok(s.clone());
+                }
+                s.trim()
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|i| visible.get(i.checked_sub(1)?).cloned())
+                    .ok_or_else(|| anyhow::anyhow!("找不到工作表“{s}”（可见工作表：{}）", visible.join("、")))
+            })
+            .collect::<Result<_>>()?,
+    };
+    if let [name] = names.as_slice() {
+        return range_to_journal(&wb.worksheet_range(name)?, opts.header_rows);
+    }
+    let mut parts = Vec::new();
+    for name in names {
+        let range = wb.worksheet_range(&name)?;
+        if range.is_empty() { continue; }
+        parts.push((name.clone(), range_to_journal(&range, opts.header_rows).with_context(|| format!("读取工作表失败: {name}"))?));
+    }
+    JournalData::concat(parts)
+}
 
-    let mut rows: Vec<Record> = Vec::new();
-    for r in rows_iter {
-        let mut map: HashMap<String, String> = HashMap::new();
-        for (i, cell) in r.iter().enumerate() {
-            if let Some(h) = headers.get(i) {
-                map.insert(h.clone(), xlsx_to_string(cell));
-            }
+/// 读取已输出的样本工作簿：返回 (工作表名, 数据)，跳过“抽样统计”等汇总表。
+pub fn load_sample_workbook(path: &Path) -> Result<Vec<(String, JournalData)>> {
+    let mut wb = open_workbook_auto(path).with_context(|| format!("打开样本工作簿失败: {}", path.display()))?;
+    let mut out = Vec::new();
+    for name in wb.sheet_names().to_owned() {
+        if name.starts_with("抽样统计") { continue; }
+        let range = wb.worksheet_range(&name)?;
+        if range.is_empty() { continue; }
+        out.push((name.clone(), range_to_journal(&range, Some((1, 1))).with_context(|| format!("读取工作表失败: {name}"))?));
+    }
+    Ok(out)
+}
+
+/// 读取样本工作簿“抽样统计”表中各总体的总体条数（分层总体按层累加）。
+pub fn load_summary_population_sizes(path: &Path) -> Result<HashMap<String, usize>> {
+    let mut wb = open_workbook_auto(path).with_context(|| format!("打开样本工作簿失败: {}", path.display()))?;
+    let mut out: HashMap<String, usize> = HashMap::new();
+    let Some(name) = wb.sheet_names().iter().find(|n| n.starts_with("抽样统计")).cloned() else { return Ok(out) };
+    let summary = range_to_journal(&wb.worksheet_range(&name)?, Some((1, 1)))?;
+    for r in summary.rows() {
+        let (Some(pop), Some(len)) = (r.text("总体名称"), r.text("总体条数")) else { continue };
+        if let Ok(len) = len.trim().parse::<usize>() {
+            *out.entry(pop.trim().to_string()).or_default() += len;
+        }
+    }
+    Ok(out)
+}
+
+/// 读取样本工作簿“抽样统计”表中各总体的样本工作表名（“工作表”列；无该列的旧工作簿为空）。
+pub fn load_summary_sheet_names(path: &Path) -> Result<HashMap<String, String>> {
+    let mut wb = open_workbook_auto(path).with_context(|| format!("打开样本工作簿失败: {}", path.display()))?;
+    let mut out: HashMap<String, String> = HashMap::new();
+    let Some(name) = wb.sheet_names().iter().find(|n| n.starts_with("抽样统计")).cloned() else { return Ok(out) };
+    let summary = range_to_journal(&wb.worksheet_range(&name)?, Some((1, 1)))?;
+    for r in summary.rows() {
+        let (Some(pop), Some(sheet)) = (r.text("总体名称"), r.text("工作表")) else { continue };
+        if !sheet.trim().is_empty() {
+            out.insert(pop.trim().to_string(), sheet.trim().to_string());
         }
-        // Skip completely empty rows
-        if map.values().all(|v| v.trim().is_empty()) { continue; }
-        rows.push(Record { values: map });
     }
-    Ok(JournalData { headers, rows })
+    Ok(out)
+}
+
+/// 读取样本工作簿“抽样统计”表中各总体（未分层）某一列的文本（无该表时为空）。
+fn load_summary_column(path: &Path, column: &str) -> Result<HashMap<String, String>> {
+    let mut wb = open_workbook_auto(path).with_context(|| format!("打开样本工作簿失败: {}", path.display()))?;
+    let mut out: HashMap<String, String> = HashMap::new();
+    let Some(name) = wb.sheet_names().iter().find(|n| n.starts_with("抽样统计")).cloned() else { return Ok(out) };
+    let summary = range_to_journal(&wb.worksheet_range(&name)?, Some((1, 1)))?;
+    for r in summary.rows() {
+        if r.text("分层").is_some_and(|s| !s.trim().is_empty()) { continue; }
+        let (Some(pop), Some(value)) = (r.text("总体名称"), r.text(column)) else { continue };
+        out.insert(pop.trim().to_string(), value.trim().to_string());
+    }
+    Ok(out)
+}
+
+/// 读取样本工作簿“抽样统计”表中各总体（未分层）的种子，用于重放抽取。
+pub fn load_summary_seeds(path: &Path) -> Result<HashMap<String, u64>> {
+    Ok(load_summary_column(path, "种子")?
+        .into_iter()
+        .filter_map(|(pop, seed)| Some((pop, seed.parse::<u64>().ok()?)))
+        .collect())
+}
+
+/// 读取样本工作簿“抽样统计”表中各总体历次扩样的数量（“扩样”列，如“10,5”），用于重放扩样。
+pub fn load_summary_extensions(path: &Path) -> Result<HashMap<String, Vec<usize>>> {
+    let mut out = HashMap::new();
+    for (pop, text) in load_summary_column(path, "扩样")? {
+        let sizes = text
+            .split([',', '，'])
+            .filter(|t| !t.trim().is_empty())
+            .map(|t| t.trim().parse::<usize>().with_context(|| format!("总体“{pop}”的扩样记录“{text}”无法识别")))
+            .collect::<Result<Vec<_>>>()?;
+        out.insert(pop, sizes);
+    }
+    Ok(out)
+}
+
+/// 读取序时账的选项
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    /// CSV 的文本编码；None 时自动识别（BOM → UTF-8 → GB18030/GBK）
+    pub encoding: Option<&'static Encoding>,
+    /// Excel 的工作表
+    pub sheets: SheetSelection,
+    /// 表头所在行（从 1 起，含首尾，多行表头时首尾不同）；None 时自动识别
+    pub header_rows: Option<(usize, usize)>,
 }
 
-fn load_csv(path: &Path) -> Result<JournalData> {
-    let file = File::open(path).with_context(|| format!("打开 CSV 失败: {}", path.display()))?;
-    let mut rdr = ReaderBuilder::new().flexible(true).has_headers(true).from_reader(file);
-    let headers = rdr.headers()?.iter().map(|s| normalize_header(s)).collect::<Vec<_>>();
-    let mut rows: Vec<Record> = Vec::new();
-    for rec in rdr.records() {
-        let rec = rec?;
-        let mut map: HashMap<String, String> = HashMap::new();
-        for (i, v) in rec.iter().enumerate() {
-            if let Some(h) = headers.get(i) { map.insert(h.clone(), v.trim().to_string()); }
+/// 把 CSV 字节解码为文本。指定编码时只去掉该编码的 BOM；自动识别时依次看 BOM、
+/// 无 BOM 的 UTF-16（隔位的 0 字节）、UTF-8 校验，都不符合则按 GB18030（兼容 GBK）解码。
+fn decode_text(bytes: &[u8], forced: Option<&'static Encoding>) -> Result<String> {
+    let (text, encoding, had_errors) = match forced {
+        Some(enc) => {
+            let (text, had_errors) = enc.decode_with_bom_removal(bytes);
+            (text, enc, had_errors)
         }
-        if map.values().all(|v| v.trim().is_empty()) { continue; }
-        rows.push(Record { values: map });
+        None => {
+            let (enc, skip) = Encoding::for_bom(bytes).unwrap_or_else(|| (sniff_encoding(bytes), 0));
+            let (text, had_errors) = enc.decode_without_bom_handling(&bytes[skip..]);
+            (text, enc, had_errors)
+        }
+    };
+    if had_errors {
+        anyhow::bail!("按 {} 解码失败，文件中有无效字节；请用 --encoding 指定正确的编码", encoding.name());
     }
-    Ok(JournalData { headers, rows })
+    Ok(text.into_owned())
 }
 
-pub fn load_journal(path: &Path) -> Result<JournalData> {
+fn sniff_encoding(bytes: &[u8]) -> &'static Encoding {
+    let head = &bytes[..bytes.len().min(4096)];
+    let zeros_at = |parity: usize| head.iter().skip(parity).step_by(2).filter(|&&b| b == 0).count();
+    let (even, odd) = (zeros_at(0), zeros_at(1));
+    // UTF-16 文本中 ASCII 字符（数字、分隔符）的高字节为 0，集中在偶数位（BE）或奇数位（LE）
+    if odd > head.len() / 8 && even * 4 < odd {
+        return UTF_16LE;
+    }
+    if even > head.len() / 8 && odd * 4 < even {
+        return UTF_16BE;
+    }
+    if std::str::from_utf8(bytes).is_ok() { UTF_8 } else { GB18030 }
+}
+
+/// 在逗号、制表符与分号中选分隔符：取前 20 个非空行中出现该字符（引号外）的行数最多者，
+/// 行数相同时比较总次数，仍相同则用逗号。标题行没有分隔符，不影响判断。
+fn sniff_delimiter(text: &str) -> u8 {
+    let lines: Vec<&str> = text.lines().filter(|l| !l.trim().is_empty()).take(20).collect();
+    let count = |line: &str, d: char| {
+        let mut quoted = false;
+        line.chars().filter(|&c| {
+            if c == '"' { quoted = !quoted; }
+            !quoted && c == d
+        }).count()
+    };
+    let score = |d: char| {
+        let counts: Vec<usize> = lines.iter().map(|l| count(l, d)).collect();
+        (counts.iter().filter(|&&c| c > 0).count(), counts.iter().sum::<usize>())
+    };
+    [',', '\t', ';']
+        .into_iter()
+        .fold((',', score(',')), |best, d| {
+            let s = score(d);
+            if s > best.1 { (d, s) } else { best }
+        })
+        .0 as u8
+}
+
+fn load_csv(path: &Path, opts: &LoadOptions) -> Result<JournalData> {
+    let bytes = fs::read(path).with_context(|| format!("打开 CSV 失败: {}", path.display()))?;
+    let text = decode_text(&bytes, opts.encoding).with_context(|| format!("读取 CSV 失败: {}", path.display()))?;
+    let mut rdr = ReaderBuilder::new().flexible(true).has_headers(false).delimiter(sniff_delimiter(&text)).from_reader(text.as_bytes());
+    read_table(
+        rdr.records().map(|r| {
+            // 记录的位置从其前面跳过的空行算起：加上这些空行得到记录所在的行
+            let r = r?;
+            let (line, byte) = r.position().map_or((1, 0), |p| (p.line() as usize, p.byte() as usize));
+            let blank = text.as_bytes()[byte..].iter().take_while(|&&b| b == b'\r' || b == b'\n').filter(|&&b| b == b'\n').count();
+            Ok((line + blank - 1, r))
+        }),
+        |rec, texts| rec.iter().map(|v| texts.cell(v.trim())).collect(),
+        opts.header_rows,
+    )
+}
+
+pub fn load_journal(path: &Path, opts: &LoadOptions) -> Result<JournalData> {
     let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+    let csv = |path: &Path| {
+        if !matches!(opts.sheets, SheetSelection::FirstVisible) {
+            anyhow::bail!("CSV 序时账没有工作表，不能指定 --sheet/--all-sheets");
+        }
+        load_csv(path, opts)
+    };
     match ext.as_str() {
-        "xlsx" | "xlsm" | "xls" => load_excel(path),
-        "csv" => load_csv(path),
+        "xlsx" | "xlsm" | "xls" => load_excel(path, opts),
+        "csv" | "tsv" | "txt" => csv(path),
         _ => {
             if path.is_file() {
                 // Try Excel first
-                load_excel(path).or_else(|_| load_csv(path))
+                load_excel(path, opts).or_else(|_| csv(path))
             } else {
                 Err(anyhow::anyhow!("不支持的文件类型: {}", path.display()))
             }
@@ -161,7 +697,47 @@ pub fn find_voucher_line_col(headers: &[String]) -> Option<String> {
     headers.iter().find(|h| h.trim() == "凭证行号").cloned()
 }
 
-pub fn parse_amount(s: &str) -> f64 {
+/// 序时账中各角色实际使用的列（表头原文）；None 表示该列不存在
+#[derive(Debug, Clone, Default)]
+pub struct Columns {
+    pub date: Option<String>,
+    pub account_code: Option<String>,
+    pub debit: Option<String>,
+    pub credit: Option<String>,
+    pub direction: Option<String>,
+    pub signed_amount: Option<String>,
+    pub report_subject: Option<String>,
+    pub voucher_line: Option<String>,
+}
+
+impl Columns {
+    /// 按配置的 `columns` 映射确定各列：已映射的角色按候选列名精确匹配，找不到即报错；
+    /// 未映射的角色沿用表头关键字识别。
+    pub fn resolve(headers: &[String], mapping: &ColumnMapping) -> Result<Columns> {
+        let pick = |role: &str, spec: Option<&ColumnSpec>, fallback: fn(&[String]) -> Option<String>| -> Result<Option<String>> {
+            let Some(spec) = spec else { return Ok(fallback(headers)) };
+            spec.0
+                .iter()
+                .find_map(|name| headers.iter().find(|h| h.trim() == name.trim()))
+                .cloned()
+                .map(Some)
+                .ok_or_else(|| anyhow::anyhow!("配置 columns.{role} 指定的列在序时账中不存在: {}", spec.0.join(" / ")))
+        };
+        Ok(Columns {
+            date: pick("date", mapping.date.as_ref(), find_date_col)?,
+            account_code: pick("account_code", mapping.account_code.as_ref(), find_acct_code_col)?,
+            debit: pick("debit", mapping.debit.as_ref(), find_debit_col)?,
+            credit: pick("credit", mapping.credit.as_ref(), find_credit_col)?,
+            direction: pick("direction", mapping.direction.as_ref(), find_direction_col)?,
+            signed_amount: pick("signed_amount", mapping.signed_amount.as_ref(), find_signed_amount_col)?,
+            report_subject: pick("report_subject", mapping.report_subject.as_ref(), find_report_subject_col)?,
+            voucher_line: pick("voucher_line", mapping.voucher_line.as_ref(), find_voucher_line_col)?,
+        })
+    }
+}
+
+/// 去掉千分位、货币符号与括号，返回（数字文本，是否为括号负数）。
+fn clean_amount(s: &str) -> (String, bool) {
     let mut t = s.trim().replace(",", "");
     let has_paren = (t.starts_with('(') && t.ends_with(')'))
         || (t.starts_with('（') && t.ends_with('）'));
@@ -171,12 +747,27 @@ pub fn parse_amount(s: &str) -> f64 {
             .to_string();
     }
     t = t
-        .trim_start_matches(|c: char| c == '¥' || c == '￥' || c == '$')
+        .trim_start_matches(['¥', '￥', '$'])
         .to_string();
+    (t, has_paren)
+}
+
+pub fn parse_amount(s: &str) -> f64 {
+    let (t, has_paren) = clean_amount(s);
     let v = t.parse::<f64>().unwrap_or(0.0);
     if has_paren { -v } else { v }
 }
 
+/// 按币种精度解析为整数最小货币单位（不经 f64，超出精度的位数四舍五入）；空白为 0，无法识别时为 `None`。
+pub fn parse_money(s: &str, currency: Currency) -> Option<Money> {
+    let (t, has_paren) = clean_amount(s);
+    if t.is_empty() {
+        return Some(Money::default());
+    }
+    let m = currency.parse(&t)?;
+    Some(if has_paren { -m } else { m })
+}
+
 pub fn parse_date_flex(s: &str) -> Option<NaiveDate> {
     let s = s.trim();
     if s.is_empty() { return None; }
@@ -191,3 +782,277 @@ pub fn parse_date_flex(s: &str) -> Option<NaiveDate> {
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 在临时目录写入文件（文件名带进程号，避免并行测试冲突）
+    fn temp_file(name: &str, bytes: &[u8]) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("audit-sampler-{}-{name}", std::process::id()));
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    fn load_bytes(name: &str, bytes: &[u8], opts: &LoadOptions) -> Result<JournalData> {
+        let path = temp_file(name, bytes);
+        let data = load_journal(&path, opts);
+        fs::remove_file(&path).unwrap();
+        data
+    }
+
+    /// 各行单元格的文本
+    fn texts(data: &JournalData) -> Vec<Vec<String>> {
+        data.rows().map(|r| data.headers.iter().map(|h| r.text(h).unwrap_or_default().into_owned()).collect()).collect()
+    }
+
+    const JOURNAL: &str = "日期,科目名称,借方金额\r\n2024-01-05,应付账款,\"1,200.00\"\r\n2024-02-06,存货,35\r\n";
+
+    fn assert_journal(data: &JournalData) {
+        assert_eq!(data.headers, ["日期", "科目名称", "借方金额"]);
+        assert_eq!(texts(data), [["2024-01-05", "应付账款", "1,200.00"], ["2024-02-06", "存货", "35"]]);
+    }
+
+    #[test]
+    fn csv_encodings_are_detected() {
+        let opts = LoadOptions::default();
+        let (gbk, _, _) = encoding_rs::GBK.encode(JOURNAL);
+        assert_journal(&load_bytes("gbk.csv", &gbk, &opts).unwrap());
+
+        let utf8_bom = [b"\xEF\xBB\xBF".as_slice(), JOURNAL.as_bytes()].concat();
+        assert_journal(&load_bytes("utf8-bom.csv", &utf8_bom, &opts).unwrap());
+
+        let utf16le_bom: Vec<u8> = [0xFF, 0xFE].into_iter().chain(JOURNAL.encode_utf16().flat_map(u16::to_le_bytes)).collect();
+        assert_journal(&load_bytes("utf16le-bom.csv", &utf16le_bom, &opts).unwrap());
+    }
+
+    #[test]
+    fn csv_delimiters_are_detected() {
+        let opts = LoadOptions::default();
+        let tab = "日期\t科目名称\t借方金额\n2024-01-05\t应付账款\t1,200.00\n2024-02-06\t存货\t35\n";
+        assert_journal(&load_bytes("tab.tsv", tab.as_bytes(), &opts).unwrap());
+        // 标题行不含分隔符；引号内的分号不计
+        let semicolon = "某公司序时账\n日期;科目名称;借方金额\n2024-01-05;应付账款;1,200.00\n2024-02-06;\"存货\";35\n";
+        assert_journal(&load_bytes("semicolon.csv", semicolon.as_bytes(), &opts).unwrap());
+    }
+
+    #[test]
+    fn forced_encoding_overrides_detection() {
+        // Windows-1252 的 “é” 不是有效的 UTF-8，自动识别按 GB18030 解码失败
+        let (latin, _, _) = encoding_rs::WINDOWS_1252.encode("date,account,debit\n2024-01-05,Café,10\n");
+        let detected = load_bytes("latin-auto.csv", &latin, &LoadOptions::default());
+        assert!(format!("{:#}", detected.unwrap_err()).contains("gb18030"));
+        let forced = LoadOptions { encoding: Some(encoding_rs::WINDOWS_1252), ..LoadOptions::default() };
+        assert_eq!(texts(&load_bytes("latin-forced.csv", &latin, &forced).unwrap())[0][1], "Café");
+
+        // 强制的编码与文件不符时报错并提示 --encoding
+        let (gbk, _, _) = encoding_rs::GBK.encode(JOURNAL);
+        let forced = LoadOptions { encoding: Some(UTF_8), ..LoadOptions::default() };
+        let err = format!("{:#}", load_bytes("gbk-as-utf8.csv", &gbk, &forced).unwrap_err());
+        assert!(err.contains("UTF-8") && err.contains("--encoding"), "{err}");
+    }
+
+    #[test]
+    fn excel_cells_keep_their_types_and_share_text() {
+        let path = std::env::temp_dir().join(format!("audit-sampler-{}-types.xlsx", std::process::id()));
+        let mut wb = rust_xlsxwriter::Workbook::new();
+        let ws = wb.add_worksheet();
+        let date = rust_xlsxwriter::Format::new().set_num_format("yyyy-mm-dd");
+        for (c, h) in ["日期", "科目名称", "借方金额", "摘要"].iter().enumerate() {
+            ws.write_string(0, c as u16, *h).unwrap();
+        }
+        for (r, (day, account, amount)) in [(5, "应付账款", 1200.5), (6, "存货", 35.0), (7, "应付账款", 8.0)].into_iter().enumerate() {
+            let r = r as u32 + 1;
+            ws.write_datetime_with_format(r, 0, rust_xlsxwriter::ExcelDateTime::from_ymd(2024, 1, day).unwrap(), &date).unwrap();
+            ws.write_string(r, 1, account).unwrap();
+            ws.write_number(r, 2, amount).unwrap();
+        }
+        ws.write_string(3, 3, "付款").unwrap();
+        wb.save(&path).unwrap();
+        let data = load_journal(&path, &LoadOptions::default());
+        fs::remove_file(&path).unwrap();
+        let data = data.unwrap();
+
+        let rows: Vec<Record> = data.rows().collect();
+        assert_eq!(rows[0].cell("日期"), Some(&Cell::Date(NaiveDate::from_ymd_opt(2024, 1, 5).unwrap())));
+        assert_eq!(rows[0].cell("借方金额"), Some(&Cell::Number(1200.5)));
+        assert_eq!(rows[0].cell("摘要"), Some(&Cell::Empty));
+        assert_eq!(rows[2].cell("摘要"), Some(&Cell::Text("付款".into())));
+        assert_eq!(rows[0].text("日期").as_deref(), Some("2024-01-05"));
+        assert_eq!(rows[0].cell("凭证号"), None);
+        // 相同文本只分配一次
+        let (Some(Cell::Text(a)), Some(Cell::Text(b))) = (rows[0].cell("科目名称"), rows[2].cell("科目名称")) else { panic!("text cells") };
+        assert!(Arc::ptr_eq(a, b));
+    }
+
+    #[test]
+    fn records_keep_their_journal_row_after_filtering() {
+        let data = load_bytes("rows.csv", JOURNAL.as_bytes(), &LoadOptions::default()).unwrap();
+        let mut kept: Vec<Record> = data.rows().filter(|r| r.text("科目名称").as_deref() == Some("存货")).collect();
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].row(), 1);
+        kept[0].set("抽样批次", "0".to_string());
+        let original = data.rows().nth(kept[0].row()).unwrap();
+        for h in &data.headers {
+            assert!(std::ptr::eq(kept[0].cell(h).unwrap(), original.cell(h).unwrap()));
+        }
+        assert_eq!(kept[0].text("抽样批次").as_deref(), Some("0"));
+        assert_eq!(original.text("抽样批次"), None);
+    }
+
+    /// 写入各工作表均为文本的 Excel：(表名, 是否隐藏, 各行)
+    fn temp_workbook(name: &str, sheets: &[(&str, bool, &[&[&str]])]) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("audit-sampler-{}-{name}", std::process::id()));
+        let mut wb = rust_xlsxwriter::Workbook::new();
+        for &(sheet, hidden, rows) in sheets {
+            let ws = wb.add_worksheet().set_name(sheet).unwrap().set_hidden(hidden);
+            for (r, row) in rows.iter().enumerate() {
+                for (c, text) in row.iter().enumerate() {
+                    ws.write_string(r as u32, c as u16, *text).unwrap();
+                }
+            }
+        }
+        wb.save(&path).unwrap();
+        path
+    }
+
+    fn load_sheets(path: &Path, sheets: SheetSelection) -> Result<JournalData> {
+        load_journal(path, &LoadOptions { sheets, ..LoadOptions::default() })
+    }
+
+    #[test]
+    fn sheets_are_selected_and_merged_by_column_name() {
+        let path = temp_workbook(
+            "sheets.xlsx",
+            &[
+                ("1月", false, &[&["日期", "科目编码", "借方金额"], &["2024-01-05", "2202", "10"]]),
+                ("说明", true, &[&["日期", "备注"], &["2024-01-31", "隐藏"]]),
+                ("2月", false, &[&["借方金额", "日期", "摘要", "科目编码"], &["20", "2024-02-03", "付款", "1405"], &["30", "2024-02-09", "", "1405"]]),
+            ],
+        );
+        let by_name = load_sheets(&path, SheetSelection::Listed(vec!["2月".into()]));
+        let by_number = load_sheets(&path, SheetSelection::Listed(vec!["2".into()]));
+        let hidden = load_sheets(&path, SheetSelection::Listed(vec!["说明".into()]));
+        let first = load_sheets(&path, SheetSelection::FirstVisible);
+        let merged = load_sheets(&path, SheetSelection::AllVisible);
+        let missing = load_sheets(&path, SheetSelection::Listed(vec!["3月".into()]));
+        fs::remove_file(&path).unwrap();
+
+        let by_name = by_name.unwrap();
+        assert_eq!(by_name.headers, ["借方金额", "日期", "摘要", "科目编码"]);
+        assert_eq!(by_name.len(), 2);
+        // 序号按可见工作表计，隐藏表只能按名称选
+        assert_eq!(by_number.unwrap().headers, by_name.headers);
+        assert_eq!(hidden.unwrap().headers, ["日期", "备注"]);
+        assert_eq!(first.unwrap().headers, ["日期", "科目编码", "借方金额"]);
+        let err = missing.unwrap_err().to_string();
+        assert!(err.contains("3月") && err.contains("1月、2月"), "{err}");
+
+        let merged = merged.unwrap();
+        assert_eq!(merged.headers, ["日期", "科目编码", "借方金额", "摘要", SOURCE_SHEET_COL]);
+        assert_eq!(
+            texts(&merged),
+            [
+                ["2024-01-05", "2202", "10", "", "1月"],
+                ["2024-02-03", "1405", "20", "付款", "2月"],
+                ["2024-02-09", "1405", "30", "", "2月"],
+            ]
+        );
+    }
+
+    #[test]
+    fn merging_refuses_an_existing_source_column() {
+        let path = temp_workbook(
+            "source.xlsx",
+            &[
+                ("1月", false, &[&["日期", "借方金额"], &["2024-01-05", "10"]]),
+                ("2月", false, &[&["日期", "借方金额", SOURCE_SHEET_COL], &["2024-02-03", "20", "x"]]),
+            ],
+        );
+        let merged = load_sheets(&path, SheetSelection::AllVisible);
+        fs::remove_file(&path).unwrap();
+        let err = merged.unwrap_err().to_string();
+        assert!(err.contains("2月") && err.contains(SOURCE_SHEET_COL), "{err}");
+    }
+
+    #[test]
+    fn title_and_blank_rows_above_the_header_are_skipped() {
+        let csv = "某某有限公司序时账\n期间：2024年1月\n,,\n\n日期,科目名称,借方金额\n2024-01-05,应付账款,\"1,200.00\"\n\n2024-02-06,存货,35\n";
+        assert_journal(&load_bytes("title.csv", csv.as_bytes(), &LoadOptions::default()).unwrap());
+        // 指定表头行（从 1 起，含空行）
+        let opts = LoadOptions { header_rows: Some((5, 5)), ..LoadOptions::default() };
+        assert_journal(&load_bytes("title-row.csv", csv.as_bytes(), &opts).unwrap());
+        let opts = LoadOptions { header_rows: Some((9, 10)), ..LoadOptions::default() };
+        assert!(load_bytes("title-range.csv", csv.as_bytes(), &opts).is_err());
+
+        // Excel 的行号同样从第 1 行算起，与第一个非空单元格所在行无关
+        let rows: &[&[&str]] = &[&[], &["某某有限公司序时账"], &[], &["日期", "科目名称", "借方金额"], &["2024-01-05", "应付账款", "1,200.00"], &[], &["2024-02-06", "存货", "35"]];
+        let path = temp_workbook("title.xlsx", &[("序时账", false, rows)]);
+        let detected = load_journal(&path, &LoadOptions::default());
+        let forced = load_journal(&path, &LoadOptions { header_rows: Some((4, 4)), ..LoadOptions::default() });
+        fs::remove_file(&path).unwrap();
+        assert_journal(&detected.unwrap());
+        assert_journal(&forced.unwrap());
+    }
+
+    #[test]
+    fn two_row_headers_are_flattened() {
+        // 上层“金额”为合并单元格（只有最左一格有值），下层为借方、贷方
+        let csv = "序时账\n日期,凭证号,金额,,摘要\n,,借方,贷方,\n2024-01-05,记-1,100,,付款\n";
+        let data = load_bytes("two-row.csv", csv.as_bytes(), &LoadOptions::default()).unwrap();
+        assert_eq!(data.headers, ["日期", "凭证号", "借方金额", "贷方金额", "摘要"]);
+        assert_eq!(texts(&data), [["2024-01-05", "记-1", "100", "", "付款"]]);
+        assert_eq!(Columns::resolve(&data.headers, &ColumnMapping::default()).unwrap().debit.as_deref(), Some("借方金额"));
+
+        // 上层为方向、下层为度量时同样得到“借方金额”
+        let csv = "日期,借方,,贷方,\n,金额,数量,金额,数量\n2024-01-05,100,2,,\n";
+        let data = load_bytes("two-row-measure.csv", csv.as_bytes(), &LoadOptions::default()).unwrap();
+        assert_eq!(data.headers, ["日期", "借方金额", "借方数量", "贷方金额", "贷方数量"]);
+
+        let csv = "日期,金额,\n,借方,贷方\n2024-01-05,100,\n";
+        let opts = LoadOptions { header_rows: Some((1, 2)), ..LoadOptions::default() };
+        assert_eq!(load_bytes("two-row-forced.csv", csv.as_bytes(), &opts).unwrap().headers, ["日期", "借方金额", "贷方金额"]);
+    }
+
+    #[test]
+    fn money_text_that_is_not_an_amount_is_rejected() {
+        let cny = Currency::CNY;
+        assert_eq!(parse_money("1,234.56", cny), Some(Money(123_456)));
+        assert_eq!(parse_money("（1,234.56）", cny), Some(Money(-123_456)));
+        assert_eq!(parse_money("￥12", cny), Some(Money(1_200)));
+        assert_eq!(parse_money("  ", cny), Some(Money(0)));
+        assert_eq!(parse_money("abc", cny), None);
+        assert_eq!(parse_money("1.2.3", cny), None);
+        assert_eq!(Cell::Text("12元".into()).money(cny), None);
+        assert_eq!(Cell::Empty.money(cny), Some(Money(0)));
+        assert_eq!(Cell::Number(0.1 + 0.2).money(cny), Some(Money(30)));
+    }
+
+    fn spec(names: &[&str]) -> Option<ColumnSpec> {
+        Some(ColumnSpec(names.iter().map(|n| n.to_string()).collect()))
+    }
+
+    #[test]
+    fn mapped_columns_resolve_and_unmapped_roles_are_detected() {
+        let headers: Vec<String> = ["记账日期", "科目编码", "借方本币", "贷方本币", "备注日期 "].iter().map(|h| h.to_string()).collect();
+        let mapping = ColumnMapping {
+            date: spec(&["备注日期"]),
+            debit: spec(&["借方金额", "借方本币"]),
+            credit: spec(&["贷方本币"]),
+            ..ColumnMapping::default()
+        };
+        let columns = Columns::resolve(&headers, &mapping).unwrap();
+        assert_eq!(columns.date.as_deref(), Some("备注日期 "));
+        assert_eq!(columns.debit.as_deref(), Some("借方本币"));
+        assert_eq!(columns.credit.as_deref(), Some("贷方本币"));
+        assert_eq!(columns.account_code.as_deref(), Some("科目编码"));
+    }
+
+    #[test]
+    fn missing_mapped_column_is_an_error() {
+        let headers: Vec<String> = ["日期", "科目编码", "借方金额", "贷方金额"].iter().map(|h| h.to_string()).collect();
+        let mapping = ColumnMapping { debit: spec(&["借方本币", "本币借方"]), ..ColumnMapping::default() };
+        let err = Columns::resolve(&headers, &mapping).unwrap_err().to_string();
+        assert!(err.contains("columns.debit") && err.contains("借方本币 / 本币借方"), "{err}");
+    }
+}
@@ -1,21 +1,74 @@
-//! MUS audit sampling: planning and extraction (Rust)
+//! MUS audit sampling: planning, extraction and evaluation (Rust)
 //!
-//! Port of `MUS.planning` and `MUS.extraction` from ref/R.
+//! Port of `MUS.planning`, `MUS.extraction` and `MUS.evaluation` from ref/R.
 //! See Design.md for algorithm details.
 
 use rand::{rngs::StdRng, Rng, SeedableRng};
-use std::cmp::{max, min};
+use serde::{Deserialize, Serialize};
+use std::cmp::max;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 
-#[derive(Debug, Clone)]
+mod artifact;
+mod attribute;
+mod bayes;
+mod evaluation;
+mod money;
+mod rng;
+mod sample_size;
+mod selection;
+mod stratification;
+mod variables;
+
+pub use artifact::SCHEMA_VERSION;
+pub use attribute::{
+    attribute_evaluation, attribute_planning, AttributeEvaluation, AttributeEvaluationOptions, AttributeModel, AttributePlan,
+    AttributePlanningOptions,
+};
+pub use bayes::{
+    bayes_evaluation, bayes_planning, BayesEvaluation, BayesEvaluationOptions, BayesLikelihood, BayesPlan, BayesPlanningOptions, BayesPrior,
+};
+pub use evaluation::{
+    mus_combine, mus_evaluation, CombinedEvaluation, Conclusion, Evaluation, EvaluationBound, EvaluationOptions, MisstatementBound,
+};
+pub use money::{Currency, Money};
+pub use rng::RMersenneTwister;
+use sample_size::SampleSizes;
+pub use selection::{pps_select, PpsScheme, PpsSelection};
+pub use stratification::{allocate_sample, stratify, Allocation, Stratum, StratumBoundaries};
+pub use variables::{
+    variables_evaluation, variables_planning, VariablesEstimator, VariablesEvaluation, VariablesEvaluationOptions, VariablesPlan,
+    VariablesPlanningOptions,
+};
+
+/// Distribution behind the sample size for a given number of errors.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PlanningModel {
+    /// Exact for monetary units drawn without replacement (MUS.planning).
+    #[default]
+    Hypergeometric,
+    /// Monetary units drawn with replacement.
+    Binomial,
+    /// Poisson approximation used by most MUS factor tables.
+    Poisson,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlanningOptions {
     pub col_name_book_values: String,
     pub confidence_level: f64,
+    #[serde(with = "artifact::float")]
     pub tolerable_error: f64,
+    #[serde(with = "artifact::float")]
     pub expected_error: f64,
     pub n_min: usize,
     pub errors_as_pct: bool,
     pub conservative: bool,
     pub combined: bool,
+    pub model: PlanningModel,
+    /// Precision of the book values: each minor unit is one monetary unit. The default
+    /// (whole units) matches R, which treats book values as MU counts.
+    pub currency: Currency,
 }
 
 impl Default for PlanningOptions {
@@ -29,59 +82,191 @@ impl Default for PlanningOptions {
             errors_as_pct: false,
             conservative: false,
             combined: false,
+            model: PlanningModel::default(),
+            currency: Currency::default(),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+/// An item of the population that keeps its identity through planning and extraction.
+pub trait SamplingUnit {
+    /// Identifier reported for the item in `Extraction` (e.g. a voucher line number).
+    fn id(&self) -> String;
+    fn book_value(&self) -> f64;
+}
+
+/// `(id, book value)` pairs.
+impl<I: ToString> SamplingUnit for (I, f64) {
+    fn id(&self) -> String {
+        self.0.to_string()
+    }
+
+    fn book_value(&self) -> f64 {
+        self.1
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Plan {
-    pub data: Vec<f64>,
+    /// Book values in minor units of `currency`; missing or infinite values are zero.
+    pub data: Vec<Money>,
+    /// Identifiers of `data`, in the same order; 1-based positions (R's row names) for `mus_planning`.
+    pub ids: Vec<String>,
     pub col_name_book_values: String,
     pub confidence_level: f64,
     pub tolerable_error: f64,
     pub expected_error: f64,
+    /// Total of the positive book values, in major units.
     pub book_value: f64,
     pub n: usize,
+    /// `book_value / n`. Extraction works with the exact fraction of minor units instead.
+    #[serde(with = "artifact::float")]
     pub high_value_threshold: f64,
     pub tolerable_taintings: f64,
     pub combined: bool,
+    pub model: PlanningModel,
+    pub currency: Currency,
+    /// Planning options kept so `mus_extend` can re-plan with a new expected error.
+    pub n_min: usize,
+    pub conservative: bool,
+    /// Conditions R reports as warnings; planning went ahead regardless.
+    pub warnings: Vec<PlanningWarning>,
+}
+
+impl Plan {
+    /// `data` in major units of `currency`.
+    pub fn book_values(&self) -> Vec<f64> {
+        self.data.iter().map(|&m| self.currency.amount(m)).collect()
+    }
+}
+
+/// A condition `mus_planning` reports instead of failing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PlanningWarning {
+    /// Number of missing or infinite book values; they have no chance for selection.
+    NonFiniteValues(usize),
+    /// Number of zero book values; they have no chance for selection.
+    ZeroValues(usize),
+    /// Number of negative book values; they are ignored (pmax).
+    NegativeValues(usize),
+    /// The tolerable and expected errors lead to an impractically large sample.
+    ImpracticallyLargeSample,
+    /// `tolerable_error >= book_value`: no sampling is necessary and `n` is 0.
+    NoSamplingNecessary,
+    /// The sample size would exceed the number of items, so every item is audited.
+    AuditEverything,
+}
+
+impl fmt::Display for PlanningWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlanningWarning::NonFiniteValues(k) => write!(f, "{k} missing or infinite book values have no chance for selection"),
+            PlanningWarning::ZeroValues(k) => write!(f, "{k} zero book values have no chance for selection"),
+            PlanningWarning::NegativeValues(k) => write!(f, "{k} negative book values are ignored (pmax)"),
+            PlanningWarning::ImpracticallyLargeSample => write!(f, "combination of parameters leads to impractically large sample"),
+            PlanningWarning::NoSamplingNecessary => write!(f, "tolerable.error >= book.value; no sampling necessary, proceeding with n=0"),
+            PlanningWarning::AuditEverything => write!(f, "MUS makes no sense - sample size must exceed population items; auditing everything"),
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+/// A condition `mus_extraction` or `mus_extend` reports alongside the sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExtractionWarning {
+    /// Fewer distinct items than required: `obey_n_as_min` extractions or extensions whose
+    /// sampling units ran past the population.
+    FewerItemsThanPlanned { planned: usize, selected: usize },
+    /// Number of items hit by more than one sampling unit; they count once in the sample.
+    RepeatedHits(usize),
+}
+
+impl fmt::Display for ExtractionWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtractionWarning::FewerItemsThanPlanned { planned, selected } => {
+                write!(f, "{selected} distinct items selected, {planned} planned")
+            }
+            ExtractionWarning::RepeatedHits(k) => write!(f, "{k} items hit by more than one sampling unit"),
+        }
+    }
+}
+
+/// Generator used to draw the random start point.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RngKind {
+    /// `rand::rngs::StdRng` seeded with the full u64 seed.
+    #[default]
+    Std,
+    /// R's Mersenne-Twister: `set.seed(seed); runif(1, 0, interval)`. Seeds must fit in an R integer.
+    RCompatible,
+}
+
+/// How sampling units are placed within the sampling population.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExtractionMode {
+    /// One random start point, then every `interval` monetary units (MUS.extraction).
+    #[default]
+    FixedInterval,
+    /// An independent random point within each interval (cell sampling); not affected by
+    /// periodic patterns in the population order. Evaluate with `EvaluationBound::Cell`.
+    Cell,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ExtractionOptions {
     pub start_point: Option<f64>,
     pub seed: Option<u64>,
     pub obey_n_as_min: bool,
     pub combined: bool,
+    pub rng: RngKind,
+    pub mode: ExtractionMode,
 }
 
-impl Default for ExtractionOptions {
-    fn default() -> Self {
-        Self { start_point: None, seed: None, obey_n_as_min: false, combined: false }
-    }
+/// A high value: an item of `Plan::data` selected with certainty.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HighValue {
+    /// Position in `Plan::data`.
+    pub index: usize,
+    pub id: String,
+    pub book_value: Money,
 }
 
-#[derive(Debug, Clone)]
+/// A sampled item. MU positions count minor units of `Plan::currency` over the sampling population.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExtractedItem {
-    pub book_value: f64,
+    /// Position in `Plan::data`.
+    pub index: usize,
+    pub id: String,
+    pub book_value: Money,
     pub mus_hit: u64,
     pub cum_before: u64,
     pub cum_after: u64,
+    /// Random point drawn within the item's cell (cell sampling only).
+    pub cell_point: Option<f64>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Extraction {
     pub plan: Plan,
+    pub mode: ExtractionMode,
+    /// Fixed interval: the start point. Cell sampling: the random point of the first cell.
     pub start_point: f64,
     pub seed: Option<u64>,
     pub obey_n_as_min: bool,
-    pub high_values: Vec<f64>,
-    pub sample_population: Vec<(f64, u64)>, // (book_value, cumulative MU)
+    pub high_values: Vec<HighValue>,
+    /// (position in plan.data, book_value, cumulative MU in minor units). Extensions remove
+    /// the items that became high values and keep the cumulative numbering of the rest.
+    pub sample_population: Vec<(usize, Money, u64)>,
+    /// Interval the sampling units were placed with (before reassessment).
+    #[serde(with = "artifact::float")]
+    pub interval: f64,
+    #[serde(with = "artifact::float")]
     pub sampling_interval: f64,
     pub sample: Vec<ExtractedItem>,
     pub extensions: usize,
     pub n_qty: Vec<usize>,
     pub combined: bool,
+    pub warnings: Vec<ExtractionWarning>,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -98,72 +283,29 @@ fn sum_nonneg(values: &[f64]) -> f64 {
     values.iter().map(|&v| v.max(0.0)).sum::<f64>()
 }
 
+// Monetary units of the positive amounts.
+fn monetary_units(values: impl IntoIterator<Item = Money>) -> u64 {
+    values.into_iter().map(Money::monetary_units).fold(0, u64::saturating_add)
+}
+
 fn round_to_u64(x: f64) -> u64 {
     // R uses round to nearest, ties to even. Rust's f64::round is ties-to-even too.
     let r = x.round();
     if r < 0.0 { 0 } else { r as u64 }
 }
 
-fn ceil_to_u64(x: f64) -> u64 { if x <= 0.0 { 0 } else { x.ceil() as u64 } }
-
-fn ceil_to_usize(x: f64) -> usize { if x <= 0.0 { 0 } else { x.ceil() as usize } }
-
-// Compute minimal integer k such that P[X <= q] >= alpha for X~Hypergeom(N, m, k)
-// Here N = m + n_black, with successes m.
-fn min_draws_for_cdf_at_most_q(q: u64, alpha: f64, m: u64, n_black: u64, k_max: u64) -> Result<u64, MusError> {
-    use statrs::distribution::{DiscreteCDF, Hypergeometric};
-    let n_total = m + n_black;
-    if n_total == 0 { return Err(MusError::Calculation("population size is zero".into())); }
-    // Find the smallest k such that CDF(q; k) <= alpha
-    // CDF is non-increasing in k for fixed q.
-    let mut lo: u64 = 0;
-    let mut hi: u64 = k_max.min(n_total);
-    let mut ans: Option<u64> = None;
-    while lo <= hi {
-        let mid = lo + (hi - lo) / 2;
-        let hg = Hypergeometric::new(n_total as u64, m as u64, mid as u64)
-            .map_err(|e| MusError::Calculation(format!("hypergeometric new: {e}")))?;
-        let cdf = hg.cdf(q);
-        if cdf <= alpha {
-            ans = Some(mid);
-            if mid == 0 { break; }
-            hi = mid - 1;
-        } else {
-            lo = mid + 1;
-        }
-    }
-    Ok(ans.unwrap_or_else(|| k_max.min(n_total)))
-}
-
-fn calculate_n_hyper(num_errors: u64, alpha: f64, tolerable_error: f64, account_value: f64) -> Result<u64, MusError> {
-    // Mirrors .calculate.n.hyper in R.
-    if !(alpha.is_finite() && alpha > 0.0 && alpha < 1.0) {
-        return Err(MusError::InvalidInput("alpha must be in (0,1)".into()));
-    }
-    if !(tolerable_error.is_finite() && account_value.is_finite() && account_value > 0.0) {
-        return Err(MusError::InvalidInput("tolerable_error and account_value must be finite and > 0".into()));
-    }
-    let max_error_rate = tolerable_error / account_value;
-    let m = round_to_u64(max_error_rate * account_value); // allowed wrong units
-    let n_black = round_to_u64((1.0 - max_error_rate) * account_value); // correct MUs
-    let k_upper = min(n_black + num_errors, round_to_u64(account_value));
-    let k = min_draws_for_cdf_at_most_q(num_errors, alpha, m, n_black, k_upper)?;
-    Ok(k)
-}
-
 fn mus_factor(confidence_level: f64, pct_ratio: f64) -> Result<f64, MusError> {
     use statrs::distribution::{ContinuousCDF, Gamma};
     if !(confidence_level > 0.0 && confidence_level < 1.0) {
         return Err(MusError::InvalidInput("confidence_level must be in (0,1)".into()));
     }
-    if !(pct_ratio >= 0.0 && pct_ratio < 1.0) {
+    if !(0.0..1.0).contains(&pct_ratio) {
         return Err(MusError::InvalidInput("pct_ratio must be in [0,1)".into()));
     }
     if pct_ratio == 0.0 {
         let g = Gamma::new(1.0, 1.0).map_err(|e| MusError::Calculation(format!("gamma: {e}")))?;
         return Ok(g.inverse_cdf(confidence_level));
     }
-    let mut f_prev = 0.0;
     let mut f = {
         let g = Gamma::new(1.0, 1.0).map_err(|e| MusError::Calculation(format!("gamma: {e}")))?;
         g.inverse_cdf(confidence_level)
@@ -171,7 +313,7 @@ fn mus_factor(confidence_level: f64, pct_ratio: f64) -> Result<f64, MusError> {
     let tol = 1e-6;
     let max_iter = 1000;
     for _ in 0..max_iter {
-        f_prev = f;
+        let f_prev = f;
         let shape = 1.0 + pct_ratio * f_prev;
         let g = Gamma::new(shape, 1.0).map_err(|e| MusError::Calculation(format!("gamma: {e}")))?;
         f = g.inverse_cdf(confidence_level);
@@ -190,33 +332,53 @@ fn mus_calc_n_conservative(confidence_level: f64, tolerable_error: f64, expected
     Ok(n as usize)
 }
 
-pub fn mus_planning(book_values: &[f64], mut opts: PlanningOptions) -> Result<Plan, MusError> {
-    if book_values.is_empty() {
+/// Plans over book values in major units of `opts.currency`, rounded to its minor units.
+pub fn mus_planning(book_values: &[f64], opts: PlanningOptions) -> Result<Plan, MusError> {
+    let ids = (1..=book_values.len()).map(|i| i.to_string()).collect();
+    plan_items(book_values, ids, opts, None)
+}
+
+/// `mus_planning` over book values already in minor units of `opts.currency` (e.g. from `Currency::parse`).
+pub fn mus_planning_money(book_values: &[Money], opts: PlanningOptions) -> Result<Plan, MusError> {
+    let ids = (1..=book_values.len()).map(|i| i.to_string()).collect();
+    plan_money(book_values.to_vec(), 0, ids, opts, None)
+}
+
+/// `mus_planning` over identified items: `Extraction` reports their ids and positions.
+pub fn mus_planning_units<U: SamplingUnit>(units: &[U], opts: PlanningOptions) -> Result<Plan, MusError> {
+    let book_values: Vec<f64> = units.iter().map(U::book_value).collect();
+    plan_items(&book_values, units.iter().map(U::id).collect(), opts, None)
+}
+
+// With `fixed_n` the sample size comes from elsewhere (Bayesian planning) instead of the search.
+fn plan_items(book_values: &[f64], ids: Vec<String>, opts: PlanningOptions, fixed_n: Option<usize>) -> Result<Plan, MusError> {
+    let nonfinite = book_values.iter().filter(|&&v| !is_finite_non_nan(v)).count();
+    let data = book_values.iter().map(|&v| if is_finite_non_nan(v) { opts.currency.money(v) } else { Money::default() }).collect();
+    plan_money(data, nonfinite, ids, opts, fixed_n)
+}
+
+// `nonfinite` of the zeros in `data` stand for missing or infinite book values.
+fn plan_money(data: Vec<Money>, nonfinite: usize, ids: Vec<String>, mut opts: PlanningOptions, fixed_n: Option<usize>) -> Result<Plan, MusError> {
+    if data.is_empty() {
         return Err(MusError::InvalidInput("data must contain at least one item".into()));
     }
     if !(opts.confidence_level > 0.0 && opts.confidence_level < 1.0) {
         return Err(MusError::InvalidInput("confidence.level must be in (0,1)".into()));
     }
-    let mut nonfinite = false;
-    let mut has_zero = false;
-    let mut has_negative = false;
-    for &v in book_values {
-        if !is_finite_non_nan(v) { nonfinite = true; }
-        if v == 0.0 { has_zero = true; }
-        if v < 0.0 { has_negative = true; }
-    }
-    if nonfinite {
-        eprintln!("Warning: There are missing or infinite values in book values; they have no chance for selection.");
-    }
-    if has_zero { eprintln!("Warning: There are zeros as book values; they have no chance for selection."); }
-    if has_negative { eprintln!("Warning: There are negative book values; they are ignored (pmax)." ); }
+    let mut warnings = Vec::new();
+    let zeros = data.iter().filter(|m| m.minor_units() == 0).count() - nonfinite;
+    let negatives = data.iter().filter(|m| m.minor_units() < 0).count();
+    if nonfinite > 0 { warnings.push(PlanningWarning::NonFiniteValues(nonfinite)); }
+    if zeros > 0 { warnings.push(PlanningWarning::ZeroValues(zeros)); }
+    if negatives > 0 { warnings.push(PlanningWarning::NegativeValues(negatives)); }
 
-    let book_value = sum_nonneg(book_values);
-    let num_items = book_values.len();
+    let total_units = monetary_units(data.iter().copied());
+    let book_value = total_units as f64 / opts.currency.scale();
+    let num_items = data.len();
 
     if opts.errors_as_pct && opts.tolerable_error.is_finite() && opts.expected_error.is_finite() {
-        opts.tolerable_error = opts.tolerable_error * book_value;
-        opts.expected_error = opts.expected_error * book_value;
+        opts.tolerable_error *= book_value;
+        opts.expected_error *= book_value;
     }
     if !(opts.tolerable_error.is_finite() && opts.tolerable_error > 0.0) {
         return Err(MusError::InvalidInput("tolerable.error must be > 0".into()));
@@ -229,45 +391,46 @@ pub fn mus_planning(book_values: &[f64], mut opts: PlanningOptions) -> Result<Pl
     }
     let too_large = (opts.tolerable_error / book_value) * (1.0 - opts.confidence_level) * (opts.tolerable_error - opts.expected_error).sqrt() < 0.07;
     if too_large {
-        eprintln!("Warning: Combination of parameters leads to impractically large sample.");
+        warnings.push(PlanningWarning::ImpracticallyLargeSample);
     }
 
-    let n_optimal: usize = if opts.tolerable_error >= book_value {
-        eprintln!("Warning: tolerable.error >= book.value; no sampling necessary, proceeding with n=0.");
+    let n_optimal: usize = if let Some(n) = fixed_n {
+        n
+    } else if opts.tolerable_error >= book_value {
+        warnings.push(PlanningWarning::NoSamplingNecessary);
         0
-    } else if calculate_n_hyper(0, 1.0 - opts.confidence_level, opts.tolerable_error, book_value)? < 1 {
-        return Err(MusError::Calculation("Undefined: if 0 errors occur, sample size must be positive".into()));
-    } else if (calculate_n_hyper(num_items as u64, 1.0 - opts.confidence_level, opts.tolerable_error, book_value)? as f64)
-        * opts.expected_error / book_value - (num_items as f64) > 0.0
-    {
-        eprintln!("Warning: MUS makes no sense for your problem - sample size must exceed population items; auditing everything.");
-        num_items
     } else {
-        // Zero expected error: directly solve using hypergeometric without interpolation
-        if opts.expected_error == 0.0 {
-            calculate_n_hyper(0, 1.0 - opts.confidence_level, opts.tolerable_error, book_value)? as usize
+        // The hypergeometric population counts minor units
+        let scale = opts.currency.scale();
+        let mut sizes = SampleSizes::new(opts.model, 1.0 - opts.confidence_level, opts.tolerable_error * scale, total_units as f64)?;
+        let rate = opts.expected_error / book_value;
+        // Largest n with n·rate ≤ i, so `n(i)·rate ≤ i` is the single CDF test `n(i) ≤ limit(i)`
+        let limit = |i: u64| (i as f64 / rate).floor() as u64;
+        if sizes.n(0)? < 1 {
+            return Err(MusError::Calculation("Undefined: if 0 errors occur, sample size must be positive".into()));
+        } else if !sizes.at_most(num_items as u64, limit(num_items as u64))? {
+            warnings.push(PlanningWarning::AuditEverything);
+            num_items
+        } else if opts.expected_error == 0.0 {
+            // Zero expected error: directly solve without interpolation
+            sizes.n(0)? as usize
         } else {
-            // find i where crossing occurs
-            let mut i: u64 = 0;
-            loop {
-                let n_i = calculate_n_hyper(i, 1.0 - opts.confidence_level, opts.tolerable_error, book_value)? as f64;
-                if n_i * opts.expected_error / book_value <= i as f64 { break; }
-                i += 1;
-            }
+            // First i where the crossing occurs; it lies in 0..=num_items by the check above
+            let i = sizes.first_crossing(num_items as u64, limit)?;
             if i == 0 {
-                calculate_n_hyper(0, 1.0 - opts.confidence_level, opts.tolerable_error, book_value)? as usize
+                sizes.n(0)? as usize
             } else {
-                let ni = calculate_n_hyper(i - 1, 1.0 - opts.confidence_level, opts.tolerable_error, book_value)? as f64;
-                let nip1 = calculate_n_hyper(i, 1.0 - opts.confidence_level, opts.tolerable_error, book_value)? as f64;
+                let ni = sizes.n(i - 1)? as f64;
+                let nip1 = sizes.n(i)? as f64;
                 let denom = 1.0 / (nip1 - ni) - opts.expected_error / book_value;
                 if denom <= 0.0 { return Err(MusError::Calculation("denominator non-positive in interpolation".into())); }
                 let n_opt = ((ni / (nip1 - ni) - (i as f64 - 1.0)) / denom).ceil();
                 let mut n_opt = if n_opt < 0.0 { 0 } else { n_opt as usize };
                 if n_opt > num_items {
-                    eprintln!("Warning: MUS makes no sense - n > population size; auditing everything.");
+                    warnings.push(PlanningWarning::AuditEverything);
                     n_opt = num_items;
                 } else if (n_opt as f64 - (nip1 + 1.0)).abs() < f64::EPSILON {
-                    n_opt = (n_opt - 1).max(0);
+                    n_opt -= 1;
                 } else if (n_opt as f64) < ni || (n_opt as f64) > nip1 {
                     return Err(MusError::Calculation(format!(
                         "n.optimal not plausible: n_opt={n_opt}, ni={ni}, nip1={nip1}"
@@ -288,7 +451,8 @@ pub fn mus_planning(book_values: &[f64], mut opts: PlanningOptions) -> Result<Pl
     let tol_taint = if book_value == 0.0 { 0.0 } else { opts.expected_error / book_value * n_final as f64 };
 
     Ok(Plan {
-        data: book_values.iter().cloned().collect(),
+        data,
+        ids,
         col_name_book_values: opts.col_name_book_values,
         confidence_level: opts.confidence_level,
         tolerable_error: opts.tolerable_error,
@@ -298,116 +462,359 @@ pub fn mus_planning(book_values: &[f64], mut opts: PlanningOptions) -> Result<Pl
         high_value_threshold: interval,
         tolerable_taintings: tol_taint,
         combined: opts.combined,
+        model: opts.model,
+        currency: opts.currency,
+        n_min: opts.n_min,
+        conservative: opts.conservative,
+        warnings,
     })
 }
 
+// findInterval equivalent: index i s.t. cum[i-1] < u <= cum[i]
+fn find_interval(cum: &[u64], u: u64) -> Option<usize> {
+    let mut lo: isize = -1; // represents 0 baseline
+    let mut hi: isize = cum.len() as isize - 1;
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2 + 1; // upper mid to avoid tight loop
+        if cum[mid as usize] < u { lo = mid; } else { hi = mid - 1; }
+    }
+    let idx = (hi + 1) as usize;
+    if idx >= cum.len() { None } else { Some(idx) }
+}
+
+// Uniform draws on [a, b] from the selected generator.
+fn uniform_source(kind: RngKind, seed: u64) -> Box<dyn FnMut(f64, f64) -> f64> {
+    match kind {
+        RngKind::Std => {
+            let mut rng = StdRng::seed_from_u64(seed);
+            Box::new(move |a, b| rng.random_range(a..=b))
+        }
+        RngKind::RCompatible => {
+            let mut mt = RMersenneTwister::new((seed & i32::MAX as u64) as i32);
+            Box::new(move |a, b| mt.runif(a, b))
+        }
+    }
+}
+
+// A selection interval of `units / parts` minor units. Kept as a fraction so that grid points
+// and high value tests are exact however many intervals are counted off.
+#[derive(Debug, Clone, Copy)]
+struct Interval {
+    units: u64,
+    parts: u64,
+}
+
+impl Interval {
+    fn minor_units(self) -> f64 {
+        self.units as f64 / self.parts as f64
+    }
+
+    // Whether an item of `m` is at least one interval.
+    fn reached_by(self, m: Money) -> bool {
+        m.minor_units() as i128 * self.parts as i128 >= self.units as i128
+    }
+
+    // `j` intervals as whole minor units and the remaining fraction of one.
+    fn times(self, j: u64) -> (u64, f64) {
+        let p = j as u128 * self.units as u128;
+        let parts = self.parts as u128;
+        ((p / parts) as u64, (p % parts) as f64 / self.parts as f64)
+    }
+
+    // Number of intervals needed to cover `units` minor units.
+    fn cells(self, units: u64) -> usize {
+        if self.units == 0 { return 0; }
+        (units as u128 * self.parts as u128).div_ceil(self.units as u128) as usize
+    }
+}
+
+impl PartialEq for Interval {
+    fn eq(&self, other: &Self) -> bool {
+        self.units as u128 * other.parts as u128 == other.units as u128 * self.parts as u128
+    }
+}
+
 pub fn mus_extraction(plan: &Plan, opts: ExtractionOptions) -> Result<Extraction, MusError> {
     if plan.n == 0 {
         return Err(MusError::InvalidInput("plan.n must be > 0 for extraction".into()));
     }
-    // Split into high values and sampling population
-    let mut high_values: Vec<f64> = Vec::new();
-    let mut sample_population: Vec<f64> = Vec::new();
-    for &v in &plan.data {
-        if v >= plan.high_value_threshold { high_values.push(v); } else { sample_population.push(v); }
+    if plan.ids.len() != plan.data.len() {
+        return Err(MusError::InvalidInput("plan.ids must match plan.data".into()));
     }
-    let mut interval = plan.high_value_threshold;
+    // Split into high values and sampling population (positions in plan.data)
+    let split = |interval: Interval| -> (Vec<usize>, Vec<usize>) { (0..plan.data.len()).partition(|&i| interval.reached_by(plan.data[i])) };
+    let mut interval = Interval { units: monetary_units(plan.data.iter().copied()), parts: plan.n as u64 };
+    let (mut high_values, mut sample_population) = split(interval);
     if opts.obey_n_as_min {
         // perfect sampling interval and stabilize partition
         loop {
-            let old_interval = interval;
-            let pop_sum: f64 = sample_population.iter().sum();
             let denom = plan.n as isize - high_values.len() as isize;
             if denom <= 0 { return Err(MusError::Calculation("no items left for sampling after removing high values".into())); }
-            interval = pop_sum / denom as f64;
-            if (interval - old_interval).abs() <= 0.0 { break; }
+            let next = Interval { units: monetary_units(sample_population.iter().map(|&i| plan.data[i])), parts: denom as u64 };
+            if next == interval { break; }
+            interval = next;
             // re-partition
-            high_values.clear();
-            sample_population.clear();
-            for &v in &plan.data {
-                if v >= interval { high_values.push(v); } else { sample_population.push(v); }
-            }
-            if (interval - old_interval).abs() == 0.0 { break; }
+            (high_values, sample_population) = split(interval);
         }
     }
+    let scale = plan.currency.scale();
+    let interval_amount = interval.minor_units() / scale;
 
-    if let Some(sp) = opts.start_point {
-        if !(sp >= 0.0 && sp <= interval) {
-            return Err(MusError::InvalidInput("start.point must be in [0, interval]".into()));
-        }
+    if let Some(sp) = opts.start_point
+        && !(0.0..=interval_amount).contains(&sp)
+    {
+        return Err(MusError::InvalidInput("start.point must be in [0, interval]".into()));
     }
-    let start_point = match opts.start_point {
-        Some(v) => v,
-        None => {
-            let seed = opts.seed.unwrap_or_else(|| {
-                use std::time::{SystemTime, UNIX_EPOCH};
-                SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .map(|d| d.as_nanos() as u64)
-                    .unwrap_or(0)
-            });
-            let mut rng: StdRng = StdRng::seed_from_u64(seed);
-            rng.random_range(0.0..=interval)
-        }
-    };
-
-    let draws_needed = plan.n.saturating_sub(high_values.len());
-    // Calculate sampling units with R-like rounding
-    let grid_step = (interval * 100.0).round() / 100.0;
-    let mut sampling_units: Vec<u64> = (0..=draws_needed)
-        .map(|j| (start_point + j as f64 * grid_step).round())
-        .map(|x| if x <= 0.0 { 0 } else { x as u64 })
-        .collect();
+    if opts.rng == RngKind::RCompatible
+        && let Some(seed) = opts.seed
+        && seed > i32::MAX as u64
+    {
+        return Err(MusError::InvalidInput("seed must be <= 2147483647 for the R-compatible generator".into()));
+    }
+    if opts.mode == ExtractionMode::Cell && opts.start_point.is_some() {
+        return Err(MusError::InvalidInput("start.point is not used by cell sampling".into()));
+    }
+    let seed = opts.seed.unwrap_or_else(|| {
+        use std::time::{SystemTime, UNIX_EPOCH};
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0)
+    });
+    let mut uniform = uniform_source(opts.rng, seed);
 
-    // Prepare cumulative sums
+    // Prepare cumulative sums in minor units; positions below are in minor units as well
     let mut cum = Vec::<u64>::with_capacity(sample_population.len());
     let mut running: u64 = 0;
-    for &v in &sample_population {
-        let inc = round_to_u64(v);
-        running = running.saturating_add(inc);
+    for &i in &sample_population {
+        running = running.saturating_add(plan.data[i].monetary_units());
         cum.push(running);
     }
     let pop_sum_u = running;
-    sampling_units.retain(|&u| u <= pop_sum_u);
 
-    // findInterval equivalent: index i s.t. cum[i-1] < u <= cum[i]
-    let mut sample: Vec<ExtractedItem> = Vec::with_capacity(sampling_units.len());
-    for &u in &sampling_units {
-        let mut lo: isize = -1; // represents 0 baseline
-        let mut hi: isize = cum.len() as isize - 1;
-        while lo < hi {
-            let mid = lo + (hi - lo) / 2 + 1; // upper mid to avoid tight loop
-            if cum[mid as usize] < u { lo = mid; } else { hi = mid - 1; }
+    let draws_needed = plan.n.saturating_sub(high_values.len());
+    // (sampling unit, random point of its cell)
+    let (start_point, mut sampling_units): (f64, Vec<(u64, Option<f64>)>) = match opts.mode {
+        ExtractionMode::FixedInterval => {
+            let start_point = opts.start_point.unwrap_or_else(|| uniform(0.0, interval_amount));
+            // Unit j = round(start + j · interval), with j · interval counted exactly
+            let units = (0..=draws_needed as u64)
+                .map(|j| {
+                    let (whole, fraction) = interval.times(j);
+                    (whole + round_to_u64(start_point * scale + fraction), None)
+                })
+                .collect();
+            (start_point, units)
+        }
+        ExtractionMode::Cell => {
+            let units: Vec<(u64, Option<f64>)> = (0..interval.cells(pop_sum_u))
+                .map(|j| {
+                    let offset = uniform(0.0, interval_amount);
+                    let (whole, fraction) = interval.times(j as u64);
+                    let u = whole + (fraction + offset * scale).ceil() as u64;
+                    (u.max(1), Some(j as f64 * interval_amount + offset))
+                })
+                .collect();
+            (units.first().and_then(|(_, p)| *p).unwrap_or(0.0), units)
         }
-        let idx = (hi + 1) as usize;
-        if idx >= cum.len() { continue; }
+    };
+    sampling_units.retain(|&(u, _)| u <= pop_sum_u);
+
+    let mut sample: Vec<ExtractedItem> = Vec::with_capacity(sampling_units.len());
+    for &(u, cell_point) in &sampling_units {
+        let Some(idx) = find_interval(&cum, u) else { continue };
         let before = if idx == 0 { 0 } else { cum[idx - 1] };
         let after = cum[idx];
-        sample.push(ExtractedItem { book_value: sample_population[idx], mus_hit: u, cum_before: before, cum_after: after });
+        let index = sample_population[idx];
+        sample.push(ExtractedItem { index, id: plan.ids[index].clone(), book_value: plan.data[index], mus_hit: u, cum_before: before, cum_after: after, cell_point });
     }
 
-    // Reassessed sampling interval
-    let pop_sum: f64 = sample_population.iter().sum();
+    let mut warnings = Vec::new();
+    let mut hits: HashMap<usize, usize> = HashMap::new();
+    sample.iter().for_each(|s| *hits.entry(s.index).or_default() += 1);
+    let repeated = hits.values().filter(|&&k| k > 1).count();
+    if repeated > 0 {
+        warnings.push(ExtractionWarning::RepeatedHits(repeated));
+    }
+    let selected = high_values.len() + hits.len();
+    if opts.obey_n_as_min && selected < plan.n {
+        warnings.push(ExtractionWarning::FewerItemsThanPlanned { planned: plan.n, selected });
+    }
+
+    // Reassessed sampling interval, over the monetary units the sample was drawn from
     let sample_len = sample.len();
-    let sampling_interval = if sample_len == 0 { f64::INFINITY } else { pop_sum / sample_len as f64 };
+    let sampling_interval = if sample_len == 0 { f64::INFINITY } else { pop_sum_u as f64 / scale / sample_len as f64 };
 
-    let sample_population_with_cum: Vec<(f64, u64)> = sample_population
+    let sample_population_with_cum: Vec<(usize, Money, u64)> = sample_population
         .into_iter()
-        .zip(cum.into_iter())
+        .zip(cum)
+        .map(|(i, c)| (i, plan.data[i], c))
+        .collect();
+    let high_values = high_values
+        .into_iter()
+        .map(|index| HighValue { index, id: plan.ids[index].clone(), book_value: plan.data[index] })
         .collect();
 
     Ok(Extraction {
         plan: plan.clone(),
+        mode: opts.mode,
         start_point,
         seed: opts.seed,
         obey_n_as_min: opts.obey_n_as_min,
         high_values,
         sample_population: sample_population_with_cum,
+        interval: interval_amount,
         sampling_interval,
         sample,
         extensions: 0,
         n_qty: vec![sample_len],
         combined: opts.combined,
+        warnings,
+    })
+}
+
+/// How many items `mus_extend` adds to an extraction.
+#[derive(Debug, Clone, Copy)]
+pub enum ExtensionSize {
+    /// Draw exactly this many additional items.
+    AdditionalN(usize),
+    /// Re-plan with this expected error and draw the items missing to reach the new `n`.
+    /// Re-planning is classical (`plan.model`), also for plans from `BayesPlan::to_plan`.
+    ExpectedError(f64),
+}
+
+/// Extends a sample (parity with `MUS.extend`): additional items are drawn by fixed-interval
+/// selection over the not yet sampled part of `sample_population`, with the original start
+/// point scaled to the new interval, so the extension is reproducible from the extraction alone.
+/// As with `obey_n_as_min`, remaining items of at least the new interval become high values.
+/// Cell sampling extractions cannot be extended this way.
+pub fn mus_extend(extract: &Extraction, size: ExtensionSize) -> Result<Extraction, MusError> {
+    if extract.mode == ExtractionMode::Cell {
+        return Err(MusError::InvalidInput("cell sampling extractions cannot be extended".into()));
+    }
+    let hit: HashSet<usize> = extract.sample.iter().map(|s| s.index).collect();
+    let current = extract.high_values.len() + hit.len();
+    let mut plan = extract.plan.clone();
+    let additional = match size {
+        ExtensionSize::AdditionalN(a) => {
+            plan.n += a;
+            a
+        }
+        ExtensionSize::ExpectedError(ee) => {
+            let replanned = mus_planning_money(&plan.data, PlanningOptions {
+                col_name_book_values: plan.col_name_book_values.clone(),
+                confidence_level: plan.confidence_level,
+                tolerable_error: plan.tolerable_error,
+                expected_error: ee,
+                n_min: plan.n_min,
+                conservative: plan.conservative,
+                combined: plan.combined,
+                model: plan.model,
+                currency: plan.currency,
+                ..PlanningOptions::default()
+            })?;
+            let a = replanned.n.saturating_sub(current);
+            plan.expected_error = replanned.expected_error;
+            plan.n = plan.n.max(replanned.n);
+            plan.tolerable_taintings = replanned.tolerable_taintings;
+            a
+        }
+    };
+    if additional == 0 {
+        return Err(MusError::InvalidInput("extension requires at least one additional item".into()));
+    }
+
+    // Remaining (not yet hit) items, as positions in `sample_population`
+    let units = |k: usize| extract.sample_population[k].1.monetary_units();
+    let mut remaining: Vec<usize> = (0..extract.sample_population.len())
+        .filter(|&k| units(k) > 0 && !hit.contains(&extract.sample_population[k].0))
+        .collect();
+    if additional > remaining.len() {
+        return Err(MusError::InvalidInput(format!(
+            "only {} items left for extension, {additional} requested",
+            remaining.len()
+        )));
+    }
+    // Perfect interval over the remaining items; those at or above it are taken as high values
+    let mut new_high: Vec<usize> = Vec::new();
+    let mut interval;
+    loop {
+        interval = Interval { units: remaining.iter().map(|&k| units(k)).sum(), parts: (additional - new_high.len()) as u64 };
+        let (high, rest): (Vec<usize>, Vec<usize>) = remaining.iter().partition(|&&k| interval.reached_by(extract.sample_population[k].1));
+        if high.is_empty() { break; }
+        new_high.extend(high);
+        remaining = rest;
+        if new_high.len() == additional { break; }
+    }
+
+    let mut rem_cum: Vec<u64> = Vec::with_capacity(remaining.len());
+    let mut running: u64 = 0;
+    for &k in &remaining {
+        running += units(k);
+        rem_cum.push(running);
+    }
+    let draws = additional - new_high.len();
+    let start_point = (extract.start_point / extract.interval).clamp(0.0, 1.0) * interval.minor_units();
+    let mut sample = extract.sample.clone();
+    let mut hits: HashMap<usize, usize> = HashMap::new();
+    for j in 0..draws as u64 {
+        let (whole, fraction) = interval.times(j);
+        let u = (whole + round_to_u64(start_point + fraction)).max(1);
+        if u > running { break; }
+        let Some(r) = find_interval(&rem_cum, u) else { continue };
+        let k = remaining[r];
+        let (index, book_value, after) = extract.sample_population[k];
+        *hits.entry(index).or_default() += 1;
+        if hits[&index] > 1 { continue; }
+        let before = after - units(k);
+        let offset = u - if r == 0 { 0 } else { rem_cum[r - 1] };
+        sample.push(ExtractedItem {
+            index,
+            id: plan.ids[index].clone(),
+            book_value,
+            mus_hit: before + offset,
+            cum_before: before,
+            cum_after: after,
+            cell_point: None,
+        });
+    }
+
+    let mut high_values = extract.high_values.clone();
+    high_values.extend(new_high.iter().map(|&k| {
+        let index = extract.sample_population[k].0;
+        HighValue { index, id: plan.ids[index].clone(), book_value: plan.data[index] }
+    }));
+    let moved: HashSet<usize> = new_high.iter().copied().collect();
+    let sample_population: Vec<(usize, Money, u64)> = extract
+        .sample_population
+        .iter()
+        .enumerate()
+        .filter(|(k, _)| !moved.contains(k))
+        .map(|(_, &item)| item)
+        .collect();
+
+    let added = new_high.len() + hits.len();
+    let mut n_qty = extract.n_qty.clone();
+    n_qty.push(added);
+    let mut warnings = extract.warnings.clone();
+    let repeated = hits.values().filter(|&&k| k > 1).count();
+    if repeated > 0 {
+        warnings.push(ExtractionWarning::RepeatedHits(repeated));
+    }
+    if added < additional {
+        warnings.push(ExtractionWarning::FewerItemsThanPlanned { planned: current + additional, selected: current + added });
+    }
+    let pop_sum = monetary_units(sample_population.iter().map(|&(_, v, _)| v));
+    Ok(Extraction {
+        sampling_interval: pop_sum as f64 / plan.currency.scale() / sample.len() as f64,
+        plan,
+        high_values,
+        sample_population,
+        sample,
+        extensions: extract.extensions + 1,
+        n_qty,
+        warnings,
+        ..extract.clone()
     })
 }
 
@@ -429,13 +836,249 @@ mod tests {
         };
         let plan = mus_planning(&data, opts).expect("plan");
         // Basic sanity
-        assert!(plan.n >= 0);
         assert!(plan.high_value_threshold.is_finite());
         // Extraction with fixed seed and obey_n_as_min for determinism
-        let ext = mus_extraction(&plan, ExtractionOptions { start_point: Some(5.0), seed: Some(0), obey_n_as_min: true, combined: false }).expect("extract");
+        let ext = mus_extraction(&plan, ExtractionOptions { start_point: Some(5.0), seed: Some(0), obey_n_as_min: true, combined: false, rng: RngKind::Std, mode: ExtractionMode::FixedInterval }).expect("extract");
         assert!(ext.sample.len() <= plan.n);
         // Interval recompute equals pop_sum / sample_len
-        let pop_sum: f64 = ext.sample_population.iter().map(|(v, _)| *v).sum();
-        assert_abs_diff_eq!(ext.sampling_interval, pop_sum / ext.sample.len() as f64, epsilon = 1e-9);
+        let pop_sum = monetary_units(ext.sample_population.iter().map(|&(_, v, _)| v));
+        assert_abs_diff_eq!(ext.sampling_interval, pop_sum as f64 / ext.sample.len() as f64, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn r_compatible_extraction_matches_r() {
+        // R reference, with book.value = (1:40 * 37) %% 101 + 1 (BV = 2100):
+        //   plan <- MUS.planning(data, tolerable.error = 500, expected.error = 0)   # n = 9
+        //   set.seed(1); MUS.extraction(plan, seed = 1)
+        // start.point = runif(1, 0, 2100 / 9) = 0.2655087 · 233.33 (see rng.rs), sampling units
+        // round(start.point + j · interval), rows by findInterval over the cumulative book values
+        let data: Vec<f64> = (1..=40u32).map(|i| f64::from(i * 37 % 101 + 1)).collect();
+        let plan = mus_planning(&data, PlanningOptions { tolerable_error: 500.0, expected_error: 0.0, ..PlanningOptions::default() }).expect("plan");
+        assert_eq!(plan.n, 9);
+        let ext = mus_extraction(&plan, ExtractionOptions { seed: Some(1), rng: RngKind::RCompatible, ..ExtractionOptions::default() }).expect("extract");
+        assert!(ext.high_values.is_empty());
+        assert_abs_diff_eq!(ext.start_point, 61.952_021, epsilon = 1e-6);
+        let units: Vec<u64> = ext.sample.iter().map(|s| s.mus_hit).collect();
+        assert_eq!(units, [62, 295, 529, 762, 995, 1229, 1462, 1695, 1929]);
+        // R rows 2, 7, 10, 16, 20, 25, 29, 33, 38
+        let rows: Vec<usize> = ext.sample.iter().map(|s| s.index).collect();
+        assert_eq!(rows, [1, 6, 9, 15, 19, 24, 28, 32, 37]);
+    }
+
+    #[test]
+    fn planning_models() {
+        // BV = 1,000,000, TE = 5%, 95%: Poisson n = ceil(ln 20 / 0.05) = 60, binomial ceil(ln 0.05 / ln 0.95) = 59
+        let data = vec![1000.0; 1000];
+        let n = |model, expected_error| {
+            mus_planning(&data, PlanningOptions { confidence_level: 0.95, tolerable_error: 50_000.0, expected_error, model, ..PlanningOptions::default() })
+                .expect("plan")
+                .n
+        };
+        assert_eq!(n(PlanningModel::Poisson, 0.0), 60);
+        assert_eq!(n(PlanningModel::Binomial, 0.0), 59);
+        assert_eq!(n(PlanningModel::Hypergeometric, 0.0), 59);
+        // With expected errors, MUS.planning interpolates between n(i − 1) and n(i) at the first
+        // crossing i: ceil((n(i−1) / Δ − (i − 1)) / (1 / Δ − EE / BV)), Δ = n(i) − n(i−1).
+        // Reference n(i) from exact Poisson, binomial and hypergeometric CDFs (1,000,000 units).
+        // EE = 1%: i = 1 between 60 and 95 (Poisson), 59 and 93 (binomial and hypergeometric)
+        assert_eq!(n(PlanningModel::Poisson, 10_000.0), 93);
+        assert_eq!(n(PlanningModel::Binomial, 10_000.0), 90);
+        assert_eq!(n(PlanningModel::Hypergeometric, 10_000.0), 90);
+        // EE = 2%: the crossing is at i = 4, between n(3) and n(4) = 156 and 184 (Poisson),
+        // 153 and 181 (binomial and hypergeometric)
+        assert_eq!(n(PlanningModel::Poisson, 20_000.0), 164);
+        assert_eq!(n(PlanningModel::Binomial, 20_000.0), 157);
+        assert_eq!(n(PlanningModel::Hypergeometric, 20_000.0), 157);
+    }
+
+    #[test]
+    fn extension_draws_new_items_only() {
+        let data: Vec<f64> = (0..500u64).map(|i| (i % 1000 + 1) as f64).collect();
+        let plan = mus_planning(&data, PlanningOptions { tolerable_error: 10_000.0, expected_error: 0.0, ..PlanningOptions::default() }).expect("plan");
+        let ext = mus_extraction(&plan, ExtractionOptions { start_point: Some(5.0), obey_n_as_min: true, ..ExtractionOptions::default() }).expect("extract");
+        let extended = mus_extend(&ext, ExtensionSize::AdditionalN(10)).expect("extend");
+        assert_eq!(extended.extensions, 1);
+        assert_eq!(extended.n_qty, vec![ext.sample.len(), 10]);
+        assert_eq!(extended.sample.len(), ext.sample.len() + 10);
+        let hits: HashSet<usize> = extended.sample.iter().map(|s| s.index).collect();
+        assert_eq!(hits.len(), extended.sample.len());
+        for item in &extended.sample {
+            assert!(item.cum_before < item.mus_hit && item.mus_hit <= item.cum_after);
+        }
+    }
+
+    #[test]
+    fn extension_counts_repeated_hits_once() {
+        // Six remaining items of 2 units over 5 draws: interval 2.4 and start 0, so grid points
+        // 0 (raised to 1) and 2 both land in the first item
+        let data = vec![2.0, 2.0, 2.0, 2.0, 2.0, 2.0, 40.0];
+        let plan = mus_planning(&data, PlanningOptions { tolerable_error: 20.0, expected_error: 0.0, ..PlanningOptions::default() }).expect("plan");
+        let ext = mus_extraction(&plan, ExtractionOptions { seed: Some(1), ..ExtractionOptions::default() }).expect("extract");
+        let ext = Extraction {
+            start_point: 0.0,
+            interval: 4.0,
+            high_values: vec![HighValue { index: 6, id: "7".into(), book_value: Money(40) }],
+            sample_population: (0..6).map(|i| (i, Money(2), 2 * (i as u64 + 1))).collect(),
+            sample: Vec::new(),
+            warnings: Vec::new(),
+            ..ext
+        };
+        let extended = mus_extend(&ext, ExtensionSize::AdditionalN(5)).expect("extend");
+        let hits: Vec<u64> = extended.sample.iter().map(|s| s.mus_hit).collect();
+        assert_eq!(hits, [1, 5, 7, 10]);
+        assert_eq!(extended.sample[0].index, 0);
+        assert_eq!(extended.n_qty.last(), Some(&4));
+        assert_eq!(extended.warnings, vec![ExtractionWarning::RepeatedHits(1), ExtractionWarning::FewerItemsThanPlanned { planned: 6, selected: 5 }]);
+    }
+
+    #[test]
+    fn extension_takes_large_remaining_items_as_high_values() {
+        let data: Vec<f64> = (0..500u64).map(|i| (i % 1000 + 1) as f64).collect();
+        let plan = mus_planning(&data, PlanningOptions { tolerable_error: 10_000.0, expected_error: 0.0, ..PlanningOptions::default() }).expect("plan");
+        let ext = mus_extraction(&plan, ExtractionOptions { start_point: Some(5.0), obey_n_as_min: true, ..ExtractionOptions::default() }).expect("extract");
+        assert!(ext.high_values.is_empty());
+        // 300 more items out of ~470: the new interval falls below the largest remaining values
+        let extended = mus_extend(&ext, ExtensionSize::AdditionalN(300)).expect("extend");
+        let high: HashSet<usize> = extended.high_values.iter().map(|h| h.index).collect();
+        let hits: HashSet<usize> = extended.sample.iter().map(|s| s.index).collect();
+        assert!(!high.is_empty());
+        assert!(high.is_disjoint(&hits));
+        assert_eq!(hits.len(), extended.sample.len());
+        assert_eq!(high.len() + hits.len(), ext.sample.len() + 300);
+        assert!(extended.warnings.is_empty());
+        let lowest_high = extended.high_values.iter().map(|h| h.book_value).min().expect("high values");
+        assert!(extended.sample[ext.sample.len()..].iter().all(|s| s.book_value < lowest_high));
+        assert!(extended.sample_population.iter().all(|&(i, _, _)| !high.contains(&i)));
+        // A second extension scales the same start point and never repeats an item
+        let again = mus_extend(&extended, ExtensionSize::AdditionalN(20)).expect("extend");
+        let all: HashSet<usize> = again.sample.iter().map(|s| s.index).chain(again.high_values.iter().map(|h| h.index)).collect();
+        assert_eq!(all.len(), again.sample.len() + again.high_values.len());
+        assert_eq!(again.n_qty, vec![ext.sample.len(), 300, 20]);
+    }
+
+    #[test]
+    fn extension_replans_with_the_original_options() {
+        let data: Vec<f64> = (0..500u64).map(|i| (i % 1000 + 1) as f64).collect();
+        let opts = PlanningOptions { tolerable_error: 10_000.0, expected_error: 0.0, n_min: 40, conservative: true, ..PlanningOptions::default() };
+        let plan = mus_planning(&data, opts.clone()).expect("plan");
+        let ext = mus_extraction(&plan, ExtractionOptions { start_point: Some(5.0), obey_n_as_min: true, ..ExtractionOptions::default() }).expect("extract");
+        let extended = mus_extend(&ext, ExtensionSize::ExpectedError(3_000.0)).expect("extend");
+        let replanned = mus_planning(&data, PlanningOptions { expected_error: 3_000.0, ..opts.clone() }).expect("plan");
+        let plain = mus_planning(&data, PlanningOptions { expected_error: 3_000.0, n_min: 0, conservative: false, ..opts }).expect("plan");
+        assert!(replanned.n > plain.n);
+        assert_eq!(extended.plan.n, replanned.n);
+        assert_eq!(extended.high_values.len() + extended.sample.len(), replanned.n);
+    }
+
+    #[test]
+    fn extension_rejects_cell_sampling() {
+        let data: Vec<f64> = (0..500u64).map(|i| (i % 1000 + 1) as f64).collect();
+        let plan = mus_planning(&data, PlanningOptions { tolerable_error: 10_000.0, expected_error: 0.0, ..PlanningOptions::default() }).expect("plan");
+        let ext = mus_extraction(&plan, ExtractionOptions { seed: Some(7), mode: ExtractionMode::Cell, ..ExtractionOptions::default() }).expect("extract");
+        assert!(matches!(mus_extend(&ext, ExtensionSize::AdditionalN(5)), Err(MusError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn cell_sampling_draws_one_point_per_cell() {
+        let data: Vec<f64> = (0..500u64).map(|i| (i % 1000 + 1) as f64).collect();
+        let plan = mus_planning(&data, PlanningOptions { tolerable_error: 10_000.0, expected_error: 0.0, ..PlanningOptions::default() }).expect("plan");
+        let opts = ExtractionOptions { seed: Some(7), obey_n_as_min: true, mode: ExtractionMode::Cell, ..ExtractionOptions::default() };
+        let ext = mus_extraction(&plan, opts.clone()).expect("extract");
+        let interval = ext.interval;
+        assert_eq!(ext.sample.len(), plan.n - ext.high_values.len());
+        for (j, item) in ext.sample.iter().enumerate() {
+            let point = item.cell_point.expect("cell point");
+            assert!(point >= j as f64 * interval && point <= (j + 1) as f64 * interval);
+            assert!(item.cum_before < item.mus_hit && item.mus_hit <= item.cum_after);
+        }
+        assert_eq!(ext.start_point, ext.sample[0].cell_point.unwrap());
+        // Same seed, same cells
+        let again = mus_extraction(&plan, opts).expect("extract");
+        assert!(ext.sample.iter().zip(&again.sample).all(|(a, b)| a.mus_hit == b.mus_hit));
+        assert_eq!(EvaluationOptions::for_extraction(&ext).bound, EvaluationBound::Cell);
+    }
+
+    #[test]
+    fn extraction_counts_minor_units() {
+        // Amounts with cents: whole-unit MUs drop the 0.30 items and blur the ranges
+        let cny = Currency::CNY;
+        let data: Vec<f64> = (0..400u64).map(|i| if i % 4 == 0 { 0.3 } else { (i * 37 % 500) as f64 + 0.01 * (i % 100) as f64 }).collect();
+        let opts = PlanningOptions { tolerable_error: 2_500.0, expected_error: 0.0, currency: cny, ..PlanningOptions::default() };
+        let plan = mus_planning(&data, opts.clone()).expect("plan");
+        let units = mus_planning(&data, PlanningOptions { currency: Currency::UNITS, ..opts }).expect("plan");
+        assert!(plan.n >= units.n && plan.n <= units.n + 1);
+        let ext = mus_extraction(&plan, ExtractionOptions { seed: Some(3), ..ExtractionOptions::default() }).expect("extract");
+        let mut before = 0u64;
+        for (&(i, v, cum), &d) in ext.sample_population.iter().zip(&data) {
+            assert_eq!(v, cny.money(data[i]));
+            assert_eq!(cum - before, cny.money(d).monetary_units());
+            before = cum;
+        }
+        for item in &ext.sample {
+            assert_eq!(item.cum_after - item.cum_before, item.book_value.monetary_units());
+            assert!(item.cum_before < item.mus_hit && item.mus_hit <= item.cum_after);
+        }
+        let total: Money = ext.sample_population.iter().map(|&(_, v, _)| v).sum();
+        assert_eq!(before, total.monetary_units());
+        assert_eq!(ext.sample.len(), plan.n - ext.high_values.len());
+    }
+
+    #[test]
+    fn extraction_grid_does_not_drift() {
+        // ~5,000 draws at an interval of a fraction of a fen: unit j is exactly round(start + j · interval)
+        let data: Vec<f64> = (0..20_000u64).map(|i| (i * 7_919 % 100_000) as f64 / 100.0 + 1.0).collect();
+        let plan = mus_planning(&data, PlanningOptions { tolerable_error: 1e6, expected_error: 0.0, currency: Currency::CNY, ..PlanningOptions::default() }).expect("plan");
+        let n = 4_999u64;
+        let plan = Plan { n: n as usize, high_value_threshold: plan.book_value / n as f64, ..plan };
+        let ext = mus_extraction(&plan, ExtractionOptions { start_point: Some(12.5), ..ExtractionOptions::default() }).expect("extract");
+        assert!(ext.high_values.is_empty());
+        assert_eq!(ext.sample.len(), n as usize);
+        let units = monetary_units(plan.data.iter().copied()) as u128;
+        assert_ne!(units % n as u128, 0);
+        for (j, item) in ext.sample.iter().enumerate() {
+            let exact = (2 * j as u128 * units + n as u128) / (2 * n as u128);
+            assert_eq!(item.mus_hit, 1_250 + exact as u64);
+        }
+    }
+
+    #[test]
+    fn warnings_are_returned_not_printed() {
+        let mut data: Vec<f64> = (0..200u64).map(|i| (i % 50 + 1) as f64).collect();
+        data.extend([0.0, 0.0, -5.0, f64::NAN]);
+        let plan = mus_planning(&data, PlanningOptions { tolerable_error: 1_000.0, expected_error: 0.0, ..PlanningOptions::default() }).expect("plan");
+        assert_eq!(plan.warnings, vec![PlanningWarning::NonFiniteValues(1), PlanningWarning::ZeroValues(2), PlanningWarning::NegativeValues(1)]);
+        let none = mus_planning(&data, PlanningOptions { tolerable_error: 1e6, expected_error: 0.0, ..PlanningOptions::default() }).expect("plan");
+        assert_eq!(none.n, 0);
+        assert!(none.warnings.contains(&PlanningWarning::NoSamplingNecessary));
+        let all = mus_planning(&data[..20], PlanningOptions { tolerable_error: 20.0, expected_error: 10.0, ..PlanningOptions::default() }).expect("plan");
+        assert!(all.warnings.contains(&PlanningWarning::AuditEverything));
+        // Cell sampling: two neighbouring cells can both land in an item that straddles their border
+        let skewed: Vec<f64> = (0..60u64).map(|i| if i % 3 == 0 { 400.0 } else { 7.0 }).collect();
+        let plan = mus_planning(&skewed, PlanningOptions { tolerable_error: 1_000.0, expected_error: 0.0, ..PlanningOptions::default() }).expect("plan");
+        let ext = mus_extraction(&plan, ExtractionOptions { seed: Some(5), obey_n_as_min: true, mode: ExtractionMode::Cell, ..ExtractionOptions::default() }).expect("extract");
+        assert_eq!(ext.sample.len(), plan.n);
+        assert_eq!(ext.warnings, vec![ExtractionWarning::RepeatedHits(1), ExtractionWarning::FewerItemsThanPlanned { planned: plan.n, selected: plan.n - 1 }]);
+        assert_eq!(ext.warnings[1].to_string(), format!("{} distinct items selected, {} planned", plan.n - 1, plan.n));
+    }
+
+    #[test]
+    fn extraction_reports_item_positions_and_ids() {
+        // Many items share a value; ids and positions tell them apart
+        let units: Vec<(String, f64)> = (0..300usize).map(|i| (format!("V{i:03}"), if i % 50 == 7 { 5_000.0 } else { (i % 4 * 100 + 50) as f64 })).collect();
+        let plan = mus_planning_units(&units, PlanningOptions { tolerable_error: 4_000.0, expected_error: 0.0, ..PlanningOptions::default() }).expect("plan");
+        assert_eq!(plan.ids[7], "V007");
+        let ext = mus_extraction(&plan, ExtractionOptions { seed: Some(1), ..ExtractionOptions::default() }).expect("extract");
+        let high: Vec<&str> = ext.high_values.iter().map(|h| h.id.as_str()).collect();
+        assert_eq!(high, ["V007", "V057", "V107", "V157", "V207", "V257"]);
+        let extended = mus_extend(&ext, ExtensionSize::AdditionalN(5)).expect("extend");
+        for item in &extended.sample {
+            assert_eq!((item.id.as_str(), plan.currency.amount(item.book_value)), (units[item.index].0.as_str(), units[item.index].1));
+        }
+        let positions: HashSet<usize> = extended.sample.iter().map(|s| s.index).collect();
+        assert_eq!(positions.len(), extended.sample.len());
+        // Plain book values are identified by 1-based row numbers, as R's row names
+        let plan = mus_planning_money(&plan.data, PlanningOptions { tolerable_error: 4_000.0, expected_error: 0.0, ..PlanningOptions::default() }).expect("plan");
+        let ext = mus_extraction(&plan, ExtractionOptions { seed: Some(1), ..ExtractionOptions::default() }).expect("extract");
+        assert!(ext.sample.iter().all(|s| s.id == (s.index + 1).to_string()));
     }
 }
@@ -5,8 +5,8 @@ mod sampling;
 use anyhow::{bail, Context, Result};
 use chrono::NaiveDate;
 use clap::{Parser, ValueEnum};
-use config::ConfigMap;
-use journal::{load_journal, JournalData};
+use config::Config;
+use journal::{load_journal, Columns, JournalData, LoadOptions, SheetSelection};
 use sampling::{build_population, perform_mus_sampling_with_rules, perform_random_sampling_with_rules, ResolvedRule};
 use std::path::PathBuf;
 
@@ -14,6 +14,44 @@ use std::path::PathBuf;
 enum Method {
     Mus,
     Random,
+    Attribute,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum AttributeModelArg {
+    Binomial,
+    Poisson,
+    Hypergeometric,
+}
+
+#[derive(Debug, Clone, PartialEq, ValueEnum)]
+enum PlanningModelArg {
+    Hypergeometric,
+    Binomial,
+    Poisson,
+}
+
+#[derive(Debug, Clone, PartialEq, ValueEnum)]
+enum SelectionArg {
+    Systematic,
+    RandomPps,
+    Sieve,
+    StratifiedSieve,
+    Sampford,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum StratifyMode {
+    Fixed,
+    EqualValue,
+    SqrtF,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum AllocationMode {
+    Proportional,
+    Neyman,
+    Value,
 }
 
 #[derive(Parser, Debug)]
@@ -23,7 +61,7 @@ enum Method {
     about = "命令行审计抽样工具（支持 MUS 与随机抽样）",
     long_about = "\
 一个用于从会计序时账进行审计抽样的命令行工具：\n\
-- 两种方法：MUS（货币单元抽样）与随机抽样；\n\
+- 三种方法：MUS（货币单元抽样）、随机抽样与属性抽样（控制测试）；\n\
 - 可按期间、报表科目与方向（借/贷）构建总体；\n\
 - 配置文件可选，所有字段均可选；不提供配置时将严格使用‘报表科目’列枚举科目；\n\
 - 输出一个 Excel，每个总体一个工作表（表名按规则或自动命名）；\n\
@@ -32,20 +70,49 @@ enum Method {
 struct Args {
     /// 序时账文件路径（Excel .xlsx/.xls 或 CSV）
     /// 注意：会自动识别中文/英文常见列，如 日期/科目编码/借方金额/贷方金额
-    #[arg(long, value_name = "FILE")] 
-    journal: PathBuf,
+    #[arg(long, value_name = "FILE", required_unless_present = "evaluate_attribute")] 
+    journal: Option<PathBuf>,
+
+    /// CSV 序时账的文本编码（如 utf-8、gbk、gb18030、utf-16le）；默认自动识别：
+    /// 有 BOM 按 BOM，能按 UTF-8 解码则为 UTF-8，否则按 GB18030（兼容用友/金蝶导出的 GBK）
+    #[arg(long, value_name = "LABEL")]
+    encoding: Option<String>,
+
+    /// Excel 序时账的工作表：名称或序号（可见工作表从 1 起）；默认第一个可见工作表。
+    /// 给出多个时按顺序合并（表头须一致），并新增“来源工作表”列
+    #[arg(long, num_args = 1.., value_name = "NAME|INDEX")]
+    sheet: Vec<String>,
+
+    /// 合并 Excel 序时账的全部可见工作表（如每月一张表，表头须一致），并新增“来源工作表”列
+    #[arg(long, default_value_t = false, conflicts_with = "sheet")]
+    all_sheets: bool,
+
+    /// 表头所在行（从 1 起），多行表头写作“首-尾”（如 3-4，合并单元格的上层名称会拼到下层，得到“借方金额”等列名）；
+    /// 默认在前 30 行中按常见列名自动识别，跳过公司名称、期间等标题行
+    #[arg(long, value_name = "N|N-M")]
+    header_row: Option<String>,
 
     /// 期间开始日期，格式：YYYY-MM-DD（含边界）
-    #[arg(long, value_name = "YYYY-MM-DD")] 
-    start: String,
+    #[arg(long, value_name = "YYYY-MM-DD", required_unless_present = "evaluate_attribute")] 
+    start: Option<String>,
 
     /// 期间结束日期，格式：YYYY-MM-DD（含边界）
-    #[arg(long, value_name = "YYYY-MM-DD")] 
-    end: String,
+    #[arg(long, value_name = "YYYY-MM-DD", required_unless_present = "evaluate_attribute")] 
+    end: Option<String>,
+
+    /// 抽样方法：mus、random 或 attribute（属性抽样，按可容忍/预计偏差率规划样本量后随机抽取）
+    #[arg(long, value_enum, required_unless_present = "evaluate_attribute")]
+    method: Option<Method>,
 
-    /// 抽样方法：mus 或 random
-    #[arg(long, value_enum)]
-    method: Method,
+    /// 金额币种（ISO 代码，如 CNY、USD、JPY）：按其小数位数把金额解析为整数最小货币单位，
+    /// MUS 以最小单位（如分）为货币单元；默认 CNY（2 位小数）。
+    /// 注意库与 R 默认以金额本身为货币单元，需与之一致时用 --currency-decimals 0
+    #[arg(long, value_name = "CODE", default_value = "CNY")]
+    currency: String,
+
+    /// 币种小数位数：覆盖 --currency 的默认精度（0–6），用于未内置的币种
+    #[arg(long, value_name = "N")]
+    currency_decimals: Option<u8>,
 
     /// 重要性水平（MUS）：如未给出 --tolerable-misstatement，则以此作为 TE；
     /// 仅 MUS 需要二者之一（materiality 或 tolerable-misstatement）。
@@ -65,6 +132,41 @@ struct Args {
     #[arg(long, default_value_t = 0.90)]
     confidence: f64,
 
+    /// 以计划样本量为最低样本量（MUS）：按完美抽样间隔迭代剔除高值项目后再系统抽取
+    #[arg(long, default_value_t = false)]
+    obey_n_as_min: bool,
+
+    /// 单元抽样（MUS）：在每个抽样间隔内独立随机取一点（而非固定间隔），不受序时账排列周期性影响
+    #[arg(long, default_value_t = false, conflicts_with = "start_point")]
+    cell_sampling: bool,
+
+    /// 固定起始点（MUS）：取值于 [0, 抽样间隔]；缺省时随机生成
+    #[arg(long, value_name = "AMOUNT")]
+    start_point: Option<f64>,
+
+    /// 选样方式（MUS）：systematic（系统选样，默认）、random-pps（随机 PPS，放回）、
+    /// sieve（Hoogduin 修正筛选抽样：按 金额 ÷ 随机数 排序取前 n 条）、stratified-sieve（分层随机数筛选抽样，样本量在 n 附近）、
+    /// sampford（Sampford 不放回 PPS）；非系统选样的样本附“入选概率”列
+    #[arg(long, value_enum, default_value_t = SelectionArg::Systematic)]
+    selection: SelectionArg,
+
+    /// MUS 规划模型：hypergeometric（超几何，默认，与 R MUS.planning 一致）、
+    /// binomial（二项）、poisson（泊松，即常用的置信系数 ÷ 可容忍错报率）
+    #[arg(long, value_enum, default_value_t = PlanningModelArg::Hypergeometric)]
+    planning_model: PlanningModelArg,
+
+    /// 可容忍偏差率（属性抽样）：如 0.05；attribute 方法必填
+    #[arg(long, value_name = "RATE")]
+    tolerable_deviation_rate: Option<f64>,
+
+    /// 预计偏差率（属性抽样）：默认 0；须小于可容忍偏差率
+    #[arg(long, value_name = "RATE", default_value_t = 0.0)]
+    expected_deviation_rate: f64,
+
+    /// 属性抽样模型：binomial（二项，默认）、poisson（泊松近似）、hypergeometric（超几何，按总体条数）
+    #[arg(long, value_enum, default_value_t = AttributeModelArg::Binomial)]
+    attribute_model: AttributeModelArg,
+
     /// 抽样数量（随机）：仅 random 方法需要；size>0
     #[arg(long, value_name = "N")] 
     size: Option<usize>,
@@ -81,8 +183,9 @@ struct Args {
     config: Option<PathBuf>,
 
     /// 输出 Excel 路径：所有总体写入同一文件，不存在则创建。
-    #[arg(long, value_name = "FILE")] 
-    output: PathBuf,
+    /// 仅规划（--save-plan 且不给 --output）时可省略。
+    #[arg(long, value_name = "FILE", required_unless_present = "save_plan")]
+    output: Option<PathBuf>,
 
     /// 选择输出列：
     /// - 默认：不传或传入以+开头的列名时，在默认列基础上追加（默认列：凭证唯一号, 凭证行号, 日期, 摘要, 科目编码, 科目全称, 借方金额, 贷方金额）；
@@ -91,6 +194,77 @@ struct Args {
     #[arg(long, num_args = 0.., value_name = "NAME")] 
     columns: Vec<String>,
 
+    /// 分层抽样：按金额分层后在各层内独立抽样（MUS 或随机），“抽样统计”按层输出。
+    /// fixed：按 --cut-points 给定的分界点；equal-value：各层金额大致相等；sqrt-f：累计 √f 法（Dalenius–Hodges）。
+    #[arg(long, value_enum, value_name = "MODE")]
+    stratify: Option<StratifyMode>,
+
+    /// 分层数（equal-value / sqrt-f）
+    #[arg(long, value_name = "K", default_value_t = 3)]
+    strata: usize,
+
+    /// 分层分界点（fixed）：如 --cut-points 10000 100000 得到 <10000、10000–100000、≥100000 三层
+    #[arg(long, num_args = 1.., value_name = "AMOUNT")]
+    cut_points: Vec<f64>,
+
+    /// 样本量分配：proportional（按条数）、neyman（按条数×标准差）、value（按金额）。
+    /// 总样本量：随机为 --size，MUS 为整个总体的规划样本量。
+    #[arg(long, value_enum, default_value_t = AllocationMode::Proportional)]
+    allocation: AllocationMode,
+
+    /// 主随机种子：各总体的种子由“主种子 + 总体名称”派生（增删其他总体不影响已有总体的样本）；
+    /// 缺省时按当前时间生成。所用种子写入“抽样统计”工作表，复核时以同一 --seed 重新执行即可得到相同样本。
+    #[arg(long, value_name = "SEED")]
+    seed: Option<u64>,
+
+    /// MUS 起始点改用与 R 一致的随机数生成器（Mersenne-Twister，set.seed + runif）：
+    /// 各总体的派生种子截为 31 位（R 整数范围），“抽样统计”中的种子可直接用于 R `MUS.extraction(plan, seed = 种子)`。
+    /// R 以金额本身为货币单元，故须同时指定 --currency-decimals 0（否则样本量与样本均与 R 不同）
+    #[arg(long, default_value_t = false)]
+    r_rng: bool,
+
+    /// 属性抽样评价：读取此前输出并已填写偏差标记列的样本工作簿，按总体计算偏差率上限并给出结论，
+    /// 结果写入 --output。使用 --tolerable-deviation-rate、--confidence、--attribute-model（hypergeometric 的总体条数取自“抽样统计”）。
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["journal", "extend"])]
+    evaluate_attribute: Option<PathBuf>,
+
+    /// 偏差标记列名（属性抽样评价）：是/Y/1/√ 等记为偏差，空白或 否/N/0 记为无偏差
+    #[arg(long, value_name = "NAME", default_value = "偏差")]
+    deviation_column: String,
+
+    /// MUS 评价：读取此前输出并已填写审定金额列的样本工作簿，以“抽样统计”中的种子重放抽取（其余参数须与原抽样一致），
+    /// 分别给出高估与低估的最可能错报与错报上限；同一科目有多个总体（如借方与贷方）时追加一行合并评价。结果写入 --output。
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["extend", "evaluate_attribute"])]
+    audited: Option<PathBuf>,
+
+    /// 审定金额列名（MUS 评价）：空白视为与账面金额一致
+    #[arg(long, value_name = "NAME", default_value = "审定金额")]
+    audit_column: String,
+
+    /// 扩样：读取此前输出的样本工作簿，在各总体已抽中的记录之外追加抽取 --extend-size 条（随机为等概率；
+    /// MUS 以“抽样统计”中的种子与历次扩样数量重放原抽取后按库的 mus_extend 追加，须给出原 MUS 参数）；
+    /// 输出“原样本 + 追加样本”，并以“抽样批次”列标记（0 为原样本，之后每次扩样递增）。其余参数须与原抽样一致。
+    #[arg(long, value_name = "FILE", requires = "extend_size")]
+    extend: Option<PathBuf>,
+
+    /// 扩样数量：每个总体追加的样本条数（与 --extend 配合使用）
+    #[arg(long, value_name = "N", requires = "extend")]
+    extend_size: Option<usize>,
+
+    /// 保存 MUS 计划（JSON，带版本号）到此目录：每个总体一个“总体名称.plan.json”，供复核与批准；
+    /// 不给 --output 时仅规划、不抽样。
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["plan", "extend", "audited", "evaluate_attribute"])]
+    save_plan: Option<PathBuf>,
+
+    /// 按已批准的 MUS 计划抽样：从此目录读取各总体的“总体名称.plan.json”（--save-plan 的输出）而不重新规划，
+    /// 总体金额须与计划逐条一致；此时无需 --materiality / --tolerable-misstatement。
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["extend", "audited", "evaluate_attribute"])]
+    plan: Option<PathBuf>,
+
+    /// 将各总体的 MUS 抽取结果（含计划、高值项目与命中的货币单元，JSON）存档到此目录：“总体名称.extraction.json”
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["extend", "audited", "evaluate_attribute"])]
+    save_extraction: Option<PathBuf>,
+
     /// 输出详细日志（默认关闭）。不加 --verbose 时，仅在完成时打印输出文件路径。
     #[arg(long, default_value_t = false)]
     verbose: bool,
@@ -107,36 +281,227 @@ fn parse_date(s: &str) -> Result<NaiveDate> {
     bail!("无法解析日期: {s}");
 }
 
+fn parse_header_rows(s: &str) -> Result<(usize, usize)> {
+    let (first, last) = s.split_once('-').unwrap_or((s, s));
+    match (first.trim().parse::<usize>(), last.trim().parse::<usize>()) {
+        (Ok(first), Ok(last)) if first >= 1 && first <= last => Ok((first, last)),
+        _ => bail!("无法解析表头行: {s}（应为 N 或 N-M，从 1 起）"),
+    }
+}
+
+fn attribute_model(arg: &AttributeModelArg) -> audit_sampling::AttributeModel {
+    match arg {
+        AttributeModelArg::Binomial => audit_sampling::AttributeModel::Binomial,
+        AttributeModelArg::Poisson => audit_sampling::AttributeModel::Poisson,
+        AttributeModelArg::Hypergeometric => audit_sampling::AttributeModel::Hypergeometric,
+    }
+}
+
+fn planning_model(arg: &PlanningModelArg) -> audit_sampling::PlanningModel {
+    match arg {
+        PlanningModelArg::Hypergeometric => audit_sampling::PlanningModel::Hypergeometric,
+        PlanningModelArg::Binomial => audit_sampling::PlanningModel::Binomial,
+        PlanningModelArg::Poisson => audit_sampling::PlanningModel::Poisson,
+    }
+}
+
+fn currency(args: &Args) -> Result<audit_sampling::Currency> {
+    match args.currency_decimals {
+        Some(d) => audit_sampling::Currency::new(d).context("--currency-decimals 无效"),
+        None => audit_sampling::Currency::from_code(&args.currency)
+            .with_context(|| format!("未知币种“{}”，请用 --currency-decimals 指定小数位数", args.currency)),
+    }
+}
+
+fn pps_scheme(arg: &SelectionArg) -> audit_sampling::PpsScheme {
+    match arg {
+        SelectionArg::Systematic => audit_sampling::PpsScheme::Systematic,
+        SelectionArg::RandomPps => audit_sampling::PpsScheme::RandomWithReplacement,
+        SelectionArg::Sieve => audit_sampling::PpsScheme::ModifiedSieve,
+        SelectionArg::StratifiedSieve => audit_sampling::PpsScheme::StratifiedSieve,
+        SelectionArg::Sampford => audit_sampling::PpsScheme::Sampford,
+    }
+}
+
+/// 属性抽样评价：逐个样本工作表统计偏差数并计算偏差率上限
+fn evaluate_attribute_samples(args: &Args, path: &std::path::Path) -> Result<()> {
+    let tdr = args.tolerable_deviation_rate.unwrap_or(0.0);
+    if tdr <= 0.0 || tdr >= 1.0 { bail!("属性抽样评价需要提供 --tolerable-deviation-rate，且介于 0 与 1 之间（例如 0.05）"); }
+    if args.confidence <= 0.0 || args.confidence >= 1.0 {
+        bail!("属性抽样评价要求 --confidence 介于 0 与 1 之间（例如 0.90 或 0.95）");
+    }
+    let samples = journal::load_sample_workbook(path).with_context(|| format!("读取样本工作簿失败: {}", path.display()))?;
+    let sizes = journal::load_summary_population_sizes(path)?;
+    let sheets = journal::load_summary_sheet_names(path)?;
+    let model = attribute_model(&args.attribute_model);
+    let mut rows = Vec::with_capacity(samples.len());
+    for (sheet, data) in &samples {
+        let population_size = sizes
+            .iter()
+            .find(|(name, _)| sampling::sample_sheet_name(&sheets, name) == *sheet)
+            .map(|(_, len)| *len);
+        if model == audit_sampling::AttributeModel::Hypergeometric && population_size.is_none() {
+            bail!("hypergeometric 评价需要总体条数，但“抽样统计”中未找到工作表“{sheet}”对应的总体");
+        }
+        let opts = audit_sampling::AttributeEvaluationOptions { model, confidence_level: args.confidence, tolerable_deviation_rate: tdr, population_size };
+        let eval = sampling::evaluate_attribute_sample(data, &args.deviation_column, opts).with_context(|| format!("工作表: {sheet}"))?;
+        if args.verbose {
+            eprintln!("[属性评价] {} n={} 偏差={} 上限={:.4} 结论={:?}", sheet, eval.n, eval.deviations, eval.upper_deviation_limit, eval.conclusion);
+        }
+        rows.push((sheet.clone(), eval));
+    }
+    let model_name = args.attribute_model.to_possible_value().map(|p| p.get_name().to_string()).unwrap_or_default();
+    let note = format!("TDR={:.4}, conf={:.2}, model={}", tdr, args.confidence, model_name);
+    let output = args.output.as_deref().expect("required by clap");
+    sampling::write_attribute_evaluation(&rows, output, &note)
+        .with_context(|| format!("写出结果失败: {}", output.display()))?;
+    println!("{}", output.display());
+    Ok(())
+}
+
 fn main() -> Result<()> {
     let args = Args::parse();
+    if let Some(path) = &args.evaluate_attribute {
+        return evaluate_attribute_samples(&args, path);
+    }
+    let method = args.method.clone().expect("required by clap");
+    let journal_path = args.journal.clone().expect("required by clap");
+    let start_str = args.start.clone().expect("required by clap");
+    let end_str = args.end.clone().expect("required by clap");
 
-    // Validate method-specific args
-    match args.method {
+    if args.save_plan.is_some() || args.plan.is_some() || args.save_extraction.is_some() {
+        if !matches!(method, Method::Mus) { bail!("--save-plan / --plan / --save-extraction 仅适用于 MUS（--method mus）"); }
+        if args.stratify.is_some() { bail!("--save-plan / --plan / --save-extraction 暂不支持分层（--stratify）"); }
+        if args.save_extraction.is_some() && args.selection != SelectionArg::Systematic {
+            bail!("--save-extraction 仅适用于系统选样（--selection systematic）");
+        }
+    }
+    if args.audited.is_some() {
+        if !matches!(method, Method::Mus) { bail!("--audited 仅适用于 MUS（--method mus）"); }
+        if args.stratify.is_some() { bail!("MUS 评价暂不支持分层（--stratify）"); }
+        if args.selection != SelectionArg::Systematic { bail!("MUS 评价仅适用于系统选样（--selection systematic）"); }
+    }
+
+    if args.extend.is_some() {
+        if args.extend_size.unwrap_or(0) == 0 { bail!("扩样需要提供 --extend-size > 0"); }
+        if matches!(method, Method::Mus) && (args.selection != SelectionArg::Systematic || args.cell_sampling) {
+            bail!("MUS 扩样仅适用于固定间隔的系统选样（--selection systematic，不加 --cell-sampling）");
+        }
+    }
+
+    // Validate method-specific args（随机与属性抽样的扩样只需要 --extend-size；MUS 扩样按原参数重放抽取）
+    match method {
+        Method::Random | Method::Attribute if args.extend.is_some() => {}
         Method::Mus => {
-            if args.materiality.is_none() && args.tolerable_misstatement.is_none() {
+            if args.materiality.is_none() && args.tolerable_misstatement.is_none() && args.plan.is_none() {
                 bail!("MUS 方法需要提供 --materiality 或 --tolerable-misstatement 之一");
             }
             if args.confidence <= 0.0 || args.confidence >= 1.0 {
                 bail!("MUS 方法要求 --confidence 介于 0 与 1 之间（例如 0.90 或 0.95）");
             }
+            if args.selection != SelectionArg::Systematic && (args.cell_sampling || args.start_point.is_some()) {
+                bail!("--cell-sampling 与 --start-point 仅适用于系统选样（--selection systematic）");
+            }
         }
         Method::Random => {
             if args.size.unwrap_or(0) == 0 { bail!("随机抽样需要提供 --size > 0"); }
         }
+        Method::Attribute => {
+            let tdr = args.tolerable_deviation_rate.unwrap_or(0.0);
+            if tdr <= 0.0 || tdr >= 1.0 { bail!("属性抽样需要提供 --tolerable-deviation-rate，且介于 0 与 1 之间（例如 0.05）"); }
+            if args.expected_deviation_rate < 0.0 || args.expected_deviation_rate >= tdr {
+                bail!("--expected-deviation-rate 须 ≥0 且小于可容忍偏差率");
+            }
+            if args.confidence <= 0.0 || args.confidence >= 1.0 {
+                bail!("属性抽样要求 --confidence 介于 0 与 1 之间（例如 0.90 或 0.95）");
+            }
+            if args.stratify.is_some() { bail!("属性抽样不支持分层（--stratify）"); }
+        }
     }
 
-    let start = parse_date(&args.start).context("解析开始日期失败")?;
-    let end = parse_date(&args.end).context("解析结束日期失败")?;
+    let currency = currency(&args)?;
+    if args.r_rng && currency.decimals() != 0 {
+        bail!("--r-rng 需与 --currency-decimals 0 同用：R 以金额本身为货币单元，按最小货币单位抽样时样本量与样本均与 R 不同");
+    }
+
+    let stratify = match &args.stratify {
+        None => None,
+        Some(_) if args.extend.is_some() => bail!("扩样暂不支持分层（--stratify）"),
+        Some(mode) => {
+            let boundaries = match mode {
+                StratifyMode::Fixed => {
+                    if args.cut_points.is_empty() { bail!("fixed 分层需要提供 --cut-points"); }
+                    audit_sampling::StratumBoundaries::Fixed(args.cut_points.clone())
+                }
+                _ if args.strata == 0 => bail!("分层数 --strata 必须 > 0"),
+                StratifyMode::EqualValue => audit_sampling::StratumBoundaries::EqualValue(args.strata),
+                StratifyMode::SqrtF => audit_sampling::StratumBoundaries::CumulativeSqrtF(args.strata),
+            };
+            let allocation = match args.allocation {
+                AllocationMode::Proportional => audit_sampling::Allocation::Proportional,
+                AllocationMode::Neyman => audit_sampling::Allocation::Neyman,
+                AllocationMode::Value => audit_sampling::Allocation::Value,
+            };
+            Some(sampling::StratifyParams { boundaries, allocation, currency })
+        }
+    };
+
+    let start = parse_date(&start_str).context("解析开始日期失败")?;
+    let end = parse_date(&end_str).context("解析结束日期失败")?;
     if end < start { bail!("结束日期早于开始日期"); }
 
     // Load journal（无论是否有配置，都要求存在“报表科目”列）
-    let data: JournalData = load_journal(&args.journal).with_context(|| format!("读取序时账失败: {}", args.journal.display()))?;
+    let load_opts = LoadOptions {
+        encoding: match &args.encoding {
+            Some(label) => Some(encoding_rs::Encoding::for_label(label.trim().as_bytes()).ok_or_else(|| anyhow::anyhow!("不支持的编码: {label}"))?),
+            None => None,
+        },
+        sheets: match (args.all_sheets, args.sheet.is_empty()) {
+            (true, _) => SheetSelection::AllVisible,
+            (false, true) => SheetSelection::FirstVisible,
+            (false, false) => SheetSelection::Listed(args.sheet.clone()),
+        },
+        header_rows: args.header_row.as_deref().map(parse_header_rows).transpose()?,
+    };
+    let data: JournalData = load_journal(&journal_path, &load_opts).with_context(|| format!("读取序时账失败: {}", journal_path.display()))?;
     let period = (start, end);
-    let subject_col = journal::find_report_subject_col(&data.headers)
+
+    // Load config (可选)
+    let cfg_opt: Option<Config> = match &args.config {
+        Some(p) => Some(config::load_config(p).context("读取配置文件失败")?),
+        None => None,
+    };
+    // 各角色使用的列：配置 columns 段优先，其余按表头识别
+    let columns = Columns::resolve(&data.headers, &cfg_opt.as_ref().map(|c| c.columns.clone()).unwrap_or_default())?;
+    let subject_col = columns
+        .report_subject
+        .clone()
         .ok_or_else(|| anyhow::anyhow!("未找到‘报表科目’列。请在序时账中提供该列，或调整导出字段。"))?;
 
+    // 扩样：读取已有样本工作簿、各总体种子、历次扩样数量与样本表名
+    let previous_samples = match &args.extend {
+        Some(p) => Some((
+            journal::load_sample_workbook(p).with_context(|| format!("读取已有样本失败: {}", p.display()))?,
+            journal::load_summary_seeds(p)?,
+            journal::load_summary_extensions(p)?,
+            journal::load_summary_sheet_names(p)?,
+        )),
+        None => None,
+    };
+
+    // MUS 评价：读取已审定的样本工作簿、各总体种子、历次扩样数量与样本表名
+    let audited = match &args.audited {
+        Some(p) => Some((
+            journal::load_sample_workbook(p).with_context(|| format!("读取样本工作簿失败: {}", p.display()))?,
+            journal::load_summary_seeds(p)?,
+            journal::load_summary_extensions(p)?,
+            journal::load_summary_sheet_names(p)?,
+        )),
+        None => None,
+    };
+
     // 计算最终输出列（保持输入表头顺序）
-    let selected_headers: Vec<String> = {
+    let mut selected_headers: Vec<String> = {
         fn split_tokens(items: &[String]) -> Vec<String> {
             let mut out = Vec::new();
             for it in items {
@@ -148,9 +513,7 @@ fn main() -> Result<()> {
             }
             out
         }
-        let defaults = vec![
-            "凭证唯一号", "凭证行号", "日期", "摘要", "科目编码", "科目全称", "借方金额", "贷方金额",
-        ];
+        let defaults = ["凭证唯一号", "凭证行号", "日期", "摘要", "科目编码", "科目全称", "借方金额", "贷方金额"];
         let tokens = split_tokens(&args.columns);
         let is_all = tokens.iter().any(|t| t.eq_ignore_ascii_case("all"));
         let has_explicit = tokens.iter().any(|t| !t.starts_with('+') && !t.eq_ignore_ascii_case("all"));
@@ -165,24 +528,37 @@ fn main() -> Result<()> {
         } else {
             let mut set: std::collections::BTreeSet<String> = defaults.iter().map(|s| (*s).to_string()).collect();
             for t in tokens { if t.starts_with('+') { set.insert(t.trim_start_matches('+').to_string()); } }
+            // 合并多个工作表时默认带出来源工作表
+            if data.headers.iter().any(|h| h == journal::SOURCE_SHEET_COL) { set.insert(journal::SOURCE_SHEET_COL.to_string()); }
             set.into_iter().collect()
         };
         // 保持输入表头顺序
         let set: std::collections::HashSet<String> = want.drain(..).collect();
         data.headers.iter().filter(|h| set.contains(*h)).cloned().collect()
     };
-
-    // Load config (可选)
-    let cfg_opt: Option<ConfigMap> = match &args.config {
-        Some(p) => Some(config::load_config(p).context("读取配置文件失败")?),
-        None => None,
-    };
+    if !selected_headers.iter().any(|h| h == sampling::ROW_COL) {
+        selected_headers.push(sampling::ROW_COL.to_string());
+    }
+    if previous_samples.is_some() && !selected_headers.iter().any(|h| h == sampling::BATCH_COL) {
+        selected_headers.push(sampling::BATCH_COL.to_string());
+    }
+    if stratify.is_some() && !selected_headers.iter().any(|h| h == sampling::STRATUM_COL) {
+        selected_headers.push(sampling::STRATUM_COL.to_string());
+    }
+    let pps_selection = pps_scheme(&args.selection);
+    if previous_samples.is_none()
+        && matches!(method, Method::Mus)
+        && pps_selection != audit_sampling::PpsScheme::Systematic
+        && !selected_headers.iter().any(|h| h == sampling::INCLUSION_COL)
+    {
+        selected_headers.push(sampling::INCLUSION_COL.to_string());
+    }
 
     // 解析目标 accounts
     let target_accounts: Vec<String> = match &cfg_opt {
         Some(cfg) => {
             if args.accounts.is_empty() || args.accounts.iter().any(|a| a.eq_ignore_ascii_case("all")) {
-                cfg.keys().cloned().collect()
+                cfg.accounts.keys().cloned().collect()
             } else {
                 args.accounts.clone()
             }
@@ -190,10 +566,10 @@ fn main() -> Result<()> {
         None => {
             // 无配置：严格使用“报表科目”列做分组
             let mut set = std::collections::BTreeSet::new();
-            for r in &data.rows {
-                if let Some(v) = r.values.get(&subject_col) { if !v.trim().is_empty() { set.insert(v.trim().to_string()); } }
+            for r in data.rows() {
+                if let Some(v) = r.text(&subject_col) && !v.trim().is_empty() { set.insert(v.trim().to_string()); }
             }
-            let mut all: Vec<String> = set.into_iter().collect();
+            let all: Vec<String> = set.into_iter().collect();
             if args.accounts.is_empty() || args.accounts.iter().any(|a| a.eq_ignore_ascii_case("all")) {
                 all
             } else {
@@ -203,15 +579,35 @@ fn main() -> Result<()> {
         }
     };
 
+    let master_seed = args.seed.unwrap_or_else(sampling::time_seed);
+    if args.verbose { eprintln!("主种子: {}", master_seed); }
+    let mus_params = |seed: u64| {
+        // 按已保存的计划抽样时不重新规划，TE 仅作占位
+        let te = args.tolerable_misstatement.or(args.materiality).unwrap_or(f64::NAN);
+        sampling::MusParams {
+            tolerable_error: te,
+            expected_error: te * args.risk_factor,
+            confidence: args.confidence,
+            obey_n_as_min: args.obey_n_as_min,
+            start_point: args.start_point,
+            seed,
+            rng: if args.r_rng { audit_sampling::RngKind::RCompatible } else { audit_sampling::RngKind::Std },
+            mode: if args.cell_sampling { audit_sampling::ExtractionMode::Cell } else { audit_sampling::ExtractionMode::FixedInterval },
+            selection: pps_selection,
+            model: planning_model(&args.planning_model),
+            currency,
+        }
+    };
+
     // Execute per account/rule and collect results
     let mut results_nonempty: Vec<(String, Vec<journal::Record>, usize)> = Vec::new();
-    let mut summary_rows: Vec<(String, usize, usize)> = Vec::new();
+    let mut summary_rows: Vec<sampling::SummaryRow> = Vec::new();
 
     for account in target_accounts {
         // 组装规则（配置中的字段均可选；若未配置该 account，则按默认：借/贷各一条规则）
         let resolved_rules: Vec<ResolvedRule> = match &cfg_opt {
             Some(cfg) => {
-                if let Some(rules) = cfg.get(&account) {
+                if let Some(rules) = cfg.accounts.get(&account) {
                     let mut out = Vec::new();
                     for rule in rules {
                         let types: Vec<config::TransactionType> = match &rule.transaction_type {
@@ -226,71 +622,264 @@ fn main() -> Result<()> {
                             };
                             let codes = rule.account_codes.clone().filter(|v| !v.is_empty());
                             let vcol = rule.value_column.clone();
-                            out.push(ResolvedRule { population_name: pname, account_codes: codes, transaction_type: t.clone(), value_column: vcol });
+                            out.push(ResolvedRule::new(pname, codes, t.clone(), vcol, &columns));
                         }
                     }
                     if out.is_empty() {
                         vec![
-                            ResolvedRule { population_name: format!("{}_贷方", account), account_codes: None, transaction_type: crate::config::TransactionType::Credit, value_column: None },
-                            ResolvedRule { population_name: format!("{}_借方", account), account_codes: None, transaction_type: crate::config::TransactionType::Debit, value_column: None },
+                            ResolvedRule::new(format!("{}_贷方", account), None, crate::config::TransactionType::Credit, None, &columns),
+                            ResolvedRule::new(format!("{}_借方", account), None, crate::config::TransactionType::Debit, None, &columns),
                         ]
                     } else { out }
                 } else {
                     vec![
-                        ResolvedRule { population_name: format!("{}_贷方", account), account_codes: None, transaction_type: crate::config::TransactionType::Credit, value_column: None },
-                        ResolvedRule { population_name: format!("{}_借方", account), account_codes: None, transaction_type: crate::config::TransactionType::Debit, value_column: None },
+                        ResolvedRule::new(format!("{}_贷方", account), None, crate::config::TransactionType::Credit, None, &columns),
+                        ResolvedRule::new(format!("{}_借方", account), None, crate::config::TransactionType::Debit, None, &columns),
                     ]
                 }
             }
             None => vec![
-                ResolvedRule { population_name: format!("{}_贷方", account), account_codes: None, transaction_type: crate::config::TransactionType::Credit, value_column: None },
-                ResolvedRule { population_name: format!("{}_借方", account), account_codes: None, transaction_type: crate::config::TransactionType::Debit, value_column: None },
+                ResolvedRule::new(format!("{}_贷方", account), None, crate::config::TransactionType::Credit, None, &columns),
+                ResolvedRule::new(format!("{}_借方", account), None, crate::config::TransactionType::Debit, None, &columns),
             ],
         };
 
+        // 同一科目有多个已抽样总体时按合并评价：各总体结论留待合并结果
+        let has_sheet = |r: &ResolvedRule| {
+            audited.as_ref().is_some_and(|(samples, _, _, sheets)| {
+                let sheet = sampling::sample_sheet_name(sheets, &r.population_name);
+                samples.iter().any(|(name, _)| *name == sheet)
+            })
+        };
+        let combined = resolved_rules.iter().filter(|r| has_sheet(r)).count() > 1;
+        let mut account_evals: Vec<(sampling::SummaryRow, audit_sampling::Evaluation)> = Vec::new();
+
         for rrule in resolved_rules {
-            let population = build_population(&data, period, &account, &rrule);
+            let (population, excluded) = build_population(&data, period, &account, &rrule, &columns, currency);
+            sampling::report_warnings(&rrule.population_name, &excluded);
             let population_len = population.len();
             if population_len == 0 {
                 if args.verbose { eprintln!("警告: {} 的总体为空，已跳过。", rrule.population_name); }
-                summary_rows.push((rrule.population_name.clone(), 0usize, 0usize));
+                summary_rows.push(sampling::SummaryRow { population: rrule.population_name.clone(), warnings: excluded, ..Default::default() });
                 continue;
             }
-            let sampled = match args.method {
-                Method::Mus => {
-                    let te = args.tolerable_misstatement.or(args.materiality).expect("validated");
-                    let ee = te * args.risk_factor;
-                    perform_mus_sampling_with_rules(population, &rrule, te, ee, args.confidence, args.verbose)
-                        .with_context(|| format!("MUS 抽样失败: {}", rrule.population_name))?
+            let mut seed = sampling::derive_seed(master_seed, &rrule.population_name);
+            if args.r_rng { seed &= i32::MAX as u64; }
+            if let Some((samples, seeds, history, sheets)) = &audited {
+                let sheet = sampling::sample_sheet_name(sheets, &rrule.population_name);
+                let Some((_, sample)) = samples.iter().find(|(name, _)| *name == sheet) else { continue };
+                let Some(&seed) = seeds.get(&rrule.population_name) else {
+                    bail!("“抽样统计”中未找到总体“{}”的种子", rrule.population_name);
+                };
+                let extensions = history.get(&rrule.population_name).cloned().unwrap_or_default();
+                let params = mus_params(seed);
+                let eval = sampling::plan_mus_with_rules(&population, &rrule, &params, args.verbose)
+                    .and_then(|plan| {
+                        let plan = audit_sampling::Plan { combined, ..plan };
+                        sampling::evaluate_mus_with_rules(&population, &plan, &params, sample, &extensions, &args.audit_column)
+                    })
+                    .with_context(|| format!("MUS 评价失败: {}", rrule.population_name))?;
+                if args.verbose {
+                    eprintln!(
+                        "[MUS评价] {} 高估上限={:.2} 低估上限={:.2} 净最可能错报={:.2} 结论={:?}",
+                        rrule.population_name, eval.overstatement.upper_error_limit, eval.understatement.upper_error_limit, eval.net_most_likely_misstatement, eval.conclusion
+                    );
                 }
-                Method::Random => {
-                    perform_random_sampling_with_rules(population, args.size.unwrap())
+                let mut row = sampling::evaluation_summary_row(&rrule.population_name, population_len, &extensions, &eval);
+                row.warnings.splice(0..0, excluded);
+                account_evals.push((row, eval));
+                continue;
+            }
+            if let (None, Some(strat)) = (&previous_samples, &stratify) {
+                let params;
+                let stratum_method = match method {
+                    Method::Mus => {
+                        params = mus_params(seed);
+                        sampling::StratumMethod::Mus(&params)
+                    }
+                    Method::Random => sampling::StratumMethod::Random(args.size.unwrap()),
+                    Method::Attribute => unreachable!("validated"),
+                };
+                let strata = sampling::perform_stratified_sampling_with_rules(population, &rrule, strat, stratum_method, seed, args.verbose)
+                    .with_context(|| format!("分层抽样失败: {}", rrule.population_name))?;
+                let mut sampled = Vec::new();
+                let mut excluded = Some(excluded);
+                for (mut row, records) in strata {
+                    row.warnings.splice(0..0, excluded.take().unwrap_or_default());
+                    summary_rows.push(row);
+                    sampled.extend(records);
+                }
+                if !sampled.is_empty() {
+                    results_nonempty.push((rrule.population_name.clone(), sampled, population_len));
+                }
+                continue;
+            }
+            let mut row = sampling::SummaryRow { population: rrule.population_name.clone(), population_len, seed: Some(seed), warnings: excluded, ..Default::default() };
+            let sampled = match (&previous_samples, &method) {
+                (Some((prev, seeds, history, sheets)), method) => {
+                    let sheet = sampling::sample_sheet_name(sheets, &rrule.population_name);
+                    let previous = prev.iter().find(|(name, _)| *name == sheet).map(|(_, d)| d);
+                    let size = args.extend_size.unwrap_or(0);
+                    let extensions = history.get(&rrule.population_name).cloned().unwrap_or_default();
+                    row.extensions = extensions.clone();
+                    match (method, previous) {
+                        (Method::Mus, None) => {
+                            let text = "已有样本中没有该总体的样本表，未扩样".to_string();
+                            sampling::report_warnings(&rrule.population_name, std::slice::from_ref(&text));
+                            row.warnings.push(text);
+                            Vec::new()
+                        }
+                        (Method::Mus, Some(previous)) => {
+                            let Some(&seed) = seeds.get(&rrule.population_name) else {
+                                bail!("“抽样统计”中未找到总体“{}”的种子", rrule.population_name);
+                            };
+                            row.seed = Some(seed);
+                            let mus = sampling::extend_mus_sample_with_rules(population, &rrule, &mus_params(seed), previous, &extensions, size, args.verbose)
+                                .with_context(|| format!("扩样失败: {}", rrule.population_name))?;
+                            row.extensions.push(size);
+                            row.high_values = Some(mus.high_values);
+                            row.sampling_interval = Some(mus.sampling_interval);
+                            row.start_point = mus.start_point;
+                            row.warnings.extend(mus.warnings);
+                            mus.records
+                        }
+                        _ => {
+                            row.extensions.push(size);
+                            sampling::extend_sample_with_rules(population, previous, size, seed)
+                                .with_context(|| format!("扩样失败: {}", rrule.population_name))?
+                        }
+                    }
+                }
+                (None, Method::Mus) => {
+                    let params = mus_params(seed);
+                    let plan = match &args.plan {
+                        Some(dir) => sampling::load_plan(dir, &population, &rrule, &params)?,
+                        None => sampling::plan_mus_with_rules(&population, &rrule, &params, args.verbose)
+                            .with_context(|| format!("MUS 抽样失败: {}", rrule.population_name))?,
+                    };
+                    if let Some(dir) = &args.save_plan {
+                        let json = plan.to_json().context("计划序列化失败")?;
+                        sampling::save_json(&sampling::artifact_path(dir, &rrule.population_name, "plan"), &json)?;
+                    }
+                    if args.output.is_none() {
+                        continue;
+                    }
+                    let mus = perform_mus_sampling_with_rules(population, &rrule, &plan, &params, args.verbose)
+                        .with_context(|| format!("MUS 抽样失败: {}", rrule.population_name))?;
+                    if let (Some(dir), Some(extract)) = (&args.save_extraction, &mus.extraction) {
+                        let json = extract.to_json().context("抽取结果序列化失败")?;
+                        sampling::save_json(&sampling::artifact_path(dir, &rrule.population_name, "extraction"), &json)?;
+                    }
+                    if !mus.records.is_empty() {
+                        row.high_values = Some(mus.high_values);
+                        row.sampling_interval = Some(mus.sampling_interval);
+                        row.start_point = mus.start_point;
+                    }
+                    row.warnings.extend(mus.warnings);
+                    mus.records
+                }
+                (None, Method::Random) => {
+                    perform_random_sampling_with_rules(population, args.size.unwrap(), seed)
+                }
+                (None, Method::Attribute) => {
+                    let params = sampling::AttributeParams {
+                        tolerable_deviation_rate: args.tolerable_deviation_rate.expect("validated"),
+                        expected_deviation_rate: args.expected_deviation_rate,
+                        confidence: args.confidence,
+                        model: attribute_model(&args.attribute_model),
+                    };
+                    let (records, plan) = sampling::perform_attribute_sampling_with_rules(population, &rrule, &params, seed, args.verbose)
+                        .with_context(|| format!("属性抽样失败: {}", rrule.population_name))?;
+                    row.allowable_deviations = Some(plan.allowable_deviations);
+                    records
                 }
             };
             let sample_len = sampled.len();
-            summary_rows.push((rrule.population_name.clone(), population_len, sample_len));
+            row.sample_len = sample_len;
+            summary_rows.push(row);
             if sample_len > 0 {
                 results_nonempty.push((rrule.population_name.clone(), sampled, population_len));
             }
         }
+        let (rows, evals): (Vec<sampling::SummaryRow>, Vec<audit_sampling::Evaluation>) = account_evals.into_iter().unzip();
+        let combined_row = if combined {
+            let c = audit_sampling::mus_combine(&evals).with_context(|| format!("合并评价失败: {account}"))?;
+            if args.verbose {
+                eprintln!("[MUS评价] {account}（合并） 高估上限={:.2} 低估上限={:.2} 结论={:?}", c.overstatement.upper_error_limit, c.understatement.upper_error_limit, c.conclusion);
+            }
+            Some(sampling::combined_summary_row(&account, &rows, &c))
+        } else {
+            None
+        };
+        summary_rows.extend(rows);
+        summary_rows.extend(combined_row);
     }
 
     // Write to Excel：仅写有样本的表，另附“抽样统计”工作表
-    let method_str = match args.method { Method::Mus => "mus", Method::Random => "random" }.to_string();
-    let note = match args.method {
+    let method_str = match method { Method::Mus => "mus", Method::Random => "random", Method::Attribute => "attribute" }.to_string();
+    let mut note = match method {
+        Method::Random | Method::Attribute if args.extend.is_some() => {
+            format!("extend=+{}", args.extend_size.unwrap_or(0))
+        }
         Method::Mus => {
             let te = args.tolerable_misstatement.or(args.materiality).unwrap_or(0.0);
-            format!("TE={:.2}, risk={:.2}, conf={:.2}", te, args.risk_factor, args.confidence)
+            let mut note = match &args.plan {
+                Some(dir) => format!("plan={}", dir.display()),
+                None => format!("TE={:.2}, risk={:.2}, conf={:.2}", te, args.risk_factor, args.confidence),
+            };
+            if args.obey_n_as_min { note.push_str(", obey_n_as_min"); }
+            if args.r_rng { note.push_str(", r_rng"); }
+            if args.cell_sampling { note.push_str(", cell"); }
+            if args.currency_decimals.is_some() || !args.currency.eq_ignore_ascii_case("CNY") {
+                note.push_str(&format!(", currency={}({})", args.currency.to_uppercase(), currency.decimals()));
+            }
+            if args.planning_model != PlanningModelArg::Hypergeometric {
+                let name = args.planning_model.to_possible_value().map(|p| p.get_name().to_string()).unwrap_or_default();
+                note.push_str(&format!(", model={name}"));
+            }
+            if args.selection != SelectionArg::Systematic {
+                let name = args.selection.to_possible_value().map(|p| p.get_name().to_string()).unwrap_or_default();
+                note.push_str(&format!(", selection={name}"));
+            }
+            note
         }
         Method::Random => {
             format!("size={}", args.size.unwrap_or(0))
         }
+        Method::Attribute => {
+            let model = args.attribute_model.to_possible_value().map(|p| p.get_name().to_string()).unwrap_or_default();
+            format!(
+                "TDR={:.4}, EDR={:.4}, conf={:.2}, model={}",
+                args.tolerable_deviation_rate.unwrap_or(0.0),
+                args.expected_deviation_rate,
+                args.confidence,
+                model
+            )
+        }
     };
-    let ctx = sampling::SummaryCtx { method: method_str, start: args.start.clone(), end: args.end.clone(), note };
+    if args.extend.is_some() && matches!(method, Method::Mus) {
+        note.push_str(&format!(", extend=+{}", args.extend_size.unwrap_or(0)));
+    }
+    let Some(output) = args.output.as_deref() else {
+        // 仅规划
+        println!("{}", args.save_plan.as_deref().expect("required by clap").display());
+        return Ok(());
+    };
+    // 评价只输出“抽样统计”（含评价各列）；种子为样本工作簿中各总体原有的种子
+    if args.audited.is_some() {
+        results_nonempty.clear();
+    } else {
+        note = format!("{}, seed={}", note, master_seed);
+    }
+    if let Some(mode) = &args.stratify {
+        let name = |v: Option<clap::builder::PossibleValue>| v.map(|p| p.get_name().to_string()).unwrap_or_default();
+        note.push_str(&format!(", stratify={}, allocation={}", name(mode.to_possible_value()), name(args.allocation.to_possible_value())));
+    }
+    let ctx = sampling::SummaryCtx { method: method_str, start: start_str.clone(), end: end_str.clone(), note };
 
-    sampling::write_results_to_excel(&results_nonempty, &summary_rows, &args.output, &selected_headers, &ctx)
-        .with_context(|| format!("写出结果失败: {}", args.output.display()))?;
+    sampling::write_results_to_excel(&results_nonempty, &summary_rows, output, &selected_headers, columns.voucher_line.as_deref(), &ctx)
+        .with_context(|| format!("写出结果失败: {}", output.display()))?;
 
-    println!("{}", args.output.display());
+    println!("{}", output.display());
     Ok(())
 }
@@ -0,0 +1,196 @@
+//! Integer money: amounts counted in minor units of a currency with a fixed precision.
+//!
+//! Every minor unit is one monetary unit (MU) for planning and extraction, so cumulative MU
+//! ranges are exact to the cent instead of depending on how `f64` book values round.
+
+use crate::MusError;
+use serde::{Deserialize, Serialize};
+use std::iter::Sum;
+use std::ops::{Add, Neg, Sub};
+
+/// Largest supported number of decimals (keeps `i64` amounts above 9e12 major units).
+const MAX_DECIMALS: u8 = 6;
+
+/// Precision of a currency: `10^decimals` minor units per major unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "CurrencyRepr")]
+pub struct Currency {
+    decimals: u8,
+}
+
+// Deserialized through `Currency::new` so saved plans cannot bypass the precision limit.
+#[derive(Deserialize)]
+struct CurrencyRepr {
+    decimals: u8,
+}
+
+impl TryFrom<CurrencyRepr> for Currency {
+    type Error = MusError;
+    fn try_from(r: CurrencyRepr) -> Result<Self, MusError> {
+        Currency::new(r.decimals)
+    }
+}
+
+impl Default for Currency {
+    /// Whole units: one MU per unit of book value, as in R's MUS package.
+    fn default() -> Self {
+        Currency::UNITS
+    }
+}
+
+impl Currency {
+    /// No decimals: book values are already in monetary units.
+    pub const UNITS: Currency = Currency { decimals: 0 };
+    /// Renminbi: 2 decimals (fen).
+    pub const CNY: Currency = Currency { decimals: 2 };
+
+    pub fn new(decimals: u8) -> Result<Self, MusError> {
+        if decimals > MAX_DECIMALS {
+            return Err(MusError::InvalidInput(format!("currency decimals must be <= {MAX_DECIMALS}")));
+        }
+        Ok(Currency { decimals })
+    }
+
+    /// Precision of an ISO 4217 currency code, for the codes common in audit work.
+    pub fn from_code(code: &str) -> Option<Self> {
+        let decimals = match code.trim().to_ascii_uppercase().as_str() {
+            "JPY" | "KRW" | "VND" | "CLP" | "ISK" => 0,
+            "CNY" | "RMB" | "HKD" | "MOP" | "TWD" | "USD" | "EUR" | "GBP" | "CHF" | "CAD" | "AUD" | "NZD" | "SGD" | "MYR" | "THB"
+            | "PHP" | "IDR" | "INR" | "RUB" | "SEK" | "NOK" | "DKK" | "PLN" | "CZK" | "HUF" | "BRL" | "MXN" | "ZAR" | "AED" | "SAR" => 2,
+            "KWD" | "BHD" | "OMR" | "JOD" | "TND" | "LYD" | "IQD" => 3,
+            _ => return None,
+        };
+        Some(Currency { decimals })
+    }
+
+    pub fn decimals(self) -> u8 {
+        self.decimals
+    }
+
+    /// Minor units per major unit.
+    pub fn scale(self) -> f64 {
+        10f64.powi(self.decimals as i32)
+    }
+
+    /// Nearest amount in minor units (halves away from zero).
+    pub fn money(self, amount: f64) -> Money {
+        Money((amount * self.scale()).round() as i64)
+    }
+
+    /// Amount in major units.
+    pub fn amount(self, money: Money) -> f64 {
+        money.0 as f64 / self.scale()
+    }
+
+    /// Parses a plain decimal such as `-1234.565` without going through `f64`; digits beyond the
+    /// precision round half away from zero. Exponent notation falls back to `f64` parsing.
+    /// Returns `None` for text that is not a number or does not fit.
+    pub fn parse(self, text: &str) -> Option<Money> {
+        let t = text.trim();
+        if t.contains(['e', 'E']) {
+            return t.parse::<f64>().ok().filter(|v| v.is_finite()).map(|v| self.money(v));
+        }
+        let (negative, digits) = match t.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, t.strip_prefix('+').unwrap_or(t)),
+        };
+        let (int_part, frac_part) = digits.split_once('.').unwrap_or((digits, ""));
+        let all_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+        if (int_part.is_empty() && frac_part.is_empty()) || !all_digits(int_part) || !all_digits(frac_part) {
+            return None;
+        }
+        let mut minor: i64 = 0;
+        for b in int_part.bytes().chain(frac_part.bytes().chain(std::iter::repeat(b'0')).take(self.decimals as usize)) {
+            minor = minor.checked_mul(10)?.checked_add((b - b'0') as i64)?;
+        }
+        if frac_part.as_bytes().get(self.decimals as usize).is_some_and(|&b| b >= b'5') {
+            minor = minor.checked_add(1)?;
+        }
+        Some(Money(if negative { -minor } else { minor }))
+    }
+}
+
+/// An amount in integer minor units of its currency.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Money(pub i64);
+
+impl Money {
+    pub fn minor_units(self) -> i64 {
+        self.0
+    }
+
+    /// Monetary units available for selection: negative amounts have none.
+    pub fn monetary_units(self) -> u64 {
+        self.0.max(0) as u64
+    }
+}
+
+impl Add for Money {
+    type Output = Money;
+    fn add(self, rhs: Money) -> Money {
+        Money(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+    fn sub(self, rhs: Money) -> Money {
+        Money(self.0 - rhs.0)
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+    fn neg(self) -> Money {
+        Money(-self.0)
+    }
+}
+
+impl Sum for Money {
+    fn sum<I: Iterator<Item = Money>>(iter: I) -> Money {
+        Money(iter.map(|m| m.0).sum())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn parsing_is_exact_and_rounds_half_away_from_zero() {
+        let cny = Currency::CNY;
+        assert_eq!(cny.parse("1234.56"), Some(Money(123_456)));
+        assert_eq!(cny.parse("-0.3"), Some(Money(-30)));
+        assert_eq!(cny.parse("+.05"), Some(Money(5)));
+        assert_eq!(cny.parse("7."), Some(Money(700)));
+        assert_eq!(cny.parse("2.345"), Some(Money(235)));
+        assert_eq!(cny.parse("-2.345"), Some(Money(-235)));
+        assert_eq!(cny.parse("1.2345E+3"), Some(Money(123_450)));
+        assert_eq!(cny.parse("12a"), None);
+        assert_eq!(cny.parse("."), None);
+        assert_eq!(cny.parse("99999999999999999999"), None);
+        // 0.1 + 0.2 in f64 is 0.30000000000000004; minor units add exactly
+        let sum: Money = ["0.1", "0.2"].iter().filter_map(|s| cny.parse(s)).sum();
+        assert_eq!(sum, Money(30));
+        assert_abs_diff_eq!(cny.amount(sum), 0.3);
+        assert_eq!(Currency::from_code("jpy").map(|c| c.parse("1234.5")), Some(Some(Money(1235))));
+        assert_eq!(Currency::from_code("KWD").map(Currency::decimals), Some(3));
+        assert!(Currency::new(7).is_err());
+        assert_eq!(Currency::UNITS.money(0.49).monetary_units(), 0);
+        assert_eq!(cny.money(-5.0).monetary_units(), 0);
+    }
+
+    #[test]
+    fn minor_units_survive_the_f64_round_trip_below_2_pow_51() {
+        use rand::{Rng, SeedableRng};
+        let cny = Currency::CNY;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        for m in (0..100_000).map(|_| rng.random_range(-(1i64 << 51)..1i64 << 51)).chain([(1 << 51) - 1, -(1 << 51) + 1]) {
+            assert_eq!(cny.money(cny.amount(Money(m))), Money(m));
+        }
+        // Above 2^52 one minor unit is lost
+        assert_ne!(cny.money(cny.amount(Money(3_615_405_527_638_402))), Money(3_615_405_527_638_402));
+    }
+}
+
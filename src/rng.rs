@@ -0,0 +1,100 @@
+//! R-compatible uniform generator (Mersenne-Twister, `set.seed` + `runif`).
+//!
+//! Reproduces R's default `RNGkind("Mersenne-Twister", "Inversion")`, so that
+//! `set.seed(s); runif(1, a, b)` in R and `RMersenneTwister::new(s).runif(a, b)`
+//! here return the same value.
+
+const N: usize = 624;
+const M: usize = 397;
+const MATRIX_A: u32 = 0x9908_b0df;
+const UPPER_MASK: u32 = 0x8000_0000;
+const LOWER_MASK: u32 = 0x7fff_ffff;
+const I2_32M1: f64 = 2.328_306_437_080_797e-10; // 1/(2^32 - 1)
+
+#[derive(Debug, Clone)]
+pub struct RMersenneTwister {
+    mt: [u32; N],
+    mti: usize,
+}
+
+impl RMersenneTwister {
+    /// Equivalent of R `set.seed(seed)`: 50 rounds of initial LCG scrambling, then the
+    /// 625 seed words (`mti` followed by the state) are filled with the same LCG.
+    pub fn new(seed: i32) -> Self {
+        let mut s = seed as u32;
+        for _ in 0..50 {
+            s = s.wrapping_mul(69069).wrapping_add(1);
+        }
+        // First word is the position `mti`, overwritten with N by FixupSeeds
+        s = s.wrapping_mul(69069).wrapping_add(1);
+        let mut mt = [0u32; N];
+        for w in mt.iter_mut() {
+            s = s.wrapping_mul(69069).wrapping_add(1);
+            *w = s;
+        }
+        Self { mt, mti: N }
+    }
+
+    fn genrand(&mut self) -> f64 {
+        let mag01 = [0u32, MATRIX_A];
+        if self.mti >= N {
+            for kk in 0..N - M {
+                let y = (self.mt[kk] & UPPER_MASK) | (self.mt[kk + 1] & LOWER_MASK);
+                self.mt[kk] = self.mt[kk + M] ^ (y >> 1) ^ mag01[(y & 1) as usize];
+            }
+            for kk in N - M..N - 1 {
+                let y = (self.mt[kk] & UPPER_MASK) | (self.mt[kk + 1] & LOWER_MASK);
+                self.mt[kk] = self.mt[kk + M - N] ^ (y >> 1) ^ mag01[(y & 1) as usize];
+            }
+            let y = (self.mt[N - 1] & UPPER_MASK) | (self.mt[0] & LOWER_MASK);
+            self.mt[N - 1] = self.mt[M - 1] ^ (y >> 1) ^ mag01[(y & 1) as usize];
+            self.mti = 0;
+        }
+        let mut y = self.mt[self.mti];
+        self.mti += 1;
+        y ^= y >> 11;
+        y ^= (y << 7) & 0x9d2c_5680;
+        y ^= (y << 15) & 0xefc6_0000;
+        y ^= y >> 18;
+        y as f64 * 2.328_306_436_538_696_3e-10 // reals: [0,1)
+    }
+
+    /// R `unif_rand()`: uniform on (0, 1), never returning the end points.
+    pub fn unif_rand(&mut self) -> f64 {
+        let v = self.genrand();
+        if v <= 0.0 {
+            0.5 * I2_32M1
+        } else if 1.0 - v <= 0.0 {
+            1.0 - 0.5 * I2_32M1
+        } else {
+            v
+        }
+    }
+
+    /// R `runif(1, a, b)`.
+    pub fn runif(&mut self, a: f64, b: f64) -> f64 {
+        if a == b {
+            return a;
+        }
+        a + (b - a) * self.unif_rand()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn matches_r_runif() {
+        // set.seed(42); runif(3)
+        let mut r = RMersenneTwister::new(42);
+        assert_abs_diff_eq!(r.unif_rand(), 0.914_806_0, epsilon = 1e-7);
+        assert_abs_diff_eq!(r.unif_rand(), 0.937_075_4, epsilon = 1e-7);
+        assert_abs_diff_eq!(r.unif_rand(), 0.286_139_5, epsilon = 1e-7);
+        // set.seed(1); runif(1, 0, 100)
+        assert_abs_diff_eq!(RMersenneTwister::new(1).runif(0.0, 100.0), 26.550_87, epsilon = 1e-5);
+        // set.seed(123); runif(1)
+        assert_abs_diff_eq!(RMersenneTwister::new(123).unif_rand(), 0.287_577_5, epsilon = 1e-7);
+    }
+}
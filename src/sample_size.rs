@@ -0,0 +1,246 @@
+//! Sample sizes for MUS planning: `n(i)`, the smallest number of monetary-unit draws for
+//! which seeing at most `i` errors at the tolerable error rate has probability ≤ alpha.
+//!
+//! Book values in cents make the hypergeometric population huge (N ≈ 1e11), where building
+//! a `statrs::Hypergeometric` per probe and summing log-binomials is both slow and loses
+//! precision. The CDF is instead started from a log-space first term and continued with the
+//! pmf ratio recurrence, stopping as soon as the running sum exceeds alpha. `SampleSizes`
+//! caches `n(i)` and searches each new value between its cached neighbours, since `n(i)` is
+//! non-decreasing in `i`.
+
+use crate::{MusError, PlanningModel};
+use statrs::distribution::{ContinuousCDF, Gamma};
+use statrs::function::beta::beta_reg;
+use statrs::function::factorial::ln_binomial;
+use statrs::function::gamma::ln_gamma;
+use std::collections::BTreeMap;
+
+/// Rescale the running CDF sum once a relative term exceeds this, to stay within f64 range.
+const RESCALE_ABOVE: f64 = 1e200;
+
+/// Stirling series remainder `ln Γ(z) − [(z − ½) ln z − z + ½ ln 2π]` for `z ≥ 10`.
+fn stirling_remainder(z: f64) -> f64 {
+    let z2 = z * z;
+    (1.0 / 12.0 - (1.0 / 360.0 - 1.0 / (1260.0 * z2)) / z2) / z
+}
+
+/// `ln[(a)_k / (b)_k]` for falling factorials with `k ≤ a ≤ b`: the log-probability that `k`
+/// draws without replacement from `b` units avoid all `b − a` marked ones.
+fn ln_falling_ratio(a: u64, b: u64, k: u64) -> f64 {
+    if k == 0 || a == b {
+        return 0.0;
+    }
+    let (za, zb, kf) = ((a - k + 1) as f64, (b - k + 1) as f64, k as f64);
+    if za < 10.0 {
+        return ln_gamma(a as f64 + 1.0) - ln_gamma(za) - ln_gamma(b as f64 + 1.0) + ln_gamma(zb);
+    }
+    // ln Γ(z + k) − ln Γ(z) = (z − ½)·ln(1 + k/z) + k·ln(z + k) − k + remainders; the two
+    // k·ln(z + k) terms combine into one ln_1p, so nothing of size ln Γ(N) is subtracted.
+    (za - 0.5) * (kf / za).ln_1p() - (zb - 0.5) * (kf / zb).ln_1p()
+        + kf * ((a as f64 - b as f64) / (b as f64 + 1.0)).ln_1p()
+        + stirling_remainder(a as f64 + 1.0)
+        - stirling_remainder(za)
+        - stirling_remainder(b as f64 + 1.0)
+        + stirling_remainder(zb)
+}
+
+/// Whether `P[X ≤ q] ≤ alpha` for `X ~ Hypergeometric(m + n_black, m, k)`.
+pub(crate) fn hyper_cdf_at_most(q: u64, alpha: f64, m: u64, n_black: u64, k: u64) -> bool {
+    let (lo, hi) = (k.saturating_sub(n_black), k.min(m));
+    if q < lo {
+        return true;
+    }
+    if q >= hi {
+        return alpha >= 1.0;
+    }
+    // Sum = exp(ln_scale) · s, with the current term exp(ln_scale) · t
+    let mut ln_scale = if lo == 0 {
+        ln_falling_ratio(n_black, m + n_black, k)
+    } else {
+        ln_binomial(m, lo) - ln_binomial(m + n_black, k)
+    };
+    let ln_alpha = alpha.ln();
+    let mut limit = (ln_alpha - ln_scale).exp();
+    let (mut s, mut t) = (0.0, 1.0);
+    for x in lo..=q {
+        s += t;
+        if s > limit {
+            return false;
+        }
+        t *= ((m - x) as f64 * (k - x) as f64) / ((x + 1) as f64 * (n_black + x + 1 - k) as f64);
+        if t > RESCALE_ABOVE {
+            ln_scale += t.ln();
+            s /= t;
+            t = 1.0;
+            limit = (ln_alpha - ln_scale).exp();
+        }
+    }
+    true
+}
+
+/// Smallest `k` in `lo..=hi` with `done(k)` for a monotone `done`, or `hi` when there is none.
+/// Gallops up from `lo` (answers are usually close to it), then bisects.
+fn first_true(lo: u64, hi: u64, mut done: impl FnMut(u64) -> Result<bool, MusError>) -> Result<u64, MusError> {
+    if lo >= hi || done(lo)? {
+        return Ok(lo.min(hi));
+    }
+    let (mut below, mut step) = (lo, 1u64);
+    let mut above = loop {
+        let probe = lo.saturating_add(step).min(hi);
+        if done(probe)? {
+            break probe;
+        }
+        if probe == hi {
+            return Ok(hi);
+        }
+        below = probe;
+        step = step.saturating_mul(2);
+    };
+    while above - below > 1 {
+        let mid = below + (above - below) / 2;
+        if done(mid)? {
+            above = mid;
+        } else {
+            below = mid;
+        }
+    }
+    Ok(above)
+}
+
+/// Smallest `k ≤ min(k_max, m + n_black)` with `P[X ≤ q] ≤ alpha` for
+/// `X ~ Hypergeometric(m + n_black, m, k)`; the upper end when no such `k` exists.
+pub(crate) fn min_draws_for_cdf_at_most_q(q: u64, alpha: f64, m: u64, n_black: u64, k_max: u64) -> Result<u64, MusError> {
+    if m + n_black == 0 {
+        return Err(MusError::Calculation("population size is zero".into()));
+    }
+    first_true(0, k_max.min(m + n_black), |k| Ok(hyper_cdf_at_most(q, alpha, m, n_black, k)))
+}
+
+/// Cached `n(i)` for one planning model, tolerable error and book value.
+pub(crate) struct SampleSizes {
+    model: PlanningModel,
+    alpha: f64,
+    /// Tolerable error rate `TE / BV`.
+    rate: f64,
+    /// Erroneous and correct monetary units of the hypergeometric model (rounded as in R).
+    m: u64,
+    n_black: u64,
+    book_units: u64,
+    cache: BTreeMap<u64, u64>,
+}
+
+impl SampleSizes {
+    pub(crate) fn new(model: PlanningModel, alpha: f64, tolerable_error: f64, account_value: f64) -> Result<Self, MusError> {
+        if !(alpha.is_finite() && alpha > 0.0 && alpha < 1.0) {
+            return Err(MusError::InvalidInput("alpha must be in (0,1)".into()));
+        }
+        if !(tolerable_error.is_finite() && tolerable_error > 0.0 && account_value.is_finite() && account_value > 0.0) {
+            return Err(MusError::InvalidInput("tolerable_error and account_value must be finite and > 0".into()));
+        }
+        let rate = tolerable_error / account_value;
+        if model == PlanningModel::Binomial && rate >= 1.0 {
+            return Err(MusError::InvalidInput("tolerable_error must be in (0, book value)".into()));
+        }
+        Ok(SampleSizes {
+            model,
+            alpha,
+            rate,
+            m: crate::round_to_u64(rate * account_value),
+            n_black: crate::round_to_u64((1.0 - rate) * account_value),
+            book_units: crate::round_to_u64(account_value),
+            cache: BTreeMap::new(),
+        })
+    }
+
+    /// Largest sample size searched for `i` errors (`.calculate.n.hyper`'s uniroot interval).
+    fn k_max(&self, i: u64) -> u64 {
+        match self.model {
+            PlanningModel::Hypergeometric => (self.n_black + i).min(self.book_units).min(self.m + self.n_black),
+            _ => u64::MAX / 2,
+        }
+    }
+
+    /// Whether `P[X ≤ i] ≤ alpha` after `k` draws, i.e. `n(i) ≤ k`.
+    fn cdf_at_most(&self, i: u64, k: u64) -> bool {
+        match self.model {
+            PlanningModel::Hypergeometric => hyper_cdf_at_most(i, self.alpha, self.m, self.n_black, k),
+            // P[Binom(k, p) ≤ i] = I_{1−p}(k − i, i + 1)
+            _ => k > i && beta_reg((k - i) as f64, (i + 1) as f64, 1.0 - self.rate) <= self.alpha,
+        }
+    }
+
+    /// `n(i)`, the smallest sample size that still supports the conclusion with `i` errors.
+    pub(crate) fn n(&mut self, i: u64) -> Result<u64, MusError> {
+        if let Some(&n) = self.cache.get(&i) {
+            return Ok(n);
+        }
+        let n = match self.model {
+            // P[Poisson(n p) ≤ i] ≤ alpha  <=>  n p ≥ qgamma(1 − alpha, i + 1)
+            PlanningModel::Poisson => {
+                let g = Gamma::new(i as f64 + 1.0, 1.0).map_err(|e| MusError::Calculation(format!("gamma: {e}")))?;
+                (g.inverse_cdf(1.0 - self.alpha) / self.rate).ceil() as u64
+            }
+            _ => {
+                let lo = self.cache.range(..i).next_back().map_or(0, |(_, &n)| n);
+                let hi = self.cache.range(i + 1..).next().map_or(u64::MAX, |(_, &n)| n).min(self.k_max(i));
+                first_true(lo, hi, |k| Ok(self.cdf_at_most(i, k)))?
+            }
+        };
+        self.cache.insert(i, n);
+        Ok(n)
+    }
+
+    /// Whether `n(i) ≤ k`; a single CDF evaluation unless `n(i)` is already known.
+    pub(crate) fn at_most(&mut self, i: u64, k: u64) -> Result<bool, MusError> {
+        if self.model == PlanningModel::Poisson || self.cache.contains_key(&i) {
+            return Ok(self.n(i)? <= k);
+        }
+        Ok(k >= self.k_max(i) || self.cdf_at_most(i, k))
+    }
+
+    /// First `i` in `0..=i_max` with `n(i) ≤ limit(i)`, or `i_max`. This is a plain scan like
+    /// R's: the test is not monotone near the crossing when the expected error is close to
+    /// the tolerable error, and each step is one early-exit CDF evaluation.
+    pub(crate) fn first_crossing(&mut self, i_max: u64, limit: impl Fn(u64) -> u64) -> Result<u64, MusError> {
+        for i in 0..i_max {
+            if self.at_most(i, limit(i))? {
+                return Ok(i);
+            }
+        }
+        Ok(i_max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+    use statrs::distribution::{DiscreteCDF, Hypergeometric};
+
+    #[test]
+    fn stable_cdf_matches_statrs_and_naive_search() {
+        // Small populations: same decisions as the statrs CDF, same n as a linear scan
+        for (m, n_black, q) in [(50u64, 950u64, 0u64), (50, 950, 3), (7, 13, 2), (300, 700, 40)] {
+            let n_total = m + n_black;
+            for k in 0..=n_total {
+                let cdf = Hypergeometric::new(n_total, m, k).expect("hyper").cdf(q);
+                for alpha in [0.05, cdf * (1.0 + 1e-9), cdf * (1.0 - 1e-9)] {
+                    if alpha > 0.0 && alpha < 1.0 {
+                        assert_eq!(hyper_cdf_at_most(q, alpha, m, n_black, k), cdf <= alpha, "m={m} nb={n_black} q={q} k={k}");
+                    }
+                }
+            }
+            let naive = (0..=n_total).find(|&k| hyper_cdf_at_most(q, 0.05, m, n_black, k)).unwrap_or(n_total);
+            assert_eq!(min_draws_for_cdf_at_most_q(q, 0.05, m, n_black, n_total).expect("n"), naive);
+        }
+        // 1e12 units at 5%: hypergeometric ≈ binomial, ln 0.05 / ln 0.95 → 59
+        assert_abs_diff_eq!(ln_falling_ratio(950_000_000_000, 1_000_000_000_000, 59), 59.0 * 0.95f64.ln(), epsilon = 1e-9);
+        let mut sizes = SampleSizes::new(PlanningModel::Hypergeometric, 0.05, 5e10, 1e12).expect("sizes");
+        let mut binom = SampleSizes::new(PlanningModel::Binomial, 0.05, 5e10, 1e12).expect("sizes");
+        for i in [0, 5, 1, 40, 3] {
+            assert_eq!(sizes.n(i).expect("n"), binom.n(i).expect("n"), "i={i}");
+            let n = sizes.n(i).expect("n");
+            assert!(sizes.at_most(i + 1, n).is_ok_and(|ok| !ok));
+        }
+    }
+}
@@ -1,13 +1,14 @@
 use crate::config::TransactionType;
-use crate::journal::{find_acct_code_col, find_credit_col, find_date_col, find_debit_col, find_direction_col, find_report_subject_col, find_signed_amount_col, find_voucher_line_col, parse_amount, parse_date_flex, JournalData, Record};
+use crate::journal::{parse_money, Cell, Columns, JournalData, Record};
 use anyhow::{bail, Context, Result};
+use audit_sampling::{allocate_sample, stratify, Allocation, Currency, ExtractionWarning, Money, PlanningWarning, Stratum, StratumBoundaries};
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
-use rand::{rng, Rng};
+use rand::SeedableRng;
 use rust_xlsxwriter::{Workbook, Worksheet};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
 use std::env;
-use libc;
 
 fn truncate_chars(s: &str, max_chars: usize) -> String { s.chars().take(max_chars).collect() }
 fn truncate_to_bytes(s: &str, max_bytes: usize) -> String {
@@ -22,7 +23,7 @@ fn truncate_to_bytes(s: &str, max_bytes: usize) -> String {
     out
 }
 
-fn sanitize_sheet_name(name: &str) -> String {
+pub fn sanitize_sheet_name(name: &str) -> String {
     let mut s = name.replace(['\\', '/', '*', '?', ':', '[', ']'], "");
     while s.starts_with('\'') { s.remove(0); }
     while s.ends_with('\'') { s.pop(); }
@@ -30,10 +31,16 @@ fn sanitize_sheet_name(name: &str) -> String {
     if s.is_empty() { return "Sheet".to_string(); }
     // 优先按字符截断，再保证字节数≤31（兼容部分库内部按字节处理的场景）
     let mut t = truncate_chars(s, 31);
-    if t.as_bytes().len() > 31 { t = truncate_to_bytes(&t, 31); }
+    if t.len() > 31 { t = truncate_to_bytes(&t, 31); }
     if t.is_empty() { "Sheet".to_string() } else { t }
 }
 
+/// 总体的样本工作表名：取“抽样统计”的“工作表”列（`load_summary_sheet_names`），写出时重名的表带有
+/// “ (2)”等后缀；旧工作簿无该列时按总体名称推得。
+pub fn sample_sheet_name(sheets: &HashMap<String, String>, population: &str) -> String {
+    sheets.get(population).cloned().unwrap_or_else(|| sanitize_sheet_name(population))
+}
+
 fn unique_sheet_name(base: &str, used: &mut std::collections::HashSet<String>) -> String {
     const MAX_CHARS: usize = 31;
     let mut candidate = sanitize_sheet_name(base);
@@ -50,7 +57,7 @@ fn unique_sheet_name(base: &str, used: &mut std::collections::HashSet<String>) -
         let max_base_chars = MAX_CHARS.saturating_sub(suffix_len_chars);
         let base0 = truncate_chars(&sanitize_sheet_name(base), max_base_chars);
         // 同样保证字节≤31
-        let max_base_bytes = 31usize.saturating_sub(suffix.as_bytes().len());
+        let max_base_bytes = 31usize.saturating_sub(suffix.len());
         let base_trimmed = truncate_to_bytes(&base0, max_base_bytes);
         let cand = format!("{base_trimmed}{suffix}");
         if !used.contains(&cand) {
@@ -67,48 +74,45 @@ pub struct ResolvedRule {
     pub account_codes: Option<Vec<String>>, // None/empty => 不过滤
     pub transaction_type: TransactionType,
     pub value_column: Option<String>, // None => 按借/贷列
+    /// 实际取数的列：`value_column`，否则按方向取借方或贷方列
+    pub amount_column: Option<String>,
+}
+
+impl ResolvedRule {
+    pub fn new(population_name: String, account_codes: Option<Vec<String>>, transaction_type: TransactionType, value_column: Option<String>, columns: &Columns) -> Self {
+        let amount_column = value_column.clone().or_else(|| match transaction_type {
+            TransactionType::Debit => columns.debit.clone(),
+            TransactionType::Credit => columns.credit.clone(),
+        });
+        ResolvedRule { population_name, account_codes, transaction_type, value_column, amount_column }
+    }
+}
+
+fn effective_amount_for_rule(r: &Record, amount_col: Option<&str>, currency: Currency) -> Money {
+    amount_col.and_then(|c| r.cell(c)).and_then(|c| c.money(currency)).unwrap_or_default()
+}
+
+/// 金额列的单元格无法识别为金额时返回提示（序时账记录号、列名与原文）
+fn unparseable_amount(r: &Record, amount_col: Option<&str>, currency: Currency) -> Option<String> {
+    let cell = r.cell(amount_col?)?;
+    match cell.money(currency) {
+        Some(_) => None,
+        None => Some(format!("序时账第 {} 条记录的{}“{}”无法识别为金额，已排除", r.row() + 1, amount_col?, cell)),
+    }
 }
 
-fn effective_amount_for_rule(
-    r: &Record,
-    value_col: Option<&str>,
-    debit_col: Option<&str>,
-    credit_col: Option<&str>,
-    direction: &TransactionType,
-) -> f64 {
-    if let Some(col) = value_col {
-        return r
-            .values
-            .get(col)
-            .map(|s| parse_amount(s))
-            .unwrap_or(0.0);
-    }
-    match direction {
-        TransactionType::Debit => debit_col
-            .and_then(|c| r.values.get(c))
-            .map(|s| parse_amount(s))
-            .unwrap_or(0.0),
-        TransactionType::Credit => credit_col
-            .and_then(|c| r.values.get(c))
-            .map(|s| parse_amount(s))
-            .unwrap_or(0.0),
-    }
-}
-
-pub fn build_population(
-    data: &JournalData,
+/// 按期间、报表科目、科目代码、借贷方向筛选总体，并排除金额不为正的记录。
+/// 返回总体与提示：金额列含无法识别的文本时，该记录被排除并记入提示。
+pub fn build_population<'a>(
+    data: &'a JournalData,
     period: (chrono::NaiveDate, chrono::NaiveDate),
     account_name: &str,
     rule: &ResolvedRule,
-) -> Vec<Record> {
+    columns: &Columns,
+    currency: Currency,
+) -> (Vec<Record<'a>>, Vec<String>) {
     let (start, end) = period;
-    let date_col = find_date_col(&data.headers);
-    let acct_col = find_acct_code_col(&data.headers);
-    let debit_col = find_debit_col(&data.headers);
-    let credit_col = find_credit_col(&data.headers);
-    let dir_col = find_direction_col(&data.headers);
-    let subject_col = find_report_subject_col(&data.headers);
-    let signed_col = find_signed_amount_col(&data.headers);
+    let Columns { date: date_col, account_code: acct_col, debit: debit_col, credit: credit_col, direction: dir_col, report_subject: subject_col, signed_amount: signed_col, .. } = columns;
     if env::var("AS_DEBUG").is_ok() {
         eprintln!(
             "[debug] headers: date={:?}, acct={:?}, debit={:?}, credit={:?}, dir={:?}",
@@ -126,17 +130,18 @@ pub fn build_population(
         .collect();
 
     let mut out = Vec::new();
+    let mut warnings = Vec::new();
     let mut dbg_total = 0usize;
     let mut dbg_in_period = 0usize;
     let mut dbg_code_match = 0usize;
     let mut dbg_debit = 0usize;
     let mut dbg_credit = 0usize;
     let mut dbg_printed = 0usize;
-    'rows: for r in &data.rows {
+    'rows: for r in data.rows() {
         // Period filter
         dbg_total += 1;
         if let Some(dc) = &date_col {
-            match r.values.get(dc).and_then(|v| parse_date_flex(v)) {
+            match r.cell(dc).and_then(Cell::date) {
                 Some(d) => {
                     if d < start || d > end { continue 'rows; } else { dbg_in_period += 1; }
                 }
@@ -147,48 +152,48 @@ pub fn build_population(
             }
         }
         // 限定“报表科目/科目名称”等（若存在该列）
-        if let Some(sc) = &subject_col {
-            if let Some(v) = r.values.get(sc) {
-                if v.trim() != account_name { continue 'rows; }
-            }
+        if let Some(sc) = &subject_col
+            && let Some(v) = r.text(sc)
+            && v.trim() != account_name
+        {
+            continue 'rows;
         }
         // Account code filter (prefix match allowed). None/empty => 不过滤
-        if !codes.is_empty() {
-            if let Some(ac) = &acct_col {
-                if let Some(code) = r.values.get(ac) {
-                    let c = code.trim();
-                    if !codes.iter().any(|cfg| c.starts_with(cfg)) { continue 'rows; } else { dbg_code_match += 1; }
-                }
-            }
+        if !codes.is_empty()
+            && let Some(ac) = &acct_col
+            && let Some(code) = r.text(ac)
+        {
+            let c = code.trim();
+            if !codes.iter().any(|cfg| c.starts_with(cfg)) { continue 'rows; } else { dbg_code_match += 1; }
         }
 
         // Direction filter
         let mut is_debit = None;
-        if let Some(dc) = &dir_col {
-            if let Some(v) = r.values.get(dc) {
-                let v = v.trim();
-                if v.contains('借') || v.eq_ignore_ascii_case("debit") { is_debit = Some(true); }
-                if v.contains('贷') || v.eq_ignore_ascii_case("credit") { is_debit = Some(false); }
-            }
+        if let Some(dc) = &dir_col
+            && let Some(v) = r.text(dc)
+        {
+            let v = v.trim();
+            if v.contains('借') || v.eq_ignore_ascii_case("debit") { is_debit = Some(true); }
+            if v.contains('贷') || v.eq_ignore_ascii_case("credit") { is_debit = Some(false); }
         }
         if is_debit.is_none() {
-            let d_amt = debit_col.as_ref().and_then(|c| r.values.get(c)).map(|s| parse_amount(s)).unwrap_or(0.0);
-            let c_amt = credit_col.as_ref().and_then(|c| r.values.get(c)).map(|s| parse_amount(s)).unwrap_or(0.0);
+            let d_amt = debit_col.as_ref().and_then(|c| r.cell(c)).map_or(0.0, Cell::amount);
+            let c_amt = credit_col.as_ref().and_then(|c| r.cell(c)).map_or(0.0, Cell::amount);
             if d_amt > 0.0 { is_debit = Some(true); }
             else if c_amt > 0.0 { is_debit = Some(false); }
             else if d_amt < 0.0 { is_debit = Some(true); }
             else if c_amt < 0.0 { is_debit = Some(false); }
             else if let Some(sc) = &signed_col {
-                let s_amt = r.values.get(sc).map(|s| parse_amount(s)).unwrap_or(0.0);
+                let s_amt = r.cell(sc).map_or(0.0, Cell::amount);
                 if s_amt > 0.0 { is_debit = Some(true); } else if s_amt < 0.0 { is_debit = Some(false); }
             }
             if env::var("AS_DEBUG").is_ok() && dbg_printed < 5 {
-                let raw_d = debit_col.as_ref().and_then(|c| r.values.get(c)).cloned();
-                let raw_c = credit_col.as_ref().and_then(|c| r.values.get(c)).cloned();
+                let raw_d = debit_col.as_ref().and_then(|c| r.cell(c));
+                let raw_c = credit_col.as_ref().and_then(|c| r.cell(c));
                 eprintln!(
                     "[debug] sample row: code={:?}, date={:?}, raw_d={:?}, raw_c={:?}, d_amt={}, c_amt={}",
-                    acct_col.as_ref().and_then(|c| r.values.get(c)),
-                    date_col.as_ref().and_then(|c| r.values.get(c)),
+                    acct_col.as_ref().and_then(|c| r.cell(c)),
+                    date_col.as_ref().and_then(|c| r.cell(c)),
                     raw_d,
                     raw_c,
                     d_amt, c_amt
@@ -199,22 +204,24 @@ pub fn build_population(
         match (rule.transaction_type.clone(), is_debit) {
             (TransactionType::Debit, Some(true)) => { dbg_debit += 1; }
             (TransactionType::Credit, Some(false)) => { dbg_credit += 1; }
-            // Unknown direction: conservatively skip
+            // Unknown direction: conservatively skip, noting amounts that could not be read
+            (_, None) => {
+                warnings.extend(unparseable_amount(&r, rule.amount_column.as_deref(), currency));
+                continue 'rows;
+            }
             _ => continue 'rows,
         }
 
-        let eff = effective_amount_for_rule(
-            r,
-            rule.value_column.as_deref(),
-            debit_col.as_deref(),
-            credit_col.as_deref(),
-            &rule.transaction_type,
-        );
-        if eff <= 0.0 {
+        if let Some(w) = unparseable_amount(&r, rule.amount_column.as_deref(), currency) {
+            warnings.push(w);
+            continue 'rows;
+        }
+        let eff = effective_amount_for_rule(&r, rule.amount_column.as_deref(), currency);
+        if eff <= Money(0) {
             continue 'rows;
         }
 
-        out.push(r.clone());
+        out.push(r);
     }
     if env::var("AS_DEBUG").is_ok() {
         eprintln!(
@@ -223,97 +230,297 @@ pub fn build_population(
             dbg_total, dbg_in_period, dbg_code_match, dbg_debit, dbg_credit, out.len()
         );
     }
-    out
+    (out, warnings)
 }
 
-fn amounts_from_population(
-    population: &[Record],
-    value_col: Option<&str>,
-    debit_col: Option<&str>,
-    credit_col: Option<&str>,
-    direction: &TransactionType,
-) -> Vec<f64> {
-    population
-        .iter()
-        .map(|r| effective_amount_for_rule(r, value_col, debit_col, credit_col, direction))
-        .collect()
+fn amounts_from_population(population: &[Record], amount_col: Option<&str>, currency: Currency) -> Vec<Money> {
+    // 按币种精度取整到最小货币单位，以 `Money` 传入库的规划与抽取
+    population.iter().map(|r| effective_amount_for_rule(r, amount_col, currency)).collect()
 }
 
-fn pps_systematic_indices(amounts: &[f64], n: usize) -> Vec<usize> {
-    if n == 0 || amounts.is_empty() { return Vec::new(); }
-    let total: f64 = amounts.iter().sum();
-    if total <= 0.0 { return Vec::new(); }
-    let interval = total / n as f64;
-    let mut rng = rng();
-    let start: f64 = rng.random_range(0.0..interval);
-    let mut thresholds: Vec<f64> = (0..n).map(|i| start + i as f64 * interval).collect();
-    thresholds.sort_by(|a, b| a.partial_cmp(b).unwrap());
-
-    let mut indices = Vec::new();
-    let mut cum = 0.0_f64;
-    let mut t_idx = 0;
-    for (i, &v) in amounts.iter().enumerate() {
-        let prev = cum;
-        cum += v.max(0.0);
-        while t_idx < thresholds.len() && thresholds[t_idx] <= cum {
-            if thresholds[t_idx] > prev {
-                indices.push(i);
-            }
-            t_idx += 1;
-        }
-        if t_idx >= thresholds.len() { break; }
+/// 由主种子与总体名称派生该总体的种子（FNV-1a + SplitMix64 混合）：
+/// 只依赖自身名称，增删其他总体不会改变已有总体的样本。
+pub fn derive_seed(master: u64, population_name: &str) -> u64 {
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in population_name.as_bytes() {
+        h ^= *b as u64;
+        h = h.wrapping_mul(0x0000_0100_0000_01b3);
     }
-    indices
+    let mut z = master ^ h;
+    z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// 未指定 --seed 时按当前时间生成主种子（写入“抽样统计”以便复核）。
+pub fn time_seed() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
 }
 
-pub fn perform_random_sampling_with_rules(population: Vec<Record>, size: usize) -> Vec<Record> {
+pub fn perform_random_sampling_with_rules(population: Vec<Record>, size: usize, seed: u64) -> Vec<Record> {
     if size >= population.len() { return population; }
     let mut idxs: Vec<usize> = (0..population.len()).collect();
-    idxs.shuffle(&mut rng());
+    idxs.shuffle(&mut StdRng::seed_from_u64(seed));
     idxs.truncate(size);
     idxs.sort_unstable();
     idxs.into_iter().map(|i| population[i].clone()).collect()
 }
 
-fn with_silenced_stderr<F, T>(f: F) -> T
-where
-    F: FnOnce() -> T,
-{
-    unsafe {
-        let stderr_fd = libc::STDERR_FILENO;
-        let saved = libc::dup(stderr_fd);
-        let devnull_c = b"/dev/null\0";
-        let dn = libc::open(devnull_c.as_ptr() as *const i8, libc::O_WRONLY);
-        if dn >= 0 {
-            libc::dup2(dn, stderr_fd);
-            libc::close(dn);
+/// 扩样时写入输出的批次列：0 为原样本，之后每次扩样递增。
+pub const BATCH_COL: &str = "抽样批次";
+
+/// 写入样本表的序时账记录号（从 1 起，不计表头与空行）：扩样与评价据此把样本行对应回总体记录。
+pub const ROW_COL: &str = "序时账记录号";
+
+fn record_key(r: &Record, headers: &[String]) -> Vec<String> {
+    headers
+        .iter()
+        .map(|h| r.text(h).map(|v| v.trim().to_string()).unwrap_or_default())
+        .collect()
+}
+
+/// 样本表各行在总体中的位置。有 `ROW_COL` 列时按记录号对应；否则按其余列的文本对应，文本相同的多行依次
+/// 对应，`strict` 时（需要区分各条记录）这种情况报错。`skip` 为不参与比较的列（如审定金额）。
+fn locate_sample_rows(population: &[Record], sample: &JournalData, skip: &[&str], strict: bool) -> Result<Vec<usize>> {
+    let mut out = Vec::with_capacity(sample.len());
+    let mut missing = 0usize;
+    if sample.headers.iter().any(|h| h == ROW_COL) {
+        let position: HashMap<usize, usize> = population.iter().enumerate().map(|(i, r)| (r.row() + 1, i)).collect();
+        for (k, r) in sample.rows().enumerate() {
+            let text = r.text(ROW_COL).unwrap_or_default();
+            let Ok(row) = text.trim().parse::<usize>() else {
+                bail!("样本表第 {} 行的{ROW_COL}“{}”无法识别", k + 2, text.trim());
+            };
+            match position.get(&row) {
+                Some(&i) => out.push(i),
+                None => missing += 1,
+            }
         }
-        let res = f();
-        if saved >= 0 {
-            libc::dup2(saved, stderr_fd);
-            libc::close(saved);
+    } else {
+        let headers: Vec<String> = sample
+            .headers
+            .iter()
+            .filter(|h| ![BATCH_COL, STRATUM_COL].contains(&h.as_str()) && !h.starts_with(INCLUSION_COL) && !skip.contains(&h.as_str()))
+            .cloned()
+            .collect();
+        let mut index: HashMap<Vec<String>, Vec<usize>> = HashMap::new();
+        for (i, r) in population.iter().enumerate() {
+            index.entry(record_key(r, &headers)).or_default().push(i);
+        }
+        for r in sample.rows() {
+            let Some(cands) = index.get_mut(&record_key(&r, &headers)).filter(|c| !c.is_empty()) else {
+                missing += 1;
+                continue;
+            };
+            if strict && cands.len() > 1 {
+                bail!("样本表中有内容相同的记录无法区分，请保留“{ROW_COL}”列");
+            }
+            out.push(cands.remove(0));
+        }
+    }
+    if missing > 0 {
+        bail!("已有样本中有 {missing} 条记录在当前总体中找不到（序时账、期间或规则可能已变化）");
+    }
+    Ok(out)
+}
+
+fn sample_batches(sample: &JournalData) -> Vec<u32> {
+    sample.rows().map(|r| r.text(BATCH_COL).and_then(|b| b.trim().parse().ok()).unwrap_or(0)).collect()
+}
+
+/// 随机扩样：按已有样本工作表 `previous`（可无）识别总体中已抽中的记录，在其余记录中等概率追加抽取
+/// `additional` 条，返回原样本 + 追加样本（每条记录带 `BATCH_COL` 批次号）。
+pub fn extend_sample_with_rules<'a>(population: Vec<Record<'a>>, previous: Option<&JournalData>, additional: usize, seed: u64) -> Result<Vec<Record<'a>>> {
+    let mut taken = vec![false; population.len()];
+    let mut out: Vec<Record> = Vec::new();
+    let mut last_batch = 0u32;
+    if let Some(prev) = previous {
+        let positions = locate_sample_rows(&population, prev, &[], false)?;
+        for (i, batch) in positions.into_iter().zip(sample_batches(prev)) {
+            taken[i] = true;
+            let mut r = population[i].clone();
+            r.set(BATCH_COL, batch.to_string());
+            out.push(r);
+            last_batch = last_batch.max(batch);
         }
-        res
     }
+    let remaining: Vec<Record> = population
+        .into_iter()
+        .zip(&taken)
+        .filter(|(_, t)| !**t)
+        .map(|(r, _)| r)
+        .collect();
+    let batch = (last_batch + 1).to_string();
+    out.extend(perform_random_sampling_with_rules(remaining, additional, seed).into_iter().map(|mut r| {
+        r.set(BATCH_COL, batch.clone());
+        r
+    }));
+    Ok(out)
 }
 
-pub fn perform_mus_sampling_with_rules(
-    population: Vec<Record>,
+/// 重建样本表 `sample` 对应的 MUS 抽取：按 `plan`（与 `population` 逐条对应）以 `params.seed`（原抽样的种子）
+/// 重放 `mus_extraction`，再按 `extensions`（历次扩样数量）依次 `mus_extend`，并核对各批次抽中的项目与样本表
+/// 逐条一致。返回抽取结果、各项目（`plan.data` 下标）的批次，以及样本表各行在总体中的位置。
+fn replay_mus_extraction(
+    population: &[Record],
+    plan: &audit_sampling::Plan,
+    params: &MusParams,
+    sample: &JournalData,
+    extensions: &[usize],
+    skip: &[&str],
+) -> Result<(audit_sampling::Extraction, HashMap<usize, u32>, Vec<usize>)> {
+    if plan.n == 0 {
+        bail!("规划样本量为 0，无法重放抽取");
+    }
+    let mut extract = mus_extract(plan, params, params.seed)?;
+    let mut batches: HashMap<usize, u32> = HashMap::new();
+    let mut record = |extract: &audit_sampling::Extraction, from: (usize, usize), batch: u32| {
+        let items = extract.high_values[from.0..].iter().map(|h| h.index).chain(extract.sample[from.1..].iter().map(|s| s.index));
+        for i in items {
+            batches.entry(i).or_insert(batch);
+        }
+    };
+    record(&extract, (0, 0), 0);
+    for (b, &size) in extensions.iter().enumerate() {
+        let from = (extract.high_values.len(), extract.sample.len());
+        extract = audit_sampling::mus_extend(&extract, audit_sampling::ExtensionSize::AdditionalN(size)).context("MUS 扩样失败")?;
+        record(&extract, from, b as u32 + 1);
+    }
+
+    let positions = locate_sample_rows(population, sample, skip, true)?;
+    let in_sheet: HashMap<usize, u32> = positions.iter().copied().zip(sample_batches(sample)).collect();
+    if in_sheet.len() != positions.len() || in_sheet != batches {
+        bail!("重放的抽样结果与样本表不一致（请使用与抽样时相同的序时账、期间、配置与 MUS 参数）");
+    }
+    Ok((extract, batches, positions))
+}
+
+/// MUS 扩样：按样本表 `previous` 与原抽样的种子（`params.seed`）、历次扩样数量 `extensions` 重建抽取，
+/// 再调用库的 `mus_extend` 追加 `additional` 个项目；返回原样本 + 追加样本（带 `BATCH_COL`）。
+pub fn extend_mus_sample_with_rules<'a>(
+    population: Vec<Record<'a>>,
     rule: &ResolvedRule,
-    tolerable_error: f64,
-    expected_error: f64,
-    confidence: f64,
+    params: &MusParams,
+    previous: &JournalData,
+    extensions: &[usize],
+    additional: usize,
     verbose: bool,
-) -> Result<Vec<Record>> {
-    // Amounts (with fallback to debit/credit columns if value_column not present)
-    let header_probe: Vec<String> = population
-        .get(0)
-        .map(|r| r.values.keys().cloned().collect())
-        .unwrap_or_else(|| Vec::new());
-    let debit_col = crate::journal::find_debit_col(&header_probe);
-    let credit_col = crate::journal::find_credit_col(&header_probe);
-    let amounts = amounts_from_population(&population, rule.value_column.as_deref(), debit_col.as_deref(), credit_col.as_deref(), &rule.transaction_type);
-    let total: f64 = amounts.iter().sum();
+) -> Result<MusSample<'a>> {
+    let plan = plan_mus_with_rules(&population, rule, params, verbose)?;
+    let (extract, mut batches, _) = replay_mus_extraction(&population, &plan, params, previous, extensions, &[])?;
+    let extended = audit_sampling::mus_extend(&extract, audit_sampling::ExtensionSize::AdditionalN(additional)).context("MUS 扩样失败")?;
+    let batch = extensions.len() as u32 + 1;
+    let new_items = extended.high_values[extract.high_values.len()..]
+        .iter()
+        .map(|h| h.index)
+        .chain(extended.sample[extract.sample.len()..].iter().map(|s| s.index));
+    for i in new_items {
+        batches.entry(i).or_insert(batch);
+    }
+    if verbose {
+        eprintln!(
+            "[MUS] 扩样 +{} -> 共 {} 条（高值项目 {} 条，抽样间隔 {:.2}）",
+            additional,
+            batches.len(),
+            extended.high_values.len(),
+            extended.sampling_interval
+        );
+    }
+    let warnings: Vec<String> = extended.warnings[extract.warnings.len()..].iter().map(extraction_warning_text).collect();
+    report_warnings(&rule.population_name, &warnings);
+    let mut items: Vec<(usize, u32)> = batches.into_iter().collect();
+    items.sort_unstable();
+    let records = items
+        .into_iter()
+        .map(|(i, b)| {
+            let mut r = population[i].clone();
+            r.set(BATCH_COL, b.to_string());
+            r
+        })
+        .collect();
+    Ok(MusSample {
+        records,
+        high_values: extended.high_values.len(),
+        start_point: Some(extended.start_point),
+        sampling_interval: extended.sampling_interval,
+        warnings,
+        extraction: Some(extended),
+    })
+}
+
+/// 规划警告的中文说明（写入 stderr 与“抽样统计”的警告列）
+fn planning_warning_text(w: &PlanningWarning) -> String {
+    match *w {
+        PlanningWarning::NonFiniteValues(k) => format!("{k} 个账面金额缺失或非有限值，不会被抽中"),
+        PlanningWarning::ZeroValues(k) => format!("{k} 个账面金额为零，不会被抽中"),
+        PlanningWarning::NegativeValues(k) => format!("{k} 个账面金额为负，已忽略"),
+        PlanningWarning::ImpracticallyLargeSample => "参数组合导致样本量过大，难以实施".to_string(),
+        PlanningWarning::NoSamplingNecessary => "可容忍错报不低于总体金额，无需抽样（n=0）".to_string(),
+        PlanningWarning::AuditEverything => "样本量超过总体项目数，MUS 不适用，已改为全部审计".to_string(),
+    }
+}
+
+/// 抽取警告的中文说明
+fn extraction_warning_text(w: &ExtractionWarning) -> String {
+    match *w {
+        ExtractionWarning::FewerItemsThanPlanned { planned, selected } => format!("实际抽中 {selected} 个不同项目，少于计划的 {planned} 个"),
+        ExtractionWarning::RepeatedHits(k) => format!("{k} 个项目被多个货币单元重复命中，只计一次"),
+    }
+}
+
+pub fn report_warnings(population: &str, texts: &[String]) {
+    for t in texts {
+        eprintln!("[警告] {population}：{t}");
+    }
+}
+
+/// MUS 参数（CLI → 库）：TE/EE/置信水平与抽取选项
+pub struct MusParams {
+    pub tolerable_error: f64,
+    pub expected_error: f64,
+    pub confidence: f64,
+    /// 以 n 为最低样本量：按完美抽样间隔迭代剔除高值项目（对应库 `obey_n_as_min`）
+    pub obey_n_as_min: bool,
+    /// 固定起始点；缺省时由种子随机生成
+    pub start_point: Option<f64>,
+    pub seed: u64,
+    pub rng: audit_sampling::RngKind,
+    pub mode: audit_sampling::ExtractionMode,
+    /// 选样方式；非系统选样时不使用 `start_point` / `mode`，样本记录带 `INCLUSION_COL` 列
+    pub selection: audit_sampling::PpsScheme,
+    /// 规划模型（超几何 / 二项 / 泊松）
+    pub model: audit_sampling::PlanningModel,
+    /// 金额币种精度：MUS 的货币单元为其最小单位（如人民币的分）
+    pub currency: Currency,
+}
+
+/// MUS 抽样结果：样本记录与写入“抽样统计”的抽取信息
+pub struct MusSample<'a> {
+    pub records: Vec<Record<'a>>,
+    pub high_values: usize,
+    pub start_point: Option<f64>,
+    pub sampling_interval: f64,
+    /// 规划与抽取警告（中文说明）
+    pub warnings: Vec<String>,
+    /// 系统选样的抽取结果（可存档为 JSON）；非系统选样或 n=0 时为空
+    pub extraction: Option<audit_sampling::Extraction>,
+}
+
+/// 非系统选样时写入样本记录的入选概率列
+pub const INCLUSION_COL: &str = "入选概率";
+
+fn amounts_for_rule(population: &[Record], rule: &ResolvedRule, currency: Currency) -> Vec<Money> {
+    amounts_from_population(population, rule.amount_column.as_deref(), currency)
+}
+
+fn mus_plan_for_rule(amounts: &[Money], rule: &ResolvedRule, params: &MusParams, verbose: bool) -> Result<audit_sampling::Plan> {
+    let MusParams { tolerable_error, expected_error, confidence, .. } = *params;
+    let total = params.currency.amount(amounts.iter().copied().sum());
     if !total.is_finite() || total <= 0.0 {
         bail!("总体金额为空或非正，已跳过（可能被负数或零值剔除后为空）");
     }
@@ -331,48 +538,473 @@ pub fn perform_mus_sampling_with_rules(
     }
 
     // Use library planning to derive n
-    use audit_sampling::{mus_planning, PlanningOptions};
+    use audit_sampling::{mus_planning_money, PlanningOptions};
     let opts = PlanningOptions {
         col_name_book_values: rule.value_column.clone().unwrap_or_else(|| "book.value".to_string()),
         confidence_level: confidence,
         tolerable_error,
         expected_error,
+        model: params.model,
+        currency: params.currency,
         ..Default::default()
     };
-    let plan = if verbose {
-        mus_planning(&amounts, opts)
-    } else {
-        with_silenced_stderr(|| mus_planning(&amounts, opts))
-    }.context("MUS 规划失败")?;
+    let plan = mus_planning_money(amounts, opts).context("MUS 规划失败")?;
+    let texts: Vec<String> = plan.warnings.iter().map(planning_warning_text).collect();
+    report_warnings(&rule.population_name, &texts);
+    Ok(plan)
+}
+
+/// 调用库的 `mus_extraction`：高值项目全部入样，其余总体按系统（或单元）抽样命中。
+fn mus_extract(plan: &audit_sampling::Plan, params: &MusParams, seed: u64) -> Result<audit_sampling::Extraction> {
+    audit_sampling::mus_extraction(plan, audit_sampling::ExtractionOptions {
+        start_point: params.start_point,
+        seed: Some(seed),
+        obey_n_as_min: params.obey_n_as_min,
+        rng: params.rng,
+        mode: params.mode,
+        combined: plan.combined,
+    })
+    .context("MUS 抽取失败")
+}
+
+/// 按 `plan`（`plan.data` 与 `population` 逐条对应）调用库的 `mus_extraction`，并把高值项目与命中的货币单元映射回记录。
+fn mus_extract_records<'a>(population: &[Record<'a>], plan: &audit_sampling::Plan, params: &MusParams, seed: u64, verbose: bool) -> Result<MusSample<'a>> {
     let n = plan.n;
-    if n == 0 { return Ok(Vec::new()); }
+    if n == 0 {
+        return Ok(MusSample { records: Vec::new(), high_values: 0, start_point: None, sampling_interval: f64::INFINITY, warnings: Vec::new(), extraction: None });
+    }
+    if params.selection != audit_sampling::PpsScheme::Systematic {
+        return pps_select_records(population, plan, params.selection, seed, verbose);
+    }
+
+    let extract = mus_extract(plan, params, seed)?;
+
+    // 高值项目与命中项目均带 plan.data 的下标；同一记录被多个货币单元命中时只保留一次
+    let mut seen: HashSet<usize> = HashSet::with_capacity(n);
+    let unique_idxs: Vec<usize> = extract
+        .high_values
+        .iter()
+        .map(|h| h.index)
+        .chain(extract.sample.iter().map(|item| item.index))
+        .filter(|&i| seen.insert(i))
+        .collect();
+    if verbose {
+        eprintln!(
+            "[MUS] 计划 n={} -> 实际 {}（高值项目 {} 条，抽样间隔 {:.2}，起始点 {:.2}）",
+            n,
+            unique_idxs.len(),
+            extract.high_values.len(),
+            extract.sampling_interval,
+            extract.start_point
+        );
+    }
 
-    // Select by PPS systematic sampling using computed n
-    let idxs = pps_systematic_indices(&amounts, n);
-    if idxs.is_empty() { return Ok(Vec::new()); }
+    let records = unique_idxs.into_iter().map(|i| population[i].clone()).collect();
+    Ok(MusSample {
+        records,
+        high_values: extract.high_values.len(),
+        start_point: Some(extract.start_point),
+        sampling_interval: extract.sampling_interval,
+        warnings: extract.warnings.iter().map(extraction_warning_text).collect(),
+        extraction: Some(extract),
+    })
+}
 
-    // 去重处理：对于被多个抽样阈值命中的大额记录，只保留首次命中的索引
-    use std::collections::HashSet;
-    let mut seen: HashSet<usize> = HashSet::with_capacity(idxs.len());
-    let mut unique_idxs: Vec<usize> = Vec::with_capacity(idxs.len());
-    for i in idxs {
-        if seen.insert(i) {
-            unique_idxs.push(i);
+/// 随机 PPS（放回）/ 筛选 / Sampford 选样：按 plan.n 在全体金额上选取，
+/// 入选概率为 1 的项目计作高值项目。
+fn pps_select_records<'a>(
+    population: &[Record<'a>],
+    plan: &audit_sampling::Plan,
+    scheme: audit_sampling::PpsScheme,
+    seed: u64,
+    verbose: bool,
+) -> Result<MusSample<'a>> {
+    let sel = audit_sampling::pps_select(&plan.book_values(), plan.n, scheme, seed).context("PPS 选样失败")?;
+    let idxs = sel.distinct();
+    let pi = &sel.inclusion_probabilities;
+    let high_values = idxs.iter().filter(|&&i| pi[i] >= 1.0).count();
+    if verbose {
+        eprintln!("[MUS] {:?}：计划 n={} -> 实际 {}（入选概率为 1 的项目 {} 条）", scheme, plan.n, idxs.len(), high_values);
+    }
+    let records = idxs
+        .into_iter()
+        .map(|i| {
+            let mut r = population[i].clone();
+            r.set(INCLUSION_COL, format!("{:.6}", pi[i]));
+            r
+        })
+        .collect();
+    Ok(MusSample { records, high_values, start_point: None, sampling_interval: f64::INFINITY, warnings: Vec::new(), extraction: None })
+}
+
+/// MUS 规划：按规则取总体金额并调用库的 `mus_planning`（`plan.data` 与 `population` 逐条对应）。
+pub fn plan_mus_with_rules(population: &[Record], rule: &ResolvedRule, params: &MusParams, verbose: bool) -> Result<audit_sampling::Plan> {
+    let amounts = amounts_for_rule(population, rule, params.currency);
+    mus_plan_for_rule(&amounts, rule, params, verbose)
+}
+
+/// 按 `plan` 抽取（`plan` 可来自 `plan_mus_with_rules` 或已保存的计划）。
+pub fn perform_mus_sampling_with_rules<'a>(
+    population: Vec<Record<'a>>,
+    rule: &ResolvedRule,
+    plan: &audit_sampling::Plan,
+    params: &MusParams,
+    verbose: bool,
+) -> Result<MusSample<'a>> {
+    let mut mus = mus_extract_records(&population, plan, params, params.seed, verbose)?;
+    report_warnings(&rule.population_name, &mus.warnings);
+    mus.warnings.splice(0..0, plan.warnings.iter().map(planning_warning_text));
+    Ok(mus)
+}
+
+/// 计划 / 抽取 JSON 文件：`目录/总体名称.plan.json`、`目录/总体名称.extraction.json`
+pub fn artifact_path(dir: &Path, population_name: &str, kind: &str) -> std::path::PathBuf {
+    dir.join(format!("{}.{kind}.json", sanitize_sheet_name(population_name)))
+}
+
+pub fn save_json(path: &Path, text: &str) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).with_context(|| format!("创建目录失败: {}", dir.display()))?;
+    }
+    std::fs::write(path, text).with_context(|| format!("写出失败: {}", path.display()))
+}
+
+/// 读取已保存的计划，并核对其账面金额与当前总体逐条一致（序时账、期间或规则变化时报错）。
+pub fn load_plan(dir: &Path, population: &[Record], rule: &ResolvedRule, params: &MusParams) -> Result<audit_sampling::Plan> {
+    let path = artifact_path(dir, &rule.population_name, "plan");
+    let text = std::fs::read_to_string(&path).with_context(|| format!("读取计划失败: {}", path.display()))?;
+    let plan = audit_sampling::Plan::from_json(&text).with_context(|| format!("计划文件无效: {}", path.display()))?;
+    if plan.currency != params.currency {
+        bail!("计划的币种精度（{} 位小数）与 --currency 不一致", plan.currency.decimals());
+    }
+    let amounts = amounts_for_rule(population, rule, params.currency);
+    if amounts != plan.data {
+        bail!("总体与计划不一致（计划 {} 条，当前 {} 条）：请使用规划时的序时账、期间与配置", plan.data.len(), amounts.len());
+    }
+    Ok(plan)
+}
+
+/// MUS 评价：按 `plan` 以原种子与历次扩样数量 `extensions` 重放抽取（含各扩样批次），从样本表 `sample` 的
+/// 审定金额列读取各高值项目与抽样项目的审定金额（空白视为与账面一致；样本行按序时账记录号对应总体记录），
+/// 调用库的 `mus_evaluation`。`plan.combined` 为 true 时该总体是合并评价的组成部分，结论留待 `mus_combine`。
+pub fn evaluate_mus_with_rules(
+    population: &[Record],
+    plan: &audit_sampling::Plan,
+    params: &MusParams,
+    sample: &JournalData,
+    extensions: &[usize],
+    audit_column: &str,
+) -> Result<audit_sampling::Evaluation> {
+    if !sample.headers.iter().any(|h| h == audit_column) {
+        bail!("样本表缺少审定金额列“{audit_column}”");
+    }
+    let (extract, _, positions) = replay_mus_extraction(population, plan, params, sample, extensions, &[audit_column])?;
+    let mut audited: HashMap<usize, f64> = HashMap::new();
+    for ((row, r), i) in sample.rows().enumerate().zip(positions) {
+        let text = r.text(audit_column).unwrap_or_default();
+        let text = text.trim();
+        let value = if text.is_empty() {
+            plan.currency.amount(plan.data[i])
+        } else {
+            match parse_money(text, params.currency) {
+                Some(m) => params.currency.amount(m),
+                None => bail!("第 {} 行的审定金额“{}”无法识别", row + 2, text),
+            }
+        };
+        audited.insert(i, value);
+    }
+    let high_value_audit_values = extract.high_values.iter().map(|h| audited[&h.index]).collect();
+    let audited_values: Vec<f64> = extract.sample.iter().map(|item| audited[&item.index]).collect();
+    let opts = audit_sampling::EvaluationOptions { high_value_audit_values, ..audit_sampling::EvaluationOptions::for_extraction(&extract) };
+    audit_sampling::mus_evaluation(&extract, &audited_values, opts).context("MUS 评价失败")
+}
+
+/// “抽样统计”中 MUS 评价的各列：单个总体或同一科目各总体的合并评价
+pub struct MusEvaluationRow {
+    pub overstatement: audit_sampling::MisstatementBound,
+    pub understatement: audit_sampling::MisstatementBound,
+    pub net_most_likely_misstatement: f64,
+    pub net_overstatement_limit: f64,
+    pub net_understatement_limit: f64,
+    pub tolerable_error: f64,
+    pub conclusion: audit_sampling::Conclusion,
+}
+
+impl MusEvaluationRow {
+    pub fn population(e: &audit_sampling::Evaluation) -> Self {
+        Self {
+            overstatement: e.overstatement.clone(),
+            understatement: e.understatement.clone(),
+            net_most_likely_misstatement: e.net_most_likely_misstatement,
+            net_overstatement_limit: e.net_overstatement_limit,
+            net_understatement_limit: e.net_understatement_limit,
+            tolerable_error: e.extraction.plan.tolerable_error,
+            conclusion: e.conclusion,
+        }
+    }
+
+    pub fn combined(c: &audit_sampling::CombinedEvaluation) -> Self {
+        Self {
+            overstatement: c.overstatement.clone(),
+            understatement: c.understatement.clone(),
+            net_most_likely_misstatement: c.net_most_likely_misstatement,
+            net_overstatement_limit: c.net_overstatement_limit,
+            net_understatement_limit: c.net_understatement_limit,
+            tolerable_error: c.tolerable_error,
+            conclusion: c.conclusion,
         }
     }
-    if verbose && unique_idxs.len() < n {
+}
+
+/// 评价后的“抽样统计”行：总体的抽取信息（含扩样）与评价结果
+pub fn evaluation_summary_row(population: &str, population_len: usize, extensions: &[usize], e: &audit_sampling::Evaluation) -> SummaryRow {
+    let x = &e.extraction;
+    let items: HashSet<usize> = x.high_values.iter().map(|h| h.index).chain(x.sample.iter().map(|s| s.index)).collect();
+    SummaryRow {
+        population: population.to_string(),
+        population_len,
+        sample_len: items.len(),
+        seed: x.seed,
+        high_values: Some(x.high_values.len()),
+        sampling_interval: Some(x.sampling_interval),
+        start_point: Some(x.start_point),
+        extensions: extensions.to_vec(),
+        warnings: x.warnings.iter().map(extraction_warning_text).collect(),
+        evaluation: Some(MusEvaluationRow::population(e)),
+        ..Default::default()
+    }
+}
+
+/// 同一科目各总体合并评价的“抽样统计”行（“科目（合并）”）：总体条数与样本条数为各总体之和
+pub fn combined_summary_row(account: &str, rows: &[SummaryRow], c: &audit_sampling::CombinedEvaluation) -> SummaryRow {
+    SummaryRow {
+        population: format!("{account}（合并）"),
+        population_len: rows.iter().map(|r| r.population_len).sum(),
+        sample_len: rows.iter().map(|r| r.sample_len).sum(),
+        evaluation: Some(MusEvaluationRow::combined(c)),
+        ..Default::default()
+    }
+}
+
+/// 属性抽样参数（控制测试）
+pub struct AttributeParams {
+    pub tolerable_deviation_rate: f64,
+    pub expected_deviation_rate: f64,
+    pub confidence: f64,
+    pub model: audit_sampling::AttributeModel,
+}
+
+/// 属性抽样：按总体条数规划样本量（库 `attribute_planning`），再等概率随机抽取 n 条。
+pub fn perform_attribute_sampling_with_rules<'a>(
+    population: Vec<Record<'a>>,
+    rule: &ResolvedRule,
+    params: &AttributeParams,
+    seed: u64,
+    verbose: bool,
+) -> Result<(Vec<Record<'a>>, audit_sampling::AttributePlan)> {
+    use audit_sampling::{attribute_planning, AttributePlanningOptions};
+    let plan = attribute_planning(AttributePlanningOptions {
+        model: params.model,
+        confidence_level: params.confidence,
+        tolerable_deviation_rate: params.tolerable_deviation_rate,
+        expected_deviation_rate: params.expected_deviation_rate,
+        population_size: Some(population.len()),
+    }).context("属性抽样规划失败")?;
+    if verbose {
         eprintln!(
-            "[MUS] 去重后样本: 计划 n={} -> 实际 {}（存在大额记录被多次命中，已合并去重）",
-            n,
-            unique_idxs.len()
+            "[属性] population='{}' N={} TDR={:.4} EDR={:.4} Conf={:.2} -> n={}（允许偏差 {} 个）",
+            rule.population_name,
+            population.len(),
+            params.tolerable_deviation_rate,
+            params.expected_deviation_rate,
+            params.confidence,
+            plan.n,
+            plan.allowable_deviations
         );
     }
+    Ok((perform_random_sampling_with_rules(population, plan.n, seed), plan))
+}
+
+/// 解析偏差标记：是/Y/yes/true/1/x/√ 等为偏差，空白与 否/N/no/false/0 为无偏差，其余无法识别。
+pub fn parse_deviation_flag(s: &str) -> Option<bool> {
+    let t = s.trim().to_ascii_lowercase();
+    match t.as_str() {
+        "" | "否" | "无" | "n" | "no" | "false" | "0" | "正常" => Some(false),
+        "是" | "有" | "y" | "yes" | "true" | "1" | "x" | "√" | "✓" | "偏差" | "异常" => Some(true),
+        _ => None,
+    }
+}
+
+/// 统计样本表中的样本条数与偏差数（`column` 为偏差标记列）。
+fn count_deviations(sample: &JournalData, column: &str) -> Result<(usize, u64)> {
+    if !sample.headers.iter().any(|h| h == column) {
+        bail!("样本表缺少偏差标记列“{column}”");
+    }
+    let mut deviations = 0u64;
+    for (i, r) in sample.rows().enumerate() {
+        let v = r.text(column).unwrap_or_default();
+        match parse_deviation_flag(&v) {
+            Some(true) => deviations += 1,
+            Some(false) => {}
+            None => bail!("第 {} 行的偏差标记“{}”无法识别（可用：是/否、Y/N、1/0）", i + 2, v),
+        }
+    }
+    Ok((sample.len(), deviations))
+}
+
+/// 属性抽样评价：按样本表 `sample` 的偏差标记列 `column` 统计样本条数与偏差数，调用库的 `attribute_evaluation`。
+pub fn evaluate_attribute_sample(sample: &JournalData, column: &str, opts: audit_sampling::AttributeEvaluationOptions) -> Result<audit_sampling::AttributeEvaluation> {
+    let (n, deviations) = count_deviations(sample, column)?;
+    audit_sampling::attribute_evaluation(n, deviations, opts).context("属性抽样评价失败")
+}
+
+/// 写出属性抽样评价结果（每个总体一行）。
+pub fn write_attribute_evaluation(rows: &[(String, audit_sampling::AttributeEvaluation)], output: &Path, note: &str) -> Result<()> {
+    let mut wb = Workbook::new();
+    let ws = wb.add_worksheet().set_name("属性抽样评价")?;
+    let headers = ["总体名称", "样本条数", "偏差数", "样本偏差率", "偏差率上限", "抽样风险允许限度", "可容忍偏差率", "置信水平", "结论", "参数"];
+    for (c, h) in headers.iter().enumerate() { ws.write_string(0, c as u16, *h)?; }
+    for (i, (name, e)) in rows.iter().enumerate() {
+        let r = (i + 1) as u32;
+        ws.write_string(r, 0, name)?;
+        ws.write_string(r, 1, e.n.to_string())?;
+        ws.write_string(r, 2, e.deviations.to_string())?;
+        ws.write_string(r, 3, format!("{:.4}", e.sample_deviation_rate))?;
+        ws.write_string(r, 4, format!("{:.4}", e.upper_deviation_limit))?;
+        ws.write_string(r, 5, format!("{:.4}", e.allowance_for_sampling_risk))?;
+        ws.write_string(r, 6, format!("{:.4}", e.tolerable_deviation_rate))?;
+        ws.write_string(r, 7, format!("{:.2}", e.confidence_level))?;
+        let conclusion = match e.conclusion {
+            audit_sampling::Conclusion::Accept => "可信赖控制",
+            audit_sampling::Conclusion::Reject => "不可信赖控制",
+            audit_sampling::Conclusion::Deferred => "待合并评价",
+        };
+        ws.write_string(r, 8, conclusion)?;
+        ws.write_string(r, 9, note)?;
+    }
+    wb.save(output).with_context(|| format!("保存 Excel 失败: {}", output.display()))?;
+    Ok(())
+}
+
+/// 分层抽样时写入样本的分层列
+pub const STRATUM_COL: &str = "分层";
+
+/// 分层设置：分层边界与样本量分配方式
+pub struct StratifyParams {
+    pub boundaries: StratumBoundaries,
+    pub allocation: Allocation,
+    pub currency: Currency,
+}
+
+/// 各层内的抽样方法：随机（总样本量）或 MUS（总样本量由整个总体的 MUS 规划得出）
+pub enum StratumMethod<'a> {
+    Random(usize),
+    Mus(&'a MusParams),
+}
+
+fn stratum_label(h: usize, s: &Stratum) -> String {
+    match (s.lower.is_finite(), s.upper.is_finite()) {
+        (false, false) => format!("第{}层 全部", h + 1),
+        (false, true) => format!("第{}层 <{:.2}", h + 1, s.upper),
+        (true, false) => format!("第{}层 ≥{:.2}", h + 1, s.lower),
+        (true, true) => format!("第{}层 {:.2}–{:.2}", h + 1, s.lower, s.upper),
+    }
+}
 
-    let mut out = Vec::with_capacity(unique_idxs.len());
-    for i in unique_idxs { out.push(population[i].clone()); }
+/// 分层抽样：按金额分层、分配总样本量，在各层内独立抽样（种子由 `seed` 与层号派生）。
+/// 返回每层的统计行与样本（样本记录带 `STRATUM_COL` 列）。整个总体的规划警告只写在第一层的统计行，
+/// 各层的抽取警告写在该层的行。
+pub fn perform_stratified_sampling_with_rules<'a>(
+    population: Vec<Record<'a>>,
+    rule: &ResolvedRule,
+    strat: &StratifyParams,
+    method: StratumMethod,
+    seed: u64,
+    verbose: bool,
+) -> Result<Vec<(SummaryRow, Vec<Record<'a>>)>> {
+    let amounts = amounts_for_rule(&population, rule, strat.currency);
+    let values: Vec<f64> = amounts.iter().map(|&m| strat.currency.amount(m)).collect();
+    let strata = stratify(&values, &strat.boundaries).context("分层失败")?;
+    let (n_total, plan) = match method {
+        StratumMethod::Random(size) => (size, None),
+        StratumMethod::Mus(params) => {
+            let plan = mus_plan_for_rule(&amounts, rule, params, verbose)?;
+            (plan.n, Some(plan))
+        }
+    };
+    let alloc = allocate_sample(&strata, n_total, strat.allocation).context("分层样本量分配失败")?;
+    if verbose {
+        eprintln!("[分层] population='{}' n={} 分配={:?}", rule.population_name, n_total, alloc);
+    }
+
+    let mut plan_warnings = plan.as_ref().map(|p| p.warnings.iter().map(planning_warning_text).collect());
+    let mut out = Vec::with_capacity(strata.len());
+    for (h, (stratum, &n_h)) in strata.iter().zip(&alloc).enumerate() {
+        let label = stratum_label(h, stratum);
+        let mut stratum_seed = derive_seed(seed, &(h + 1).to_string());
+        if matches!(method, StratumMethod::Mus(p) if p.rng == audit_sampling::RngKind::RCompatible) {
+            stratum_seed &= i32::MAX as u64;
+        }
+        let members: Vec<Record> = stratum.indices.iter().map(|&i| population[i].clone()).collect();
+        let mut row = SummaryRow {
+            population: rule.population_name.clone(),
+            stratum: Some(label.clone()),
+            population_len: members.len(),
+            seed: Some(stratum_seed),
+            warnings: plan_warnings.take().unwrap_or_default(),
+            ..Default::default()
+        };
+        let mut records = match (&method, &plan) {
+            (StratumMethod::Mus(params), Some(plan)) if n_h > 0 => {
+                let data: Vec<Money> = stratum.indices.iter().map(|&i| amounts[i]).collect();
+                let book_value = strat.currency.amount(data.iter().map(|&m| m.max(Money::default())).sum());
+                if book_value <= 0.0 {
+                    Vec::new()
+                } else {
+                    let ids = stratum.indices.iter().map(|&i| plan.ids[i].clone()).collect();
+                    let plan_h = audit_sampling::Plan { data, ids, book_value, n: n_h, high_value_threshold: book_value / n_h as f64, ..plan.clone() };
+                    let mus = mus_extract_records(&members, &plan_h, params, stratum_seed, verbose)
+                        .with_context(|| format!("MUS 抽样失败: {}", label))?;
+                    row.high_values = Some(mus.high_values);
+                    row.sampling_interval = Some(mus.sampling_interval);
+                    row.start_point = mus.start_point;
+                    report_warnings(&format!("{} {}", rule.population_name, label), &mus.warnings);
+                    row.warnings.extend(mus.warnings);
+                    mus.records
+                }
+            }
+            (StratumMethod::Random(_), _) => perform_random_sampling_with_rules(members, n_h, stratum_seed),
+            _ => Vec::new(),
+        };
+        for r in &mut records {
+            r.set(STRATUM_COL, label.clone());
+        }
+        row.sample_len = records.len();
+        out.push((row, records));
+    }
     Ok(out)
 }
 
+/// “抽样统计”中的一行；高值项目数与抽样间隔仅 MUS 抽样填写，允许偏差数仅属性抽样填写
+#[derive(Default)]
+pub struct SummaryRow {
+    pub population: String,
+    pub stratum: Option<String>,
+    pub population_len: usize,
+    pub sample_len: usize,
+    pub seed: Option<u64>,
+    pub high_values: Option<usize>,
+    pub sampling_interval: Option<f64>,
+    pub start_point: Option<f64>,
+    pub allowable_deviations: Option<u64>,
+    /// 历次扩样的数量（MUS 扩样与评价据此重放）
+    pub extensions: Vec<usize>,
+    /// 规划与抽取警告，以“；”连接写入
+    pub warnings: Vec<String>,
+    /// MUS 评价结果（--audited）；有评价时“抽样统计”追加评价各列
+    pub evaluation: Option<MusEvaluationRow>,
+}
+
 pub struct SummaryCtx {
     pub method: String,
     pub start: String,
@@ -382,35 +1014,39 @@ pub struct SummaryCtx {
 
 pub fn write_results_to_excel(
     results: &[(String, Vec<Record>, usize)],
-    summary_rows: &[(String, usize, usize)],
+    summary_rows: &[SummaryRow],
     output: &Path,
     display_headers: &[String],
+    voucher_col: Option<&str>,
     summary_ctx: &SummaryCtx,
 ) -> Result<()> {
     let mut wb = Workbook::new();
     let mut used: std::collections::HashSet<String> = std::collections::HashSet::new();
+    // 总体名称 → 实际表名，写入“抽样统计”的“工作表”列供评价与扩样读回
+    let mut sheets: HashMap<String, String> = HashMap::new();
     for (sheet_name, rows, _pop_len) in results {
         let sname = unique_sheet_name(sheet_name, &mut used);
-        let mut ws = wb.add_worksheet().set_name(&sname)?;
-        write_sheet(&mut ws, rows, display_headers)?;
+        let ws = wb.add_worksheet().set_name(&sname)?;
+        write_sheet(ws, rows, display_headers, voucher_col)?;
+        sheets.insert(sheet_name.clone(), sname);
     }
     // Summary sheet (always add)
     let sname = unique_sheet_name("抽样统计", &mut used);
-    let mut ws = wb.add_worksheet().set_name(&sname)?;
-    write_summary(&mut ws, summary_rows, summary_ctx)?;
+    let ws = wb.add_worksheet().set_name(&sname)?;
+    write_summary(ws, summary_rows, &sheets, summary_ctx)?;
     wb.save(output).with_context(|| format!("保存 Excel 失败: {}", output.display()))?;
     Ok(())
 }
 
-fn write_sheet(ws: &mut Worksheet, rows: &[Record], display_headers: &[String]) -> Result<()> {
+fn write_sheet(ws: &mut Worksheet, rows: &[Record], display_headers: &[String], voucher_col: Option<&str>) -> Result<()> {
     // Use the selected headers order
     let headers: Vec<String> = display_headers.to_vec();
 
     // Optional: sort rows by 凭证行号（若存在该列）
     use std::cmp::Ordering;
-    let voucher_col = find_voucher_line_col(&headers).or_else(|| Some("凭证行号".to_string()));
+    let voucher_col = voucher_col.or(Some("凭证行号"));
     let mut rows_sorted: Vec<Record> = rows.to_vec();
-    if let Some(vc) = &voucher_col {
+    if let Some(vc) = voucher_col {
         fn parse_int_like(s: &str) -> Option<i64> {
             let t = s.trim().replace(",", "");
             if t.is_empty() { return None; }
@@ -420,13 +1056,13 @@ fn write_sheet(ws: &mut Worksheet, rows: &[Record], display_headers: &[String])
             None
         }
         rows_sorted.sort_by(|a, b| {
-            let av = a.values.get(vc).map(|s| s.as_str()).unwrap_or("");
-            let bv = b.values.get(vc).map(|s| s.as_str()).unwrap_or("");
-            match (parse_int_like(av), parse_int_like(bv)) {
+            let av = a.text(vc).unwrap_or_default();
+            let bv = b.text(vc).unwrap_or_default();
+            match (parse_int_like(&av), parse_int_like(&bv)) {
                 (Some(x), Some(y)) => x.cmp(&y),
                 (Some(_), None) => Ordering::Less,
                 (None, Some(_)) => Ordering::Greater,
-                (None, None) => av.cmp(bv),
+                (None, None) => av.cmp(&bv),
             }
         });
     }
@@ -436,35 +1072,572 @@ fn write_sheet(ws: &mut Worksheet, rows: &[Record], display_headers: &[String])
     // Rows
     for (i, r) in rows_sorted.iter().enumerate() {
         for (c, h) in headers.iter().enumerate() {
-            let v = r.values.get(h).cloned().unwrap_or_default();
+            let v = if h == ROW_COL { (r.row() + 1).to_string().into() } else { r.text(h).unwrap_or_default() };
             // Excel 单元格字符串上限 32767 个字符；再保证字节安全
             let mut safe = truncate_chars(&v, 32767);
-            if safe.as_bytes().len() > 32767 { safe = truncate_to_bytes(&safe, 32767); }
+            if safe.len() > 32767 { safe = truncate_to_bytes(&safe, 32767); }
             ws.write_string((i + 1) as u32, c as u16, &safe)?;
         }
     }
     Ok(())
 }
 
-fn write_summary(ws: &mut Worksheet, rows: &[(String, usize, usize)], ctx: &SummaryCtx) -> Result<()> {
-    let headers = vec![
+fn write_summary(ws: &mut Worksheet, rows: &[SummaryRow], sheets: &HashMap<String, String>, ctx: &SummaryCtx) -> Result<()> {
+    let headers = [
         "总体名称".to_string(),
+        "分层".to_string(),
         "总体条数".to_string(),
         "样本条数".to_string(),
         "方法".to_string(),
         "开始日期".to_string(),
         "结束日期".to_string(),
         "参数".to_string(),
+        "高值项目数".to_string(),
+        "抽样间隔".to_string(),
+        "起始点".to_string(),
+        "种子".to_string(),
+        "允许偏差数".to_string(),
+        "扩样".to_string(),
+        "警告".to_string(),
+        "工作表".to_string(),
     ];
+    const EVALUATION_HEADERS: [&str; 11] = [
+        "高估错报数", "高估最可能错报", "高估错报上限", "低估错报数", "低估最可能错报", "低估错报上限", "净最可能错报", "净高估上限", "净低估上限", "可容忍错报", "结论",
+    ];
+    let evaluated = rows.iter().any(|r| r.evaluation.is_some());
     for (c, h) in headers.iter().enumerate() { ws.write_string(0, c as u16, h)?; }
-    for (i, (name, pop, sam)) in rows.iter().enumerate() {
-        ws.write_string((i + 1) as u32, 0, name)?;
-        ws.write_string((i + 1) as u32, 1, &pop.to_string())?;
-        ws.write_string((i + 1) as u32, 2, &sam.to_string())?;
-        ws.write_string((i + 1) as u32, 3, &ctx.method)?;
-        ws.write_string((i + 1) as u32, 4, &ctx.start)?;
-        ws.write_string((i + 1) as u32, 5, &ctx.end)?;
-        ws.write_string((i + 1) as u32, 6, &ctx.note)?;
+    if evaluated {
+        for (c, h) in EVALUATION_HEADERS.iter().enumerate() { ws.write_string(0, (headers.len() + c) as u16, *h)?; }
+    }
+    for (i, row) in rows.iter().enumerate() {
+        let r = (i + 1) as u32;
+        if let Some(e) = &row.evaluation {
+            let c0 = headers.len() as u16;
+            ws.write_string(r, c0, e.overstatement.num_errors.to_string())?;
+            ws.write_string(r, c0 + 1, format!("{:.2}", e.overstatement.most_likely_misstatement))?;
+            ws.write_string(r, c0 + 2, format!("{:.2}", e.overstatement.upper_error_limit))?;
+            ws.write_string(r, c0 + 3, e.understatement.num_errors.to_string())?;
+            ws.write_string(r, c0 + 4, format!("{:.2}", e.understatement.most_likely_misstatement))?;
+            ws.write_string(r, c0 + 5, format!("{:.2}", e.understatement.upper_error_limit))?;
+            ws.write_string(r, c0 + 6, format!("{:.2}", e.net_most_likely_misstatement))?;
+            ws.write_string(r, c0 + 7, format!("{:.2}", e.net_overstatement_limit))?;
+            ws.write_string(r, c0 + 8, format!("{:.2}", e.net_understatement_limit))?;
+            ws.write_string(r, c0 + 9, format!("{:.2}", e.tolerable_error))?;
+            let conclusion = match e.conclusion {
+                audit_sampling::Conclusion::Accept => "可接受",
+                audit_sampling::Conclusion::Reject => "不可接受",
+                audit_sampling::Conclusion::Deferred => "见合并评价",
+            };
+            ws.write_string(r, c0 + 10, conclusion)?;
+        }
+        ws.write_string(r, 0, &row.population)?;
+        if let Some(st) = &row.stratum { ws.write_string(r, 1, st)?; }
+        ws.write_string(r, 2, row.population_len.to_string())?;
+        ws.write_string(r, 3, row.sample_len.to_string())?;
+        ws.write_string(r, 4, &ctx.method)?;
+        ws.write_string(r, 5, &ctx.start)?;
+        ws.write_string(r, 6, &ctx.end)?;
+        ws.write_string(r, 7, &ctx.note)?;
+        if let Some(h) = row.high_values { ws.write_string(r, 8, h.to_string())?; }
+        if let Some(si) = row.sampling_interval.filter(|v| v.is_finite()) { ws.write_string(r, 9, format!("{:.2}", si))?; }
+        if let Some(sp) = row.start_point { ws.write_string(r, 10, format!("{:.2}", sp))?; }
+        if let Some(seed) = row.seed { ws.write_string(r, 11, seed.to_string())?; }
+        if let Some(k) = row.allowable_deviations { ws.write_string(r, 12, k.to_string())?; }
+        if !row.extensions.is_empty() {
+            let sizes: Vec<String> = row.extensions.iter().map(|k| k.to_string()).collect();
+            ws.write_string(r, 13, sizes.join(","))?;
+        }
+        if !row.warnings.is_empty() { ws.write_string(r, 14, row.warnings.join("；"))?; }
+        if let Some(sheet) = sheets.get(&row.population) { ws.write_string(r, 15, sheet)?; }
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ColumnMapping;
+    use crate::journal::{load_journal, LoadOptions};
+    use chrono::NaiveDate;
+
+    #[test]
+    fn sampled_records_point_at_their_journal_rows() {
+        let mut csv = String::from("日期,科目编码,借方金额,贷方金额\n");
+        for i in 0..12 {
+            let (code, debit, credit) = if i % 3 == 0 { ("2202", "", "50") } else { ("1405", "100", "") };
+            let date = if i == 4 { "无" } else { "2024-03-01" };
+            csv.push_str(&format!("{date},{code},{debit},{credit}\n"));
+        }
+        let path = std::env::temp_dir().join(format!("audit-sampler-{}-population.csv", std::process::id()));
+        std::fs::write(&path, csv).unwrap();
+        let data = load_journal(&path, &LoadOptions::default());
+        std::fs::remove_file(&path).unwrap();
+        let data = data.unwrap();
+
+        let columns = Columns::resolve(&data.headers, &ColumnMapping::default()).unwrap();
+        let rule = ResolvedRule::new("存货_借方".into(), Some(vec!["1405".into()]), TransactionType::Debit, None, &columns);
+        let period = (NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(), NaiveDate::from_ymd_opt(2024, 12, 31).unwrap());
+        let (population, warnings) = build_population(&data, period, "存货", &rule, &columns, Currency::CNY);
+        assert!(warnings.is_empty());
+        assert_eq!(population.iter().map(Record::row).collect::<Vec<_>>(), [1, 2, 5, 7, 8, 10, 11]);
+
+        let sampled = perform_random_sampling_with_rules(population, 3, 7);
+        assert_eq!(sampled.len(), 3);
+        for r in &sampled {
+            let original = data.rows().nth(r.row()).unwrap();
+            for h in &data.headers {
+                assert!(std::ptr::eq(r.cell(h).unwrap(), original.cell(h).unwrap()));
+            }
+        }
+    }
+
+    /// 借方、贷方交替的序时账：金额各不相同，另有 3 笔 20 000 的借方（高值项目，内容除记录号外相同）
+    fn mus_journal_csv() -> String {
+        let mut csv = String::from("日期,科目编码,摘要,借方金额,贷方金额\n");
+        for i in 0..120 {
+            let amount = format!("{}.{:02}", 100 + (i * 37 % 97) * 13, i % 100);
+            let line = match i {
+                0 | 50 | 100 => "2024-06-30,1405,,20000.00,".to_string(),
+                _ if i % 2 == 0 => format!("2024-03-{:02},1405,第{i}笔,{amount},", 1 + i % 28),
+                _ => format!("2024-03-{:02},1405,第{i}笔,,{amount}", 1 + i % 28),
+            };
+            csv.push_str(&line);
+            csv.push('\n');
+        }
+        csv
+    }
+
+    fn load_csv_text(name: &str, csv: &str) -> JournalData {
+        let path = std::env::temp_dir().join(format!("audit-sampler-{}-{name}.csv", std::process::id()));
+        std::fs::write(&path, csv).unwrap();
+        let data = load_journal(&path, &LoadOptions::default());
+        std::fs::remove_file(&path).unwrap();
+        data.unwrap()
+    }
+
+    fn period() -> (NaiveDate, NaiveDate) {
+        (NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(), NaiveDate::from_ymd_opt(2024, 12, 31).unwrap())
+    }
+
+    fn rule(data: &JournalData, transaction_type: TransactionType) -> ResolvedRule {
+        let columns = Columns::resolve(&data.headers, &ColumnMapping::default()).unwrap();
+        let name = match transaction_type {
+            TransactionType::Debit => "存货_借方",
+            TransactionType::Credit => "存货_贷方",
+        };
+        ResolvedRule::new(name.into(), None, transaction_type, None, &columns)
+    }
+
+    fn population<'a>(data: &'a JournalData, rule: &ResolvedRule) -> Vec<Record<'a>> {
+        let columns = Columns::resolve(&data.headers, &ColumnMapping::default()).unwrap();
+        build_population(data, period(), "存货", rule, &columns, Currency::CNY).0
+    }
+
+    fn mus_params(seed: u64) -> MusParams {
+        MusParams {
+            tolerable_error: 10_000.0,
+            expected_error: 0.0,
+            confidence: 0.95,
+            obey_n_as_min: false,
+            start_point: None,
+            seed,
+            rng: Default::default(),
+            mode: Default::default(),
+            selection: audit_sampling::PpsScheme::Systematic,
+            model: Default::default(),
+            currency: Currency::CNY,
+        }
+    }
+
+    /// 读回的样本表、各总体种子与历次扩样数量（与 CLI 的 --extend / --audited 相同）
+    type SampleWorkbook = (Vec<(String, JournalData)>, HashMap<String, u64>, HashMap<String, Vec<usize>>);
+
+    /// 按 CLI 的方式写出样本工作簿（各总体一张样本表 + “抽样统计”）并读回
+    fn round_trip(name: &str, sheets: &[(String, Vec<Record>, usize)], rows: &[SummaryRow], headers: &[String]) -> SampleWorkbook {
+        let path = std::env::temp_dir().join(format!("audit-sampler-{}-{name}.xlsx", std::process::id()));
+        let ctx = SummaryCtx { method: "mus".into(), start: "2024-01-01".into(), end: "2024-12-31".into(), note: String::new() };
+        write_results_to_excel(sheets, rows, &path, headers, None, &ctx).unwrap();
+        let loaded = (
+            crate::journal::load_sample_workbook(&path).unwrap(),
+            crate::journal::load_summary_seeds(&path).unwrap(),
+            crate::journal::load_summary_extensions(&path).unwrap(),
+        );
+        std::fs::remove_file(&path).unwrap();
+        loaded
+    }
+
+    fn summary_row(population: &str, population_len: usize, seed: u64, extensions: &[usize]) -> SummaryRow {
+        SummaryRow { population: population.into(), population_len, seed: Some(seed), extensions: extensions.to_vec(), ..Default::default() }
+    }
+
+    /// 各批次抽中的序时账记录号
+    fn batch_rows(records: &[Record]) -> HashMap<u32, HashSet<usize>> {
+        let mut out: HashMap<u32, HashSet<usize>> = HashMap::new();
+        for r in records {
+            let batch = r.text(BATCH_COL).map_or(0, |b| b.parse().unwrap());
+            out.entry(batch).or_default().insert(r.row());
+        }
+        out
+    }
+
+    fn extraction_items(x: &audit_sampling::Extraction) -> HashSet<usize> {
+        x.high_values.iter().map(|h| h.index).chain(x.sample.iter().map(|s| s.index)).collect()
+    }
+
+    #[test]
+    fn mus_extension_keeps_batch_0_and_appends_the_items_mus_extend_picks() {
+        let data = load_csv_text("extend", &mus_journal_csv());
+        let rule = rule(&data, TransactionType::Debit);
+        let population = population(&data, &rule);
+        let seed = derive_seed(7, &rule.population_name);
+        let plan = plan_mus_with_rules(&population, &rule, &mus_params(seed), false).unwrap();
+        let mus = perform_mus_sampling_with_rules(population.clone(), &rule, &plan, &mus_params(seed), false).unwrap();
+
+        // 库直接抽取并两次扩样，作为各批次应抽中的项目
+        let opts = audit_sampling::ExtractionOptions { seed: Some(seed), ..Default::default() };
+        let mut extract = audit_sampling::mus_extraction(&plan, opts).unwrap();
+        let mut expected: HashMap<u32, HashSet<usize>> = HashMap::new();
+        let mut taken = HashSet::new();
+        for (batch, size) in [(0, 0), (1, 5), (2, 3)] {
+            if size > 0 {
+                extract = audit_sampling::mus_extend(&extract, audit_sampling::ExtensionSize::AdditionalN(size)).unwrap();
+            }
+            let rows = extraction_items(&extract).into_iter().filter(|&i| taken.insert(i)).map(|i| population[i].row()).collect();
+            expected.insert(batch, rows);
+        }
+        assert!(!expected[&1].is_empty() && !expected[&2].is_empty());
+
+        let mut headers = data.headers.clone();
+        headers.extend([ROW_COL.to_string(), BATCH_COL.to_string()]);
+        let name = rule.population_name.clone();
+        let mut records = mus.records;
+        let mut extensions = Vec::new();
+        for (batch, size) in [(1, 5), (2, 3)] {
+            let sheets = [(name.clone(), records, population.len())];
+            let (samples, seeds, history) = round_trip("extend", &sheets, &[summary_row(&name, population.len(), seed, &extensions)], &headers);
+            assert_eq!(history.get(&name).cloned().unwrap_or_default(), extensions);
+            let extended = extend_mus_sample_with_rules(population.clone(), &rule, &mus_params(seeds[&name]), &samples[0].1, &extensions, size, false).unwrap();
+            records = extended.records;
+            extensions.push(size);
+            let got = batch_rows(&records);
+            for b in 0..=batch {
+                assert_eq!(got.get(&b).cloned().unwrap_or_default(), expected[&b], "batch {b}");
+            }
+        }
+    }
+
+    #[test]
+    fn replay_rejects_a_changed_journal_or_a_wrong_seed() {
+        let data = load_csv_text("replay", &mus_journal_csv());
+        let rule = rule(&data, TransactionType::Debit);
+        let population = population(&data, &rule);
+        let seed = derive_seed(7, &rule.population_name);
+        let plan = plan_mus_with_rules(&population, &rule, &mus_params(seed), false).unwrap();
+        let mus = perform_mus_sampling_with_rules(population.clone(), &rule, &plan, &mus_params(seed), false).unwrap();
+        let mut headers = data.headers.clone();
+        headers.push(ROW_COL.to_string());
+        let sheets = [(rule.population_name.clone(), mus.records, population.len())];
+        let (samples, _, _) = round_trip("replay", &sheets, &[summary_row(&rule.population_name, population.len(), seed, &[])], &headers);
+        let sample = &samples[0].1;
+
+        let replay_error = |population: Vec<Record>, seed: u64| {
+            let err = extend_mus_sample_with_rules(population, &rule, &mus_params(seed), sample, &[], 5, false).err().expect("replay should fail");
+            format!("{err:#}")
+        };
+        // 另一个主种子派生的种子
+        assert!(replay_error(population.clone(), derive_seed(8, &rule.population_name)).contains("重放的抽样结果与样本表不一致"));
+
+        // 同一记录号的金额变化后，抽样间隔与命中的项目随之变化
+        let changed = load_csv_text("replay-changed", &mus_journal_csv().replacen("20000.00", "26000.00", 1));
+        assert!(replay_error(self::population(&changed, &rule), seed).contains("重放的抽样结果与样本表不一致"));
+    }
+
+    #[test]
+    fn duplicate_rows_without_row_numbers_fail_in_strict_mode() {
+        let data = load_csv_text("duplicates", &mus_journal_csv());
+        let rule = rule(&data, TransactionType::Debit);
+        let population = population(&data, &rule);
+        let seed = derive_seed(7, &rule.population_name);
+        let plan = plan_mus_with_rules(&population, &rule, &mus_params(seed), false).unwrap();
+        let mus = perform_mus_sampling_with_rules(population.clone(), &rule, &plan, &mus_params(seed), false).unwrap();
+        // 不写记录号与摘要：3 笔高值项目的内容相同，只能按文本依次对应
+        let headers: Vec<String> = data.headers.iter().filter(|h| *h != "摘要").cloned().collect();
+        let sheets = [(rule.population_name.clone(), mus.records, population.len())];
+        let (samples, _, _) = round_trip("duplicates", &sheets, &[summary_row(&rule.population_name, population.len(), seed, &[])], &headers);
+        let sample = &samples[0].1;
+
+        let err = extend_mus_sample_with_rules(population.clone(), &rule, &mus_params(seed), sample, &[], 5, false).err().expect("strict");
+        assert!(format!("{err:#}").contains(&format!("请保留“{ROW_COL}”列")), "{err:#}");
+        // 随机扩样不需要区分各条记录
+        let extended = extend_sample_with_rules(population, Some(sample), 5, seed).unwrap();
+        assert_eq!(batch_rows(&extended)[&1].len(), 5);
+    }
+
+    #[test]
+    fn audited_workbook_is_evaluated_per_population_and_combined() {
+        use approx::assert_abs_diff_eq;
+        use audit_sampling::{mus_combine, mus_evaluation, mus_extend, mus_extraction, Conclusion, EvaluationOptions, ExtensionSize, ExtractionOptions};
+        const AUDIT: &str = "审定金额";
+        let data = load_csv_text("audited", &mus_journal_csv());
+        let mut headers = data.headers.clone();
+        headers.extend([ROW_COL.to_string(), BATCH_COL.to_string(), AUDIT.to_string()]);
+
+        // 借方扩样一次并在扩样批次中发现高估，贷方只有原样本并发现低估
+        let cases = [(rule(&data, TransactionType::Debit), vec![4], 0.5), (rule(&data, TransactionType::Credit), vec![], 1.5)];
+        let mut sheets = Vec::new();
+        let mut rows = Vec::new();
+        let mut expected = Vec::new();
+        for (rule, extensions, factor) in &cases {
+            let name = &rule.population_name;
+            let population = population(&data, rule);
+            let seed = derive_seed(7, name);
+            let params = mus_params(seed);
+            let plan = plan_mus_with_rules(&population, rule, &params, false).unwrap();
+            let mut records = perform_mus_sampling_with_rules(population.clone(), rule, &plan, &params, false).unwrap().records;
+            let plan = audit_sampling::Plan { combined: true, ..plan };
+            let mut extract = mus_extraction(&plan, ExtractionOptions { seed: Some(seed), combined: true, ..Default::default() }).unwrap();
+            let batch_0 = extract.sample.len();
+            for (k, &size) in extensions.iter().enumerate() {
+                let sheets = [(name.clone(), records, population.len())];
+                let (samples, _, _) = round_trip("audited-extend", &sheets, &[summary_row(name, population.len(), seed, &extensions[..k])], &headers);
+                records = extend_mus_sample_with_rules(population.clone(), rule, &params, &samples[0].1, &extensions[..k], size, false).unwrap().records;
+                extract = mus_extend(&extract, ExtensionSize::AdditionalN(size)).unwrap();
+            }
+
+            // 最后一个抽样项目有错报（借方即扩样批次中的项目），其余审定金额留空（与账面一致）
+            let misstated = extract.sample.last().unwrap().index;
+            let book = |i: usize| plan.currency.amount(plan.data[i]);
+            let audited = |i: usize| if i == misstated { (book(i) * factor * 100.0).round() / 100.0 } else { book(i) };
+            for r in &mut records {
+                if r.row() == population[misstated].row() {
+                    r.set(AUDIT, format!("{:.2}", audited(misstated)));
+                }
+            }
+            // 样本行的顺序与抽取顺序无关
+            records.reverse();
+
+            let opts = EvaluationOptions { high_value_audit_values: extract.high_values.iter().map(|h| audited(h.index)).collect(), ..EvaluationOptions::for_extraction(&extract) };
+            let audited_values: Vec<f64> = extract.sample.iter().map(|s| audited(s.index)).collect();
+            let evaluation = mus_evaluation(&extract, &audited_values, opts).unwrap();
+            assert!(extensions.is_empty() || evaluation.extraction.sample.len() > batch_0);
+            rows.push(summary_row(name, population.len(), seed, extensions));
+            sheets.push((name.clone(), records, population.len()));
+            let value = audited(misstated);
+            expected.push((population, plan, misstated, value, evaluation));
+        }
+
+        let (samples, seeds, history) = round_trip("audited", &sheets, &rows, &headers);
+        let mut summary_rows = Vec::new();
+        let mut evaluations = Vec::new();
+        for ((rule, extensions, _), (population, plan, misstated, audited, want)) in cases.iter().zip(&expected) {
+            let name = &rule.population_name;
+            let sheet = &samples.iter().find(|(sheet, _)| *sheet == sanitize_sheet_name(name)).unwrap().1;
+            let history = history.get(name).cloned().unwrap_or_default();
+            assert_eq!(&history, extensions);
+            let got = evaluate_mus_with_rules(population, plan, &mus_params(seeds[name]), sheet, &history, AUDIT).unwrap();
+
+            // 扩样批次参与评价；审定金额按序时账记录号对应到抽中的项目
+            assert_eq!(got.extraction.sample.len(), want.extraction.sample.len());
+            let j = got.extraction.sample.iter().position(|s| s.index == *misstated).unwrap();
+            assert_abs_diff_eq!(got.audited_values[j], *audited, epsilon = 1e-9);
+            assert_eq!(got.taintings.iter().filter(|t| **t != 0.0).count(), 1);
+            assert_abs_diff_eq!(got.overstatement.upper_error_limit, want.overstatement.upper_error_limit, epsilon = 1e-6);
+            assert_abs_diff_eq!(got.understatement.upper_error_limit, want.understatement.upper_error_limit, epsilon = 1e-6);
+            assert_eq!(got.conclusion, Conclusion::Deferred);
+            summary_rows.push(evaluation_summary_row(name, population.len(), &history, &got));
+            evaluations.push(got);
+        }
+        assert!(evaluations[0].overstatement.num_errors == 1 && evaluations[1].understatement.num_errors == 1);
+
+        let combined = mus_combine(&evaluations).unwrap();
+        let want = mus_combine(&expected.into_iter().map(|e| e.4).collect::<Vec<_>>()).unwrap();
+        assert_abs_diff_eq!(combined.overstatement.upper_error_limit, want.overstatement.upper_error_limit, epsilon = 1e-6);
+        assert_abs_diff_eq!(combined.understatement.upper_error_limit, want.understatement.upper_error_limit, epsilon = 1e-6);
+        assert_eq!(combined.conclusion, want.conclusion);
+        summary_rows.push(combined_summary_row("存货", &summary_rows, &combined));
+
+        // “抽样统计”：各总体的结论见合并评价，合并行写出合并的上限与结论
+        let path = std::env::temp_dir().join(format!("audit-sampler-{}-evaluated.xlsx", std::process::id()));
+        let ctx = SummaryCtx { method: "mus".into(), start: "2024-01-01".into(), end: "2024-12-31".into(), note: String::new() };
+        write_results_to_excel(&sheets, &summary_rows, &path, &headers, None, &ctx).unwrap();
+        let mut wb = calamine::open_workbook_auto(&path).unwrap();
+        let range = calamine::Reader::worksheet_range(&mut wb, "抽样统计").unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let table: Vec<Vec<String>> = range.rows().map(|r| r.iter().map(ToString::to_string).collect()).collect();
+        let col = |h: &str| table[0].iter().position(|c| c == h).unwrap();
+        let conclusions: Vec<&str> = table[1..].iter().map(|r| r[col("结论")].as_str()).collect();
+        let accepted = if combined.conclusion == Conclusion::Accept { "可接受" } else { "不可接受" };
+        assert_eq!(conclusions, ["见合并评价", "见合并评价", accepted]);
+        let row = &table[3];
+        assert_eq!(row[col("总体名称")], "存货（合并）");
+        assert_eq!(row[col("总体条数")], (sheets[0].2 + sheets[1].2).to_string());
+        assert_eq!(row[col("高估错报上限")], format!("{:.2}", combined.overstatement.upper_error_limit));
+        assert_eq!(row[col("低估错报上限")], format!("{:.2}", combined.understatement.upper_error_limit));
+    }
+
+    #[test]
+    fn derived_seeds_depend_only_on_master_and_population_name() {
+        // 按 FNV-1a 与 SplitMix64 的定义另行算得；改动派生方法会让已有样本工作簿无法重放
+        assert_eq!(derive_seed(42, "存货_借方"), 2_609_768_411_503_330_195);
+        let names = ["存货_借方", "存货_贷方", "应付账款_借方", ""];
+        let seeds: HashSet<u64> = names.iter().flat_map(|n| [0, 1, 42].map(|m| derive_seed(m, n))).collect();
+        assert_eq!(seeds.len(), names.len() * 3);
+    }
+
+    #[test]
+    fn a_population_sample_does_not_depend_on_the_other_rules() {
+        let data = load_csv_text("rules", &mus_journal_csv());
+        let (debit, credit) = (rule(&data, TransactionType::Debit), rule(&data, TransactionType::Credit));
+        // 与 CLI 相同：各规则依次建立总体，以主种子与总体名称派生种子后抽样
+        let sample = |rules: &[&ResolvedRule]| -> HashMap<String, (Vec<usize>, Vec<usize>)> {
+            rules
+                .iter()
+                .map(|rule| {
+                    let population = population(&data, rule);
+                    let params = mus_params(derive_seed(7, &rule.population_name));
+                    let plan = plan_mus_with_rules(&population, rule, &params, false).unwrap();
+                    let mus = perform_mus_sampling_with_rules(population.clone(), rule, &plan, &params, false).unwrap();
+                    let random = perform_random_sampling_with_rules(population, 6, params.seed);
+                    let rows = |records: &[Record]| records.iter().map(Record::row).collect();
+                    (rule.population_name.clone(), (rows(&mus.records), rows(&random)))
+                })
+                .collect()
+        };
+        let alone = sample(&[&debit]);
+        let together = sample(&[&credit, &debit]);
+        assert_eq!(alone[&debit.population_name], together[&debit.population_name]);
+        assert_ne!(together[&debit.population_name].0, together[&credit.population_name].0);
+    }
+
+    #[test]
+    fn stratum_rows_carry_the_plan_warnings_once() {
+        use audit_sampling::{Allocation, ExtractionMode, PlanningWarning, StratumBoundaries};
+        let data = load_csv_text("strata", &mus_journal_csv());
+        let rule = rule(&data, TransactionType::Debit);
+        let strat = StratifyParams { boundaries: StratumBoundaries::Fixed(vec![400.0, 15_000.0]), allocation: Allocation::Value, currency: Currency::CNY };
+        // 小的可容忍错报使整个总体的规划给出警告；单元抽样时第 2 层有项目被重复命中
+        let params = MusParams { tolerable_error: 2_500.0, mode: ExtractionMode::Cell, ..mus_params(7) };
+        let strata = perform_stratified_sampling_with_rules(population(&data, &rule), &rule, &strat, StratumMethod::Mus(&params), 7, false).unwrap();
+        let warnings: Vec<Vec<String>> = strata.into_iter().map(|(row, _)| row.warnings).collect();
+        let planning = planning_warning_text(&PlanningWarning::ImpracticallyLargeSample);
+        let extraction = extraction_warning_text(&ExtractionWarning::RepeatedHits(1));
+        assert_eq!(warnings, [vec![planning], vec![extraction], vec![]]);
+    }
+
+    fn attribute_params(model: audit_sampling::AttributeModel) -> AttributeParams {
+        AttributeParams { tolerable_deviation_rate: 0.1, expected_deviation_rate: 0.0, confidence: 0.95, model }
+    }
+
+    #[test]
+    fn attribute_sampling_draws_the_planned_number_of_records() {
+        use audit_sampling::{attribute_planning, AttributeModel, AttributePlanningOptions};
+        let data = load_csv_text("attribute", &mus_journal_csv());
+        let rule = rule(&data, TransactionType::Debit);
+        let population = population(&data, &rule);
+        let rows: HashSet<usize> = population.iter().map(Record::row).collect();
+        for model in [AttributeModel::Binomial, AttributeModel::Poisson, AttributeModel::Hypergeometric] {
+            let params = attribute_params(model);
+            let (records, plan) = perform_attribute_sampling_with_rules(population.clone(), &rule, &params, 7, false).unwrap();
+            let want = attribute_planning(AttributePlanningOptions {
+                model,
+                confidence_level: 0.95,
+                tolerable_deviation_rate: 0.1,
+                expected_deviation_rate: 0.0,
+                population_size: Some(population.len()),
+            })
+            .unwrap();
+            assert_eq!((plan.n, plan.allowable_deviations), (want.n, want.allowable_deviations));
+            assert!(plan.n > 0 && plan.n < population.len());
+            // n 条不同的总体记录，同一种子得到同一样本
+            let sampled: HashSet<usize> = records.iter().map(Record::row).collect();
+            assert_eq!(sampled.len(), plan.n);
+            assert!(sampled.is_subset(&rows));
+            let (again, _) = perform_attribute_sampling_with_rules(population.clone(), &rule, &params, 7, false).unwrap();
+            assert_eq!(again.iter().map(Record::row).collect::<Vec<_>>(), records.iter().map(Record::row).collect::<Vec<_>>());
+        }
+
+        // 样本量不低于总体条数时全部抽取
+        let params = AttributeParams { tolerable_deviation_rate: 0.01, ..attribute_params(AttributeModel::Binomial) };
+        let (records, plan) = perform_attribute_sampling_with_rules(population.clone(), &rule, &params, 7, false).unwrap();
+        assert!(plan.n >= population.len());
+        assert_eq!(records.len(), population.len());
+    }
+
+    #[test]
+    fn deviation_flags_accept_the_documented_spellings() {
+        for flag in ["是", "有", "Y", "y", "Yes", "TRUE", "1", "x", "√", "✓", "偏差", "异常", " 是 "] {
+            assert_eq!(parse_deviation_flag(flag), Some(true), "{flag}");
+        }
+        for flag in ["", "  ", "否", "无", "N", "no", "False", "0", "正常"] {
+            assert_eq!(parse_deviation_flag(flag), Some(false), "{flag}");
+        }
+        for flag in ["2", "ok", "是的", "待定", "-"] {
+            assert_eq!(parse_deviation_flag(flag), None, "{flag}");
+        }
+    }
+
+    #[test]
+    fn colliding_sheet_names_are_recorded_in_the_summary() {
+        let data = load_csv_text("collide", &mus_journal_csv());
+        let population = population(&data, &rule(&data, TransactionType::Debit));
+        // 两个总体名称去掉非法字符后相同，第二张样本表写为“存货_借方 (2)”
+        let names = ["存货_借方".to_string(), "存货:_借方".to_string()];
+        let sheets: Vec<(String, Vec<Record>, usize)> = names.iter().zip([3, 5]).map(|(n, k)| (n.clone(), population[..k].to_vec(), 10 * k)).collect();
+        let summary: Vec<SummaryRow> = sheets.iter().map(|(n, r, len)| SummaryRow { population: n.clone(), population_len: *len, sample_len: r.len(), ..Default::default() }).collect();
+        let path = std::env::temp_dir().join(format!("audit-sampler-{}-collide.xlsx", std::process::id()));
+        let ctx = SummaryCtx { method: "attribute".into(), start: "2024-01-01".into(), end: "2024-12-31".into(), note: String::new() };
+        write_results_to_excel(&sheets, &summary, &path, &data.headers, None, &ctx).unwrap();
+        let samples = crate::journal::load_sample_workbook(&path).unwrap();
+        let sizes = crate::journal::load_summary_population_sizes(&path).unwrap();
+        let sheet_names = crate::journal::load_summary_sheet_names(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(samples.iter().map(|(s, _)| s.as_str()).collect::<Vec<_>>(), ["存货_借方", "存货_借方 (2)"]);
+        for (sheet, sample) in &samples {
+            let (_, &len) = sizes.iter().find(|(name, _)| sample_sheet_name(&sheet_names, name) == *sheet).expect("population of sheet");
+            assert_eq!(len, 10 * sample.rows().count());
+        }
+    }
+
+    #[test]
+    fn deviations_are_read_from_the_returned_sample_workbook() {
+        use audit_sampling::{attribute_evaluation, AttributeEvaluationOptions, AttributeModel};
+        const DEVIATION: &str = "偏差";
+        let data = load_csv_text("deviations", &mus_journal_csv());
+        let rule = rule(&data, TransactionType::Debit);
+        let population = population(&data, &rule);
+        let (mut records, plan) = perform_attribute_sampling_with_rules(population.clone(), &rule, &attribute_params(AttributeModel::Hypergeometric), 7, false).unwrap();
+        // 每 7 条中 3 条偏差（不同写法），其余为无偏差或留空
+        for (r, flag) in records.iter_mut().zip(["是", "否", "Y", "", "N", "√", "0"].into_iter().cycle()) {
+            r.set(DEVIATION, flag.to_string());
+        }
+        let deviations = records.iter().filter(|r| parse_deviation_flag(&r.text(DEVIATION).unwrap()) == Some(true)).count() as u64;
+        let mut headers = data.headers.clone();
+        headers.extend([ROW_COL.to_string(), DEVIATION.to_string()]);
+        let name = rule.population_name.clone();
+        let summary = [SummaryRow { population: name.clone(), population_len: population.len(), sample_len: records.len(), ..Default::default() }];
+
+        let evaluate = |records: Vec<Record>, column: &str| {
+            let path = std::env::temp_dir().join(format!("audit-sampler-{}-deviations.xlsx", std::process::id()));
+            let ctx = SummaryCtx { method: "attribute".into(), start: "2024-01-01".into(), end: "2024-12-31".into(), note: String::new() };
+            write_results_to_excel(&[(name.clone(), records, population.len())], &summary, &path, &headers, None, &ctx).unwrap();
+            let samples = crate::journal::load_sample_workbook(&path).unwrap();
+            let sizes = crate::journal::load_summary_population_sizes(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            let opts = AttributeEvaluationOptions { model: AttributeModel::Hypergeometric, confidence_level: 0.95, tolerable_deviation_rate: 0.1, population_size: sizes.get(&name).copied() };
+            evaluate_attribute_sample(&samples[0].1, column, opts).map_err(|e| format!("{e:#}"))
+        };
+
+        let eval = evaluate(records.clone(), DEVIATION).unwrap();
+        assert_eq!((eval.n, eval.deviations), (plan.n, deviations));
+        let opts = AttributeEvaluationOptions { model: AttributeModel::Hypergeometric, confidence_level: 0.95, tolerable_deviation_rate: 0.1, population_size: Some(population.len()) };
+        let want = attribute_evaluation(plan.n, deviations, opts).unwrap();
+        assert_eq!(eval.upper_deviation_limit, want.upper_deviation_limit);
+        assert_eq!(eval.conclusion, want.conclusion);
+
+        assert!(evaluate(records.clone(), "控制偏差").unwrap_err().contains("样本表缺少偏差标记列“控制偏差”"));
+        records[1].set(DEVIATION, "待定".to_string());
+        let err = evaluate(records, DEVIATION).unwrap_err();
+        assert!(err.contains("第 3 行的偏差标记“待定”无法识别"), "{err}");
+    }
+}
+
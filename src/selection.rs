@@ -0,0 +1,273 @@
+//! Probability-proportional-to-size selection schemes over item book values.
+//!
+//! Besides the systematic monetary-unit selection used by `mus_extraction`, items can be
+//! drawn by random PPS with replacement, sieve sampling (Hoogduin's modified sieve or a
+//! stratified Poisson sieve) or Sampford's PPS without replacement. Every scheme reports
+//! the first-order inclusion probability of each item (for the modified sieve, the target
+//! probability it approximates). Non-positive values are never selected. See Design.md for
+//! the formulas.
+
+use crate::MusError;
+use rand::distr::weighted::WeightedIndex;
+use rand::distr::Distribution;
+use rand::seq::SliceRandom;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Sampford rejection rounds tried before switching to Brewer's method.
+const SAMPFORD_MAX_ATTEMPTS: usize = 1_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PpsScheme {
+    /// Systematic monetary-unit selection. It has a single implementation, `mus_extraction`
+    /// (exact interval over minor units, high values, R-compatible start); `pps_select`
+    /// rejects it.
+    #[default]
+    Systematic,
+    /// `n` independent uniform monetary-unit draws; items may be hit more than once.
+    RandomWithReplacement,
+    /// Modified sieve sampling (Hoogduin, Hall & Tsay, 2010): every item draws a uniform
+    /// `r_i` and the `n` items with the largest `x_i / r_i` are taken, after the items with
+    /// `π_i = 1`. The size is exactly `n`. The reported `π_i` are the target fixed-size PPS
+    /// probabilities `n·x_i / T`, not the scheme's own: the ranking attains them only
+    /// approximately (closely when `n ≪ N`).
+    ModifiedSieve,
+    /// Sieve (Poisson) sampling with stratified random numbers: the `N` uniforms are drawn
+    /// one per interval `[k/N, (k+1)/N)` and randomly assigned to items; item `i` is taken
+    /// when its number is below `π_i`. `π_i` is exact and the size varies around `n`.
+    StratifiedSieve,
+    /// Sampford's rejective method: exactly `n` distinct items with `π_i ∝ x_i`. When
+    /// rejection keeps failing (large `n / N`), Brewer's draw-by-draw method is used instead;
+    /// it has the same inclusion probabilities.
+    Sampford,
+}
+
+#[derive(Debug, Clone)]
+pub struct PpsSelection {
+    pub scheme: PpsScheme,
+    /// Selected item indices in draw order; repeats occur only for `RandomWithReplacement`.
+    pub selected: Vec<usize>,
+    /// First-order inclusion probability of every item (0 for non-positive values); the
+    /// target probability for `ModifiedSieve`, which attains it only approximately.
+    pub inclusion_probabilities: Vec<f64>,
+}
+
+impl PpsSelection {
+    /// Selected indices without repeats, in first-draw order.
+    pub fn distinct(&self) -> Vec<usize> {
+        let mut seen = vec![false; self.inclusion_probabilities.len()];
+        self.selected.iter().copied().filter(|&i| !std::mem::replace(&mut seen[i], true)).collect()
+    }
+}
+
+/// Inclusion probabilities `π_i = n·x_i / T` for a fixed-size PPS design; items with
+/// `π_i ≥ 1` are taken with certainty and the remaining size is spread over the rest.
+fn pps_inclusion_probabilities(values: &[f64], n: usize) -> Vec<f64> {
+    let mut pi = vec![0.0; values.len()];
+    let mut open: Vec<usize> = (0..values.len()).filter(|&i| values[i] > 0.0).collect();
+    let mut left = n.min(open.len());
+    loop {
+        let total: f64 = open.iter().map(|&i| values[i]).sum();
+        let (certain, rest): (Vec<usize>, Vec<usize>) = open.iter().partition(|&&i| left as f64 * values[i] >= total);
+        if certain.is_empty() || left == 0 {
+            for &i in &open {
+                pi[i] = left as f64 * values[i] / total;
+            }
+            return pi;
+        }
+        for &i in &certain {
+            pi[i] = 1.0;
+        }
+        left -= certain.len().min(left);
+        open = rest;
+        if open.is_empty() {
+            return pi;
+        }
+    }
+}
+
+fn weighted(weights: &[f64]) -> Result<WeightedIndex<f64>, MusError> {
+    WeightedIndex::new(weights).map_err(|e| MusError::Calculation(format!("weights: {e}")))
+}
+
+/// Brewer's draw-by-draw selection of `n` distinct items among `open` (`Σ pi = n`).
+fn brewer(rng: &mut StdRng, open: &[usize], pi: &[f64], n: usize) -> Result<Vec<usize>, MusError> {
+    let mut taken = vec![false; open.len()];
+    let mut draw = Vec::with_capacity(n);
+    let mut used = 0.0;
+    for step in 0..n {
+        let left = (n - step) as f64;
+        let rest = n as f64 - used;
+        let weights: Vec<f64> = open
+            .iter()
+            .zip(&taken)
+            .map(|(&i, &t)| if t { 0.0 } else { (pi[i] * (rest - pi[i]) / (rest - pi[i] * left)).max(0.0) })
+            .collect();
+        let k = weighted(&weights)?.sample(rng);
+        taken[k] = true;
+        used += pi[open[k]];
+        draw.push(open[k]);
+    }
+    Ok(draw)
+}
+
+/// Sampford draw of `n` distinct items among `open` with inclusion probabilities `pi`.
+fn sampford(rng: &mut StdRng, open: &[usize], pi: &[f64], n: usize) -> Result<Vec<usize>, MusError> {
+    if n == 0 {
+        return Ok(Vec::new());
+    }
+    let first = weighted(&open.iter().map(|&i| pi[i]).collect::<Vec<_>>())?;
+    let rest = weighted(&open.iter().map(|&i| pi[i] / (1.0 - pi[i])).collect::<Vec<_>>())?;
+    for _ in 0..SAMPFORD_MAX_ATTEMPTS {
+        let mut taken = vec![false; open.len()];
+        let mut draw = Vec::with_capacity(n);
+        let k = first.sample(rng);
+        taken[k] = true;
+        draw.push(open[k]);
+        while draw.len() < n {
+            let k = rest.sample(rng);
+            if std::mem::replace(&mut taken[k], true) {
+                break;
+            }
+            draw.push(open[k]);
+        }
+        if draw.len() == n {
+            return Ok(draw);
+        }
+    }
+    brewer(rng, open, pi, n)
+}
+
+/// Select `n` items (or `n` monetary-unit draws) from `values` by `scheme`. Systematic
+/// selection goes through `mus_extraction` and is an `InvalidInput` error here.
+pub fn pps_select(values: &[f64], n: usize, scheme: PpsScheme, seed: u64) -> Result<PpsSelection, MusError> {
+    if values.iter().any(|v| !v.is_finite()) {
+        return Err(MusError::InvalidInput("book values must be finite".into()));
+    }
+    let total: f64 = values.iter().filter(|v| **v > 0.0).sum();
+    if total <= 0.0 {
+        return Err(MusError::InvalidInput("population must contain positive book values".into()));
+    }
+    if n == 0 {
+        return Err(MusError::InvalidInput("sample size must be > 0".into()));
+    }
+    if scheme == PpsScheme::Systematic {
+        return Err(MusError::InvalidInput("systematic selection is done by mus_extraction".into()));
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
+    let (selected, inclusion_probabilities) = match scheme {
+        PpsScheme::Systematic => unreachable!("rejected above"),
+        PpsScheme::RandomWithReplacement => {
+            let dist = weighted(&values.iter().map(|v| v.max(0.0)).collect::<Vec<_>>())?;
+            let selected = (0..n).map(|_| dist.sample(&mut rng)).collect();
+            let pi = values.iter().map(|&v| if v > 0.0 { 1.0 - (1.0 - v / total).powi(n as i32) } else { 0.0 }).collect();
+            (selected, pi)
+        }
+        PpsScheme::ModifiedSieve => {
+            let pi = pps_inclusion_probabilities(values, n);
+            let mut selected: Vec<usize> = (0..values.len()).filter(|&i| pi[i] >= 1.0).collect();
+            let left = n.min(pi.iter().filter(|&&p| p > 0.0).count()) - selected.len();
+            // `1 - U` lies in (0, 1], so every ratio is finite
+            let mut ranked: Vec<(f64, usize)> = (0..values.len())
+                .filter(|&i| pi[i] > 0.0 && pi[i] < 1.0)
+                .map(|i| (values[i] / (1.0 - rng.random::<f64>()), i))
+                .collect();
+            ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+            selected.extend(ranked.iter().take(left).map(|&(_, i)| i));
+            (selected, pi)
+        }
+        PpsScheme::StratifiedSieve => {
+            let pi = pps_inclusion_probabilities(values, n);
+            // One uniform per item, stratified over `[0,1)` and randomly assigned, so the
+            // realised size stays close to `n` while each number is still marginally uniform.
+            let m = values.len() as f64;
+            let mut r: Vec<f64> = (0..values.len()).map(|k| (k as f64 + rng.random::<f64>()) / m).collect();
+            r.shuffle(&mut rng);
+            let selected = (0..values.len()).filter(|&i| r[i] < pi[i]).collect();
+            (selected, pi)
+        }
+        PpsScheme::Sampford => {
+            let pi = pps_inclusion_probabilities(values, n);
+            let mut selected: Vec<usize> = (0..values.len()).filter(|&i| pi[i] >= 1.0).collect();
+            let open: Vec<usize> = (0..values.len()).filter(|&i| pi[i] > 0.0 && pi[i] < 1.0).collect();
+            let left = n.min(selected.len() + open.len()) - selected.len();
+            selected.extend(sampford(&mut rng, &open, &pi, left)?);
+            (selected, pi)
+        }
+    };
+    Ok(PpsSelection { scheme, selected, inclusion_probabilities })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn inclusion_probabilities_and_sizes() {
+        let values = [5.0, 10.0, 20.0, 40.0, 0.0, 300.0, 25.0];
+        assert!(matches!(pps_select(&values, 3, PpsScheme::Systematic, 7), Err(MusError::InvalidInput(_))));
+        for scheme in [PpsScheme::RandomWithReplacement, PpsScheme::ModifiedSieve, PpsScheme::StratifiedSieve, PpsScheme::Sampford] {
+            let sel = pps_select(&values, 3, scheme, 7).expect("select");
+            assert_eq!(sel.inclusion_probabilities[4], 0.0);
+            assert!(!sel.selected.contains(&4));
+            assert!(sel.inclusion_probabilities.iter().all(|p| (0.0..=1.0).contains(p)));
+        }
+        // Item 5 is a certainty; the other two draws are spread over the rest (T = 100)
+        let sel = pps_select(&values, 3, PpsScheme::Sampford, 7).expect("sampford");
+        assert_abs_diff_eq!(sel.inclusion_probabilities[5], 1.0);
+        assert_abs_diff_eq!(sel.inclusion_probabilities[3], 0.8, epsilon = 1e-12);
+        assert_abs_diff_eq!(sel.inclusion_probabilities.iter().sum::<f64>(), 3.0, epsilon = 1e-12);
+        assert_eq!(sel.distinct().len(), 3);
+        assert!(sel.selected.contains(&5));
+    }
+
+    #[test]
+    fn without_replacement_frequencies_match_inclusion_probabilities() {
+        let values = [1.0, 2.0, 3.0, 4.0, 5.0];
+        let pi = pps_inclusion_probabilities(&values, 2);
+        let open: Vec<usize> = (0..values.len()).collect();
+        let reps = 20_000;
+        let (mut sampford_hits, mut brewer_hits) = ([0usize; 5], [0usize; 5]);
+        for seed in 0..reps {
+            let sel = pps_select(&values, 2, PpsScheme::Sampford, seed).expect("sampford");
+            assert_eq!(sel.distinct().len(), 2);
+            sel.selected.iter().for_each(|&i| sampford_hits[i] += 1);
+            let draw = brewer(&mut StdRng::seed_from_u64(seed), &open, &pi, 2).expect("brewer");
+            assert_ne!(draw[0], draw[1]);
+            draw.iter().for_each(|&i| brewer_hits[i] += 1);
+        }
+        for i in 0..values.len() {
+            assert_abs_diff_eq!(sampford_hits[i] as f64 / reps as f64, pi[i], epsilon = 0.015);
+            assert_abs_diff_eq!(brewer_hits[i] as f64 / reps as f64, pi[i], epsilon = 0.015);
+        }
+    }
+
+    #[test]
+    fn modified_sieve_takes_n_items_with_pps_probabilities() {
+        // Item 20 is a certainty (20·400/610 ≥ 1); the other 4 draws spread over T = 210
+        let values: Vec<f64> = (1..=20).map(f64::from).chain([400.0, 0.0]).collect();
+        let reps = 20_000;
+        let mut hits = vec![0usize; values.len()];
+        for seed in 0..reps {
+            let sel = pps_select(&values, 5, PpsScheme::ModifiedSieve, seed).expect("sieve");
+            assert_eq!(sel.selected.len(), 5);
+            assert_eq!(sel.distinct().len(), 5);
+            assert_eq!(sel.selected[0], 20);
+            sel.selected.iter().for_each(|&i| hits[i] += 1);
+        }
+        let sel = pps_select(&values, 5, PpsScheme::ModifiedSieve, 0).expect("sieve");
+        let pi = &sel.inclusion_probabilities;
+        assert_abs_diff_eq!(pi[20], 1.0);
+        assert_eq!(pi[21], 0.0);
+        assert_abs_diff_eq!(pi[9], 4.0 * 10.0 / 210.0, epsilon = 1e-12);
+        assert_abs_diff_eq!(pi.iter().sum::<f64>(), 5.0, epsilon = 1e-12);
+        assert_eq!(hits[21], 0);
+        for i in 0..values.len() {
+            assert_abs_diff_eq!(hits[i] as f64 / reps as f64, pi[i], epsilon = 0.025);
+        }
+        // Asking for more items than there are positive values takes them all
+        let all = pps_select(&[3.0, 0.0, 1.0], 5, PpsScheme::ModifiedSieve, 1).expect("all");
+        assert_eq!(all.distinct(), [0, 2]);
+    }
+}
@@ -0,0 +1,231 @@
+//! Stratification by amount bands and allocation of a total sample size.
+//!
+//! Strata are half-open bands `[lower, upper)` on the book value; the first band is
+//! unbounded below and the last unbounded above. Empty bands are dropped.
+
+use crate::MusError;
+
+/// How stratum boundaries are chosen.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StratumBoundaries {
+    /// Explicit cut points (sorted and de-duplicated); `k` cuts give at most `k + 1` strata.
+    Fixed(Vec<f64>),
+    /// `k` strata holding roughly equal total book value.
+    EqualValue(usize),
+    /// `k` strata by the Dalenius–Hodges cumulative √f rule.
+    CumulativeSqrtF(usize),
+}
+
+/// How a total sample size is split over strata.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Allocation {
+    /// `n_h ∝ N_h`.
+    #[default]
+    Proportional,
+    /// `n_h ∝ N_h · S_h` (Neyman); falls back to proportional when every `S_h` is zero.
+    Neyman,
+    /// `n_h ∝` stratum book value.
+    Value,
+}
+
+#[derive(Debug, Clone)]
+pub struct Stratum {
+    pub lower: f64,
+    pub upper: f64,
+    /// Indices into the stratified values, in original order.
+    pub indices: Vec<usize>,
+    pub book_value: f64,
+    pub std_dev: f64,
+}
+
+/// Number of equal-width classes used by the cumulative √f rule per requested stratum.
+const SQRT_F_CLASSES_PER_STRATUM: usize = 10;
+
+fn cut_points(values: &[f64], boundaries: &StratumBoundaries) -> Result<Vec<f64>, MusError> {
+    let mut cuts = match boundaries {
+        StratumBoundaries::Fixed(cuts) => {
+            if cuts.iter().any(|c| !c.is_finite()) {
+                return Err(MusError::InvalidInput("cut points must be finite".into()));
+            }
+            cuts.clone()
+        }
+        StratumBoundaries::EqualValue(k) | StratumBoundaries::CumulativeSqrtF(k) if *k == 0 => {
+            return Err(MusError::InvalidInput("number of strata must be > 0".into()));
+        }
+        StratumBoundaries::EqualValue(k) => {
+            let mut sorted = values.to_vec();
+            sorted.sort_by(|a, b| a.total_cmp(b));
+            let total: f64 = sorted.iter().map(|v| v.max(0.0)).sum();
+            let mut cuts = Vec::with_capacity(k - 1);
+            let mut cum = 0.0;
+            let mut j = 1;
+            for (i, v) in sorted.iter().enumerate() {
+                cum += v.max(0.0);
+                while j < *k && cum >= total * j as f64 / *k as f64 {
+                    if let Some(&next) = sorted.get(i + 1) {
+                        cuts.push(next);
+                    }
+                    j += 1;
+                }
+            }
+            cuts
+        }
+        StratumBoundaries::CumulativeSqrtF(k) => {
+            let lo = values.iter().copied().fold(f64::INFINITY, f64::min);
+            let hi = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            if hi <= lo {
+                return Ok(Vec::new());
+            }
+            let classes = k * SQRT_F_CLASSES_PER_STRATUM;
+            let width = (hi - lo) / classes as f64;
+            let mut freq = vec![0usize; classes];
+            for &v in values {
+                let c = (((v - lo) / width) as usize).min(classes - 1);
+                freq[c] += 1;
+            }
+            let cum: Vec<f64> = freq
+                .iter()
+                .scan(0.0, |acc, &f| {
+                    *acc += (f as f64).sqrt();
+                    Some(*acc)
+                })
+                .collect();
+            let step = cum[classes - 1] / *k as f64;
+            (1..*k)
+                .map(|j| {
+                    let target = step * j as f64;
+                    let c = (0..classes)
+                        .min_by(|&a, &b| (cum[a] - target).abs().total_cmp(&(cum[b] - target).abs()))
+                        .unwrap_or(0);
+                    lo + width * (c + 1) as f64
+                })
+                .collect()
+        }
+    };
+    cuts.sort_by(|a, b| a.total_cmp(b));
+    cuts.dedup();
+    Ok(cuts)
+}
+
+fn std_dev(values: &[f64], indices: &[usize]) -> f64 {
+    if indices.len() < 2 {
+        return 0.0;
+    }
+    let n = indices.len() as f64;
+    let mean = indices.iter().map(|&i| values[i]).sum::<f64>() / n;
+    let ss: f64 = indices.iter().map(|&i| (values[i] - mean).powi(2)).sum();
+    (ss / (n - 1.0)).sqrt()
+}
+
+/// Split `values` into amount bands.
+pub fn stratify(values: &[f64], boundaries: &StratumBoundaries) -> Result<Vec<Stratum>, MusError> {
+    if values.is_empty() {
+        return Err(MusError::InvalidInput("data must contain at least one item".into()));
+    }
+    if values.iter().any(|v| !v.is_finite()) {
+        return Err(MusError::InvalidInput("book values must be finite".into()));
+    }
+    let cuts = cut_points(values, boundaries)?;
+    let mut buckets: Vec<Vec<usize>> = vec![Vec::new(); cuts.len() + 1];
+    for (i, &v) in values.iter().enumerate() {
+        buckets[cuts.partition_point(|&c| c <= v)].push(i);
+    }
+    Ok(buckets
+        .into_iter()
+        .enumerate()
+        .filter(|(_, idx)| !idx.is_empty())
+        .map(|(h, indices)| Stratum {
+            lower: if h == 0 { f64::NEG_INFINITY } else { cuts[h - 1] },
+            upper: cuts.get(h).copied().unwrap_or(f64::INFINITY),
+            book_value: indices.iter().map(|&i| values[i]).sum(),
+            std_dev: std_dev(values, &indices),
+            indices,
+        })
+        .collect())
+}
+
+/// Allocate a total sample size `n` over `strata` (largest-remainder rounding, each
+/// stratum capped at its size; the surplus of capped strata goes to the others).
+pub fn allocate_sample(strata: &[Stratum], n: usize, allocation: Allocation) -> Result<Vec<usize>, MusError> {
+    let sizes: Vec<usize> = strata.iter().map(|s| s.indices.len()).collect();
+    let total_size: usize = sizes.iter().sum();
+    if n >= total_size {
+        return Ok(sizes);
+    }
+    let mut weights: Vec<f64> = match allocation {
+        Allocation::Proportional => sizes.iter().map(|&s| s as f64).collect(),
+        Allocation::Neyman => strata.iter().map(|s| s.indices.len() as f64 * s.std_dev).collect(),
+        Allocation::Value => strata.iter().map(|s| s.book_value.max(0.0)).collect(),
+    };
+    if weights.iter().any(|w| !w.is_finite()) {
+        return Err(MusError::Calculation("allocation weights must be finite".into()));
+    }
+    if weights.iter().sum::<f64>() <= 0.0 {
+        weights = sizes.iter().map(|&s| s as f64).collect();
+    }
+
+    // Strata whose quota exceeds their size are taken in full; repeat on the rest.
+    let mut alloc = vec![0usize; strata.len()];
+    let mut full = vec![false; strata.len()];
+    let mut quotas = vec![0.0f64; strata.len()];
+    loop {
+        let left = n - full.iter().zip(&sizes).filter(|(f, _)| **f).map(|(_, s)| s).sum::<usize>();
+        let w_open: f64 = weights.iter().zip(&full).filter(|(_, f)| !**f).map(|(w, _)| w).sum();
+        let mut changed = false;
+        for h in 0..strata.len() {
+            if full[h] {
+                continue;
+            }
+            quotas[h] = if w_open > 0.0 { left as f64 * weights[h] / w_open } else { 0.0 };
+            if quotas[h] >= sizes[h] as f64 {
+                full[h] = true;
+                alloc[h] = sizes[h];
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    let mut assigned: usize = 0;
+    for h in 0..strata.len() {
+        if !full[h] {
+            alloc[h] = quotas[h].floor() as usize;
+        }
+        assigned += alloc[h];
+    }
+    let mut order: Vec<usize> = (0..strata.len()).filter(|&h| !full[h] && alloc[h] < sizes[h]).collect();
+    order.sort_by(|&a, &b| (quotas[b] - quotas[b].floor()).total_cmp(&(quotas[a] - quotas[a].floor())));
+    for h in order.into_iter().take(n.saturating_sub(assigned)) {
+        alloc[h] += 1;
+    }
+    Ok(alloc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bands_and_allocation() {
+        let values: Vec<f64> = (1..=100).map(|i| (i * i) as f64).collect();
+        let strata = stratify(&values, &StratumBoundaries::Fixed(vec![1000.0, 5000.0])).expect("stratify");
+        assert_eq!(strata.iter().map(|s| s.indices.len()).collect::<Vec<_>>(), vec![31, 39, 30]);
+
+        let strata = stratify(&values, &StratumBoundaries::CumulativeSqrtF(4)).expect("stratify");
+        assert_eq!(strata.len(), 4);
+        assert_eq!(strata.iter().map(|s| s.indices.len()).sum::<usize>(), 100);
+
+        let strata = stratify(&values, &StratumBoundaries::EqualValue(3)).expect("stratify");
+        assert_eq!(strata.len(), 3);
+        for alloc in [Allocation::Proportional, Allocation::Neyman, Allocation::Value] {
+            let n = allocate_sample(&strata, 40, alloc).expect("allocate");
+            assert_eq!(n.iter().sum::<usize>(), 40);
+            assert!(n.iter().zip(&strata).all(|(&k, s)| k <= s.indices.len()));
+        }
+        // The top band (fewest items, most value) is capped at its size under value allocation
+        let n = allocate_sample(&strata, 60, Allocation::Value).expect("allocate");
+        assert_eq!(n[2], strata[2].indices.len());
+        assert_eq!(n.iter().sum::<usize>(), 60);
+    }
+}
@@ -0,0 +1,256 @@
+//! Classical variables sampling: mean-per-unit, difference and ratio estimation
+//!
+//! Sample size follows the AICPA audit sampling guide formula with an optional finite
+//! population correction; evaluation uses the normal approximation.
+//! See Design.md for the formulas.
+
+use crate::{Conclusion, MusError};
+use statrs::distribution::{ContinuousCDF, Normal};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VariablesEstimator {
+    /// Mean-per-unit: population audited value = N × sample mean of audited values.
+    #[default]
+    MeanPerUnit,
+    /// Difference: book value + N × mean difference (audited − book).
+    Difference,
+    /// Ratio: book value × (sum audited / sum book) of the sample.
+    Ratio,
+}
+
+#[derive(Debug, Clone)]
+pub struct VariablesPlanningOptions {
+    pub population_size: usize,
+    /// Expected standard deviation of the audited values (MPU) or of the differences (difference, ratio).
+    pub expected_std_dev: f64,
+    pub tolerable_misstatement: f64,
+    pub expected_misstatement: f64,
+    /// Risk of incorrect acceptance (beta, one-sided).
+    pub risk_incorrect_acceptance: f64,
+    /// Risk of incorrect rejection (alpha, two-sided).
+    pub risk_incorrect_rejection: f64,
+    pub finite_population_correction: bool,
+}
+
+impl Default for VariablesPlanningOptions {
+    fn default() -> Self {
+        Self {
+            population_size: 0,
+            expected_std_dev: f64::NAN,
+            tolerable_misstatement: f64::NAN,
+            expected_misstatement: 0.0,
+            risk_incorrect_acceptance: 0.10,
+            risk_incorrect_rejection: 0.10,
+            finite_population_correction: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VariablesPlan {
+    pub population_size: usize,
+    /// Sample size before the finite population correction.
+    pub n_uncorrected: usize,
+    pub n: usize,
+    pub z_acceptance: f64,
+    pub z_rejection: f64,
+    /// Planned allowance for sampling risk: `TM − EM` scaled by `z_α / (z_α + z_β)`.
+    pub planned_allowance: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct VariablesEvaluationOptions {
+    pub estimator: VariablesEstimator,
+    pub population_size: usize,
+    /// Recorded book value of the whole population.
+    pub book_value: f64,
+    /// Two-sided confidence level of the interval (1 − risk of incorrect rejection).
+    pub confidence_level: f64,
+    pub tolerable_misstatement: f64,
+}
+
+impl Default for VariablesEvaluationOptions {
+    fn default() -> Self {
+        Self {
+            estimator: VariablesEstimator::default(),
+            population_size: 0,
+            book_value: f64::NAN,
+            confidence_level: 0.90,
+            tolerable_misstatement: f64::NAN,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VariablesEvaluation {
+    pub estimator: VariablesEstimator,
+    pub n: usize,
+    /// Estimated audited value of the population.
+    pub point_estimate: f64,
+    /// Book value − point estimate (positive = overstatement).
+    pub projected_misstatement: f64,
+    pub standard_error: f64,
+    /// Achieved allowance for sampling risk: `z × standard_error`.
+    pub precision: f64,
+    pub lower: f64,
+    pub upper: f64,
+    /// Accept when both ends of the interval imply a misstatement within ± tolerable misstatement.
+    pub conclusion: Conclusion,
+}
+
+fn std_normal_quantile(p: f64) -> Result<f64, MusError> {
+    let norm = Normal::new(0.0, 1.0).map_err(|e| MusError::Calculation(format!("normal: {e}")))?;
+    Ok(norm.inverse_cdf(p))
+}
+
+fn sample_std_dev(values: &[f64]) -> f64 {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt()
+}
+
+pub fn variables_planning(opts: VariablesPlanningOptions) -> Result<VariablesPlan, MusError> {
+    let big_n = opts.population_size;
+    if big_n == 0 {
+        return Err(MusError::InvalidInput("population_size must be > 0".into()));
+    }
+    if !(opts.expected_std_dev.is_finite() && opts.expected_std_dev >= 0.0) {
+        return Err(MusError::InvalidInput("expected_std_dev must be finite and >= 0".into()));
+    }
+    if !(opts.tolerable_misstatement.is_finite() && opts.tolerable_misstatement > 0.0) {
+        return Err(MusError::InvalidInput("tolerable_misstatement must be finite and > 0".into()));
+    }
+    if !(opts.expected_misstatement.is_finite() && opts.expected_misstatement >= 0.0) {
+        return Err(MusError::InvalidInput("expected_misstatement must be finite and >= 0".into()));
+    }
+    if opts.expected_misstatement >= opts.tolerable_misstatement {
+        return Err(MusError::InvalidInput("expected_misstatement must be < tolerable_misstatement".into()));
+    }
+    for (name, risk) in [
+        ("risk_incorrect_acceptance", opts.risk_incorrect_acceptance),
+        ("risk_incorrect_rejection", opts.risk_incorrect_rejection),
+    ] {
+        if !(risk > 0.0 && risk < 1.0) {
+            return Err(MusError::InvalidInput(format!("{name} must be in (0,1)")));
+        }
+    }
+    let z_beta = std_normal_quantile(1.0 - opts.risk_incorrect_acceptance)?;
+    let z_alpha = std_normal_quantile(1.0 - opts.risk_incorrect_rejection / 2.0)?;
+    let margin = opts.tolerable_misstatement - opts.expected_misstatement;
+    let n_prime = (big_n as f64 * opts.expected_std_dev * (z_alpha + z_beta) / margin).powi(2);
+    let n_corrected = if opts.finite_population_correction { n_prime / (1.0 + n_prime / big_n as f64) } else { n_prime };
+    let n_uncorrected = (n_prime.ceil() as usize).clamp(2, big_n);
+    let n = (n_corrected.ceil() as usize).clamp(2, big_n);
+    Ok(VariablesPlan {
+        population_size: big_n,
+        n_uncorrected,
+        n,
+        z_acceptance: z_beta,
+        z_rejection: z_alpha,
+        planned_allowance: margin * z_alpha / (z_alpha + z_beta),
+    })
+}
+
+pub fn variables_evaluation(book_values: &[f64], audited_values: &[f64], opts: VariablesEvaluationOptions) -> Result<VariablesEvaluation, MusError> {
+    let n = book_values.len();
+    if audited_values.len() != n {
+        return Err(MusError::InvalidInput(format!("audited_values must match book_values ({} != {})", audited_values.len(), n)));
+    }
+    if n < 2 {
+        return Err(MusError::InvalidInput("sample must contain at least two items".into()));
+    }
+    if opts.population_size < n {
+        return Err(MusError::InvalidInput("population_size must be >= sample size".into()));
+    }
+    if book_values.iter().chain(audited_values).any(|v| !v.is_finite()) || !opts.book_value.is_finite() {
+        return Err(MusError::InvalidInput("book and audited values must be finite".into()));
+    }
+    if !(opts.confidence_level > 0.0 && opts.confidence_level < 1.0) {
+        return Err(MusError::InvalidInput("confidence_level must be in (0,1)".into()));
+    }
+    if !(opts.tolerable_misstatement.is_finite() && opts.tolerable_misstatement > 0.0) {
+        return Err(MusError::InvalidInput("tolerable_misstatement must be finite and > 0".into()));
+    }
+    let big_n = opts.population_size as f64;
+    let nf = n as f64;
+    let (point_estimate, residuals): (f64, Vec<f64>) = match opts.estimator {
+        VariablesEstimator::MeanPerUnit => (big_n * audited_values.iter().sum::<f64>() / nf, audited_values.to_vec()),
+        VariablesEstimator::Difference => {
+            let diffs: Vec<f64> = audited_values.iter().zip(book_values).map(|(a, b)| a - b).collect();
+            (opts.book_value + big_n * diffs.iter().sum::<f64>() / nf, diffs)
+        }
+        VariablesEstimator::Ratio => {
+            let sum_book: f64 = book_values.iter().sum();
+            if sum_book == 0.0 {
+                return Err(MusError::InvalidInput("ratio estimation needs a non-zero sample book value".into()));
+            }
+            let ratio = audited_values.iter().sum::<f64>() / sum_book;
+            let resid: Vec<f64> = audited_values.iter().zip(book_values).map(|(a, b)| a - ratio * b).collect();
+            (opts.book_value * ratio, resid)
+        }
+    };
+    let s = match opts.estimator {
+        // Residuals around the ratio line already have zero mean
+        VariablesEstimator::Ratio => (residuals.iter().map(|e| e * e).sum::<f64>() / (nf - 1.0)).sqrt(),
+        _ => sample_std_dev(&residuals),
+    };
+    let fpc = (1.0 - nf / big_n).max(0.0).sqrt();
+    let standard_error = big_n * s / nf.sqrt() * fpc;
+    let z = std_normal_quantile(1.0 - (1.0 - opts.confidence_level) / 2.0)?;
+    let precision = z * standard_error;
+    let lower = point_estimate - precision;
+    let upper = point_estimate + precision;
+    let worst = (opts.book_value - lower).abs().max((opts.book_value - upper).abs());
+    let conclusion = if worst <= opts.tolerable_misstatement { Conclusion::Accept } else { Conclusion::Reject };
+    Ok(VariablesEvaluation {
+        estimator: opts.estimator,
+        n,
+        point_estimate,
+        projected_misstatement: opts.book_value - point_estimate,
+        standard_error,
+        precision,
+        lower,
+        upper,
+        conclusion,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn planning_matches_aicpa_formula() {
+        // N=1000, S=50, TM=15000, EM=0, beta=5%, alpha=10%: n' = (1000·50·(1.645+1.645)/15000)² ≈ 120.3
+        let plan = variables_planning(VariablesPlanningOptions {
+            population_size: 1000,
+            expected_std_dev: 50.0,
+            tolerable_misstatement: 15_000.0,
+            risk_incorrect_acceptance: 0.05,
+            risk_incorrect_rejection: 0.10,
+            ..Default::default()
+        })
+        .expect("planning");
+        assert_eq!(plan.n_uncorrected, 121);
+        assert_eq!(plan.n, 108);
+        assert_abs_diff_eq!(plan.planned_allowance, 7_500.0, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn difference_and_ratio_estimates() {
+        let book: Vec<f64> = (1..=20).map(|i| 100.0 * i as f64).collect();
+        let opts = VariablesEvaluationOptions { population_size: 200, book_value: 210_000.0, tolerable_misstatement: 5_000.0, ..Default::default() };
+        // Every item overstated by 10: projected misstatement N × 10 with no spread
+        let audited: Vec<f64> = book.iter().map(|b| b - 10.0).collect();
+        let diff = variables_evaluation(&book, &audited, VariablesEvaluationOptions { estimator: VariablesEstimator::Difference, ..opts.clone() }).expect("difference");
+        assert_abs_diff_eq!(diff.projected_misstatement, 2_000.0, epsilon = 1e-9);
+        assert_abs_diff_eq!(diff.standard_error, 0.0, epsilon = 1e-9);
+        assert_eq!(diff.conclusion, Conclusion::Accept);
+        // Proportional 5% overstatement is captured exactly by the ratio estimator
+        let audited: Vec<f64> = book.iter().map(|b| b * 0.95).collect();
+        let ratio = variables_evaluation(&book, &audited, VariablesEvaluationOptions { estimator: VariablesEstimator::Ratio, ..opts }).expect("ratio");
+        assert_abs_diff_eq!(ratio.projected_misstatement, 10_500.0, epsilon = 1e-6);
+        assert_eq!(ratio.conclusion, Conclusion::Reject);
+    }
+}